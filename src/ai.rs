@@ -0,0 +1,382 @@
+use bevy::{
+    app::{Plugin, Update},
+    ecs::{
+        component::Component,
+        query::{Has, Or, With, Without},
+        schedule::IntoScheduleConfigs,
+        system::{Query, Res},
+    },
+    math::{vec3, Quat, Vec3},
+    state::condition::in_state,
+    time::Time,
+    transform::components::Transform,
+};
+use bevy_rapier3d::dynamics::Velocity;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arena::CurrentArena,
+    ball::Ball,
+    constants::BALL_RADIUS,
+    game_state::{GameplaySet, MatchState},
+    goals::PlayerType,
+    player::{Player1, Player2, PlayerInput},
+    sprint::SprintState,
+};
+
+const PLAYER_RADIUS: f32 = 0.5;
+/// How far ahead the ball's movement is guessed
+const BALL_LEAD_TIME: f32 = 0.3;
+/// Sprint left over for defending
+const SPRINT_RESERVE: f32 = 3.0;
+/// Opponents closer to the own goal than this are dangerous
+const DANGER_DISTANCE: f32 = 18.0;
+/// Lined up closer to the ball than this, the computer charges a kick
+const SHOOTING_RANGE: f32 = 4.0;
+/// The charged kick is released once the ball is this close
+const KICK_RELEASE_DISTANCE: f32 = 1.5;
+/// Attackers closer than this get tackled
+const TACKLE_RANGE: f32 = 4.0;
+
+/// How well the computer plays
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Medium => "Medium",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Difficulty::Easy => Difficulty::Medium,
+            Difficulty::Medium => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+
+    /// Seconds between two looks at the ball
+    fn reaction_time(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.4,
+            Difficulty::Medium => 0.2,
+            Difficulty::Hard => 0.05,
+        }
+    }
+
+    /// Largest angle in radians a shot misses the goal center by
+    fn aim_error(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.6,
+            Difficulty::Medium => 0.3,
+            Difficulty::Hard => 0.08,
+        }
+    }
+}
+
+/// What the computer sees of the match when it decides what to do next
+#[derive(Clone, Copy, Debug)]
+pub struct AiView {
+    pub own: Vec3,
+    pub opponent: Vec3,
+    pub ball: Vec3,
+    pub ball_velocity: Vec3,
+    /// Sprint seconds left
+    pub sprint: f32,
+    /// Middle of the goal line the computer defends
+    pub own_goal: Vec3,
+    /// Middle of the goal line the computer shoots at
+    pub opponent_goal: Vec3,
+}
+
+/// Where the computer wants to go, until it looks at the ball again
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AiPlan {
+    pub target: Vec3,
+    pub sprint: bool,
+    /// Charging a kick, it is released when a plan without kick follows
+    pub kick: bool,
+    pub tackle: bool,
+}
+
+impl AiView {
+    /// The ball is on the computer's half and the opponent gets there first
+    fn under_pressure(&self, ball: Vec3) -> bool {
+        let near_own_goal = ball.distance(self.own_goal) < DANGER_DISTANCE;
+        let opponent_first = self.opponent.distance(ball) < self.own.distance(ball);
+        near_own_goal && opponent_first
+    }
+
+    /// Stands between ball and own goal, or goes behind the ball to push it at the opponent goal.
+    /// `aim_error` turns the shot away from the goal center, in radians.
+    pub fn plan(&self, aim_error: f32) -> AiPlan {
+        let ball = flat(self.ball + self.ball_velocity * BALL_LEAD_TIME);
+        let own = flat(self.own);
+
+        if self.under_pressure(ball) {
+            let opponent = flat(self.opponent);
+            if own.distance(opponent) < TACKLE_RANGE {
+                return AiPlan {
+                    target: opponent,
+                    sprint: false,
+                    kick: false,
+                    tackle: true,
+                };
+            }
+            let own_goal = self.own_goal;
+            let guard = own_goal + (ball - own_goal).normalize_or_zero() * 4.0;
+            return AiPlan {
+                target: guard,
+                sprint: own.distance(guard) > 4.0 && self.sprint > 0.0,
+                kick: false,
+                tackle: false,
+            };
+        }
+
+        let shot =
+            Quat::from_rotation_y(aim_error) * (self.opponent_goal - ball).normalize_or_zero();
+        let behind_ball = ball - shot * (BALL_RADIUS + PLAYER_RADIUS + 0.5);
+        let lined_up = (ball - own).normalize_or_zero().dot(shot) > 0.8;
+
+        let target = if lined_up {
+            // Run through the ball
+            ball + shot * 2.0
+        } else if (own - ball).dot(shot) > 0.0 {
+            // In front of the ball, go around it instead of pushing it back
+            let side = shot.cross(Vec3::Y);
+            let side = if (own - ball).dot(side) >= 0.0 {
+                side
+            } else {
+                -side
+            };
+            behind_ball + side * (BALL_RADIUS + PLAYER_RADIUS + 1.0)
+        } else {
+            behind_ball
+        };
+        let distance = own.distance(ball);
+        let shooting_range = lined_up && distance < SHOOTING_RANGE;
+        AiPlan {
+            target,
+            sprint: shooting_range && self.sprint > SPRINT_RESERVE,
+            kick: shooting_range && distance > KICK_RELEASE_DISTANCE,
+            tackle: false,
+        }
+    }
+}
+
+fn flat(position: Vec3) -> Vec3 {
+    vec3(position.x, 0.0, position.z)
+}
+
+/// Moves towards the plan, slowing down shortly before reaching it
+pub fn follow(own: Vec3, plan: &AiPlan) -> PlayerInput {
+    let offset = plan.target - flat(own);
+    let movement = offset.normalize_or_zero() * (offset.length() / 2.0).min(1.0);
+    PlayerInput {
+        movement,
+        sprint: plan.sprint,
+        kick: plan.kick,
+        tackle: plan.tackle,
+    }
+}
+
+/// Lets the computer control this player instead of a keyboard or gamepad
+#[derive(Component, Debug)]
+pub struct AiController {
+    pub difficulty: Difficulty,
+    next_look: f32,
+    plan: Option<AiPlan>,
+    rng: u64,
+}
+
+impl AiController {
+    pub fn new(difficulty: Difficulty, seed: u64) -> Self {
+        AiController {
+            difficulty,
+            next_look: 0.0,
+            plan: None,
+            // Xorshift never leaves zero
+            rng: seed.max(1),
+        }
+    }
+
+    /// Uniform in -1..1, deterministic so simulated matches can be replayed
+    fn random(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+    }
+
+    /// Plans again once the reaction time passed and returns the input towards the current plan
+    pub fn update(&mut self, view: &AiView, now: f32) -> PlayerInput {
+        if self.plan.is_none() || now >= self.next_look {
+            let aim_error = self.random() * self.difficulty.aim_error();
+            self.plan = Some(view.plan(aim_error));
+            self.next_look = now + self.difficulty.reaction_time();
+        }
+        self.plan
+            .map(|plan| follow(view.own, &plan))
+            .unwrap_or_default()
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn drive_ai(
+    time: Res<Time>,
+    arena: Res<CurrentArena>,
+    mut q_ai: Query<(
+        &mut AiController,
+        &mut PlayerInput,
+        &Transform,
+        &SprintState,
+        Has<Player1>,
+    )>,
+    q_players: Query<(&Transform, Has<Player1>), Or<(With<Player1>, With<Player2>)>>,
+    q_ball: Query<(&Transform, &Velocity), (With<Ball>, Without<AiController>)>,
+) {
+    let Ok((ball, ball_velocity)) = q_ball.single() else {
+        return;
+    };
+    let now = time.elapsed_secs();
+    for (mut ai, mut input, transform, sprint, is_player_1) in q_ai.iter_mut() {
+        let Some((opponent, _)) = q_players.iter().find(|(_, p1)| *p1 != is_player_1) else {
+            continue;
+        };
+        let (scorer, defender) = if is_player_1 {
+            (PlayerType::First, PlayerType::Second)
+        } else {
+            (PlayerType::Second, PlayerType::First)
+        };
+        let view = AiView {
+            own: transform.translation,
+            opponent: opponent.translation,
+            ball: ball.translation,
+            ball_velocity: ball_velocity.linvel,
+            sprint: sprint.remaining(),
+            own_goal: arena.goal(defender),
+            opponent_goal: arena.goal(scorer),
+        };
+        *input = ai.update(&view, now);
+    }
+}
+
+/// Writes the `PlayerInput` of every player with an `AiController`
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_systems(
+            Update,
+            drive_ai
+                .run_if(in_state(MatchState::Playing))
+                .in_set(GameplaySet::Input),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{vec3, Vec3};
+
+    use super::{AiController, AiView, Difficulty};
+
+    fn view(own: Vec3, ball: Vec3) -> AiView {
+        AiView {
+            own,
+            opponent: vec3(25.0, 1.0, 10.0),
+            ball,
+            ball_velocity: Vec3::ZERO,
+            sprint: 10.0,
+            own_goal: vec3(-28.5, 0.0, 0.0),
+            opponent_goal: vec3(28.5, 0.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn plan_when_behind_ball_then_runs_through_it_towards_opponent_goal() {
+        // given
+        let view = view(vec3(-2.0, 1.0, 0.0), vec3(0.0, 0.5, 0.0));
+
+        // when
+        let plan = view.plan(0.0);
+
+        // then
+        assert!(plan.target.x > 0.0);
+        assert!(plan.target.z.abs() < 0.01);
+    }
+
+    #[test]
+    fn plan_when_in_front_of_ball_then_goes_around_it() {
+        // given
+        let view = view(vec3(2.0, 1.0, 0.0), vec3(0.0, 0.5, 0.0));
+
+        // when
+        let plan = view.plan(0.0);
+
+        // then
+        assert!(plan.target.x < 0.0);
+        assert!(plan.target.z.abs() > 1.0);
+        assert!(!plan.sprint);
+    }
+
+    #[test]
+    fn plan_when_opponent_attacks_own_goal_then_guards_it() {
+        // given
+        let mut view = view(vec3(10.0, 1.0, 0.0), vec3(-15.0, 0.5, 0.0));
+        view.opponent = vec3(-13.0, 1.0, 0.0);
+
+        // when
+        let plan = view.plan(0.0);
+
+        // then
+        assert!(plan.target.x < -20.0);
+        assert!(plan.sprint);
+    }
+
+    #[test]
+    fn update_when_within_reaction_time_then_keeps_plan() {
+        // given
+        let mut ai = AiController::new(Difficulty::Easy, 7);
+        let first = ai.update(&view(vec3(-2.0, 1.0, 0.0), vec3(0.0, 0.5, 0.0)), 0.0);
+
+        // when
+        let second = ai.update(&view(vec3(-2.0, 1.0, 0.0), vec3(0.0, 0.5, 8.0)), 0.1);
+
+        // then
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn random_when_drawn_often_then_stays_within_bounds() {
+        let mut ai = AiController::new(Difficulty::Hard, 1);
+        assert!((0..1000)
+            .map(|_| ai.random())
+            .all(|r| (-1.0..1.0).contains(&r)));
+    }
+
+    #[test]
+    fn plan_when_attacker_is_close_near_own_goal_then_tackles_it() {
+        // given
+        let mut view = view(vec3(-16.0, 1.0, 2.0), vec3(-15.0, 0.5, 0.0));
+        view.opponent = vec3(-14.0, 1.0, 0.0);
+
+        // when
+        let plan = view.plan(0.0);
+
+        // then
+        assert!(plan.tackle);
+        assert_eq!(plan.target, vec3(-14.0, 0.0, 0.0));
+    }
+}
@@ -1,13 +1,20 @@
-use std::f32::consts::FRAC_PI_2;
-
-use crate::colors::{GREY, RED};
+use crate::{
+    arena_features::{spawn_feature, ArenaFeature},
+    colors::{GREY, RED, WHITE},
+    goals::PlayerType,
+};
 use bevy::prelude::*;
 use bevy::{
-    app::{Plugin, Startup},
-    asset::Assets,
-    ecs::system::{Commands, ResMut},
-    math::{vec3, Quat},
+    app::{Plugin, PreUpdate, Startup},
+    asset::{Asset, Assets},
+    ecs::{
+        resource::Resource,
+        schedule::common_conditions::resource_changed,
+        system::{Commands, ResMut},
+    },
+    math::{vec3, Vec2, Vec3},
     pbr::{NotShadowCaster, StandardMaterial},
+    reflect::TypePath,
     render::mesh::Mesh,
     transform::components::Transform,
 };
@@ -15,113 +22,368 @@ use bevy_rapier3d::{
     dynamics::RigidBody,
     geometry::{Collider, ColliderMassProperties},
 };
+use serde::{Deserialize, Serialize};
+
+/// Thickness of the invisible lid on top of the walls
+const CEILING_THICKNESS: f32 = 1.0;
+
+/// A straight piece of wall, seen from above
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WallSegment {
+    /// Center on the ground, x and z
+    pub center: Vec2,
+    /// Length along x and z
+    pub size: Vec2,
+}
+
+/// A goal sensor behind a goal line parallel to the z axis
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GoalDefinition {
+    /// The player who scores by shooting the ball into this goal
+    pub scorer: PlayerType,
+    /// Middle of the goal line, x and z. The goal opens towards the arena center.
+    pub center: Vec2,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl GoalDefinition {
+    /// Where the goal lies along x, +1 on the right side of the arena
+    pub fn side(&self) -> f32 {
+        if self.center.x >= 0.0 {
+            1.0
+        } else {
+            -1.0
+        }
+    }
+}
+
+/// Where the players and the ball start at every kickoff
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpawnPoints {
+    pub player1: Vec3,
+    pub player2: Vec3,
+    pub ball: Vec3,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ObstacleShape {
+    Block { size: Vec3 },
+    Pillar { radius: f32, height: f32 },
+}
+
+/// Something fixed on the field that players and ball bounce off
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Obstacle {
+    pub center: Vec3,
+    pub shape: ObstacleShape,
+}
+
+/// Everything that makes up a field, loaded from `assets/arenas/*.arena.ron`
+#[derive(Asset, TypePath, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ArenaDefinition {
+    pub name: String,
+    /// Length of the floor along x and z
+    pub size: Vec2,
+    /// How high the walls are drawn
+    pub wall_height: f32,
+    /// Height of the invisible ceiling, walls reach up to it for the physics
+    #[serde(default = "default_ceiling")]
+    pub ceiling: f32,
+    pub walls: Vec<WallSegment>,
+    /// One goal for each player to score in
+    pub goals: Vec<GoalDefinition>,
+    pub spawns: SpawnPoints,
+    #[serde(default)]
+    pub obstacles: Vec<Obstacle>,
+    /// Bumpers, rotating bars, moving platforms and boost pads
+    #[serde(default)]
+    pub features: Vec<ArenaFeature>,
+}
+
+fn default_ceiling() -> f32 {
+    10.0
+}
+
+/// The classic field, built into the game so headless matches and servers need no asset files
+pub const DEFAULT_ARENA: &str = include_str!("../assets/arenas/default.arena.ron");
+
+impl Default for ArenaDefinition {
+    fn default() -> Self {
+        ArenaDefinition::parse(DEFAULT_ARENA.as_bytes()).expect("the default arena is valid")
+    }
+}
 
-const MAP_SIZE_HALF: f32 = 15.0;
+impl ArenaDefinition {
+    /// Reads an arena from RON, it needs a goal for each player
+    pub fn parse(bytes: &[u8]) -> Result<Self, ArenaError> {
+        let arena: ArenaDefinition = ron::de::from_bytes(bytes).map_err(ArenaError::Ron)?;
+        for scorer in [PlayerType::First, PlayerType::Second] {
+            if !arena.goals.iter().any(|g| g.scorer == scorer) {
+                return Err(ArenaError::MissingGoal(scorer));
+            }
+        }
+        Ok(arena)
+    }
+
+    /// Middle of the goal line `scorer` shoots at, on the ground
+    pub fn goal(&self, scorer: PlayerType) -> Vec3 {
+        self.goals
+            .iter()
+            .find(|g| g.scorer == scorer)
+            .map_or(Vec3::ZERO, |g| vec3(g.center.x, 0.0, g.center.y))
+    }
+
+    pub fn half_size(&self) -> Vec2 {
+        self.size / 2.0
+    }
+}
+
+/// Why an arena file could not be used
+#[derive(Debug)]
+pub enum ArenaError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+    MissingGoal(PlayerType),
+}
+
+impl std::fmt::Display for ArenaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArenaError::Io(e) => write!(f, "could not read arena: {e}"),
+            ArenaError::Ron(e) => write!(f, "invalid arena: {e}"),
+            ArenaError::MissingGoal(scorer) => write!(f, "arena has no goal for {scorer:?}"),
+        }
+    }
+}
+
+impl std::error::Error for ArenaError {}
 
-fn build_arena_walls(
+/// The arena matches are played in, changing it rebuilds the field
+#[derive(Resource, Clone, Debug, Default, Deref, DerefMut)]
+pub struct CurrentArena(pub ArenaDefinition);
+
+/// Part of the field, replaced when the arena changes
+#[derive(Component)]
+pub struct ArenaPart;
+
+#[derive(Component)]
+struct Wall {
+    size: Vec2,
+}
+
+#[derive(Component)]
+struct Ground {
+    size: Vec2,
+}
+
+#[derive(Component)]
+struct ObstacleBody(ObstacleShape);
+
+fn build_arena(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    arena: Res<CurrentArena>,
+    q_parts: Query<Entity, With<ArenaPart>>,
 ) {
-    let wall_height = 4.0;
-
-    let mesh = meshes.add(Rectangle::new(MAP_SIZE_HALF * 2.0, wall_height * 2.0));
-    let mesh_long = meshes.add(Rectangle::new(MAP_SIZE_HALF * 4.0, wall_height * 2.0));
-    let material = materials.add(StandardMaterial {
-        base_color: RED,
-        ..Default::default()
-    });
-
-    let transforms_with_mesh = [
-        (
-            Transform::from_translation(vec3(MAP_SIZE_HALF * 2.0, wall_height * 0.5, 0.0))
-                .with_rotation(Quat::from_rotation_y(-FRAC_PI_2)),
-            mesh.clone(),
-        ),
-        (
-            Transform::from_translation(vec3(-MAP_SIZE_HALF * 2.0, wall_height * 0.5, 0.0))
-                .with_rotation(Quat::from_rotation_y(FRAC_PI_2)),
-            mesh.clone(),
-        ),
-        (
-            Transform::from_translation(vec3(0.0, wall_height * 0.5, -MAP_SIZE_HALF)),
-            mesh_long.clone(),
-        ),
-        (
-            Transform::from_translation(vec3(0.0, wall_height * 0.5, MAP_SIZE_HALF)),
-            mesh_long.clone(),
+    for part in &q_parts {
+        commands.entity(part).despawn();
+    }
+
+    commands.spawn((
+        ArenaPart,
+        Ground { size: arena.size },
+        Collider::cuboid(arena.size.x, 0.1, arena.size.y),
+        Transform::from_xyz(0.0, -0.1, 0.0),
+    ));
+
+    // Keeps high balls from flying out of the field
+    commands.spawn((
+        ArenaPart,
+        Collider::cuboid(
+            arena.size.x * 0.5,
+            CEILING_THICKNESS * 0.5,
+            arena.size.y * 0.5,
         ),
-    ];
+        RigidBody::Fixed,
+        Transform::from_xyz(0.0, arena.ceiling + CEILING_THICKNESS * 0.5, 0.0),
+    ));
+
+    for wall in &arena.walls {
+        commands.spawn((
+            ArenaPart,
+            Wall { size: wall.size },
+            Collider::cuboid(wall.size.x * 0.5, arena.ceiling, wall.size.y * 0.5),
+            RigidBody::Fixed,
+            ColliderMassProperties::Mass(100.0),
+            Transform::from_xyz(wall.center.x, 0.0, wall.center.y),
+        ));
+    }
 
-    for (t, m) in transforms_with_mesh {
+    for obstacle in &arena.obstacles {
+        let collider = match obstacle.shape {
+            ObstacleShape::Block { size } => {
+                Collider::cuboid(size.x * 0.5, size.y * 0.5, size.z * 0.5)
+            }
+            ObstacleShape::Pillar { radius, height } => Collider::cylinder(height * 0.5, radius),
+        };
         commands.spawn((
-            NotShadowCaster,
-            Mesh3d(m.clone()),
-            Transform::from(t.clone()),
-            MeshMaterial3d(material.clone()),
+            ArenaPart,
+            ObstacleBody(obstacle.shape.clone()),
+            collider,
+            RigidBody::Fixed,
+            Transform::from_translation(obstacle.center),
         ));
     }
+
+    for feature in &arena.features {
+        spawn_feature(&mut commands, feature);
+    }
 }
 
-fn build_ground(
+#[allow(clippy::type_complexity)]
+fn add_arena_meshes(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    arena: Res<CurrentArena>,
+    q_ground: Query<(Entity, &Ground), Added<Ground>>,
+    q_walls: Query<(Entity, &Wall), Added<Wall>>,
+    q_obstacles: Query<(Entity, &ObstacleBody), Added<ObstacleBody>>,
 ) {
-    /* Create the ground. */
-    commands
-        .spawn(Collider::cuboid(200.0, 0.1, 100.0))
-        .insert(Transform::from_xyz(0.0, -0.1, 0.0));
+    for (entity, ground) in &q_ground {
+        commands
+            .entity(entity)
+            .insert(Visibility::default())
+            .with_child((
+                Mesh3d(meshes.add(Plane3d::default().mesh().size(ground.size.x, ground.size.y))),
+                MeshMaterial3d(materials.add(StandardMaterial {
+                    base_color: GREY,
+                    perceptual_roughness: 0.8,
+                    ..default()
+                })),
+                // The collider top is the floor
+                Transform::from_xyz(0.0, 0.1, 0.0),
+            ));
+    }
 
-    commands.spawn((
-        Mesh3d(meshes.add(Plane3d::default().mesh().size(60.0, 30.0))),
-        MeshMaterial3d(materials.add(StandardMaterial {
-            base_color: GREY,
-            perceptual_roughness: 0.8,
-            ..default()
-        })),
-    ));
+    if !q_walls.is_empty() {
+        let wall_material = materials.add(StandardMaterial {
+            base_color: RED,
+            ..Default::default()
+        });
+        for (entity, wall) in &q_walls {
+            commands
+                .entity(entity)
+                .insert(Visibility::default())
+                .with_child((
+                    NotShadowCaster,
+                    Mesh3d(meshes.add(Cuboid::new(
+                        wall.size.x,
+                        arena.wall_height * 2.0,
+                        wall.size.y,
+                    ))),
+                    MeshMaterial3d(wall_material.clone()),
+                    Transform::from_xyz(0.0, arena.wall_height * 0.5, 0.0),
+                ));
+        }
+    }
+
+    for (entity, ObstacleBody(shape)) in &q_obstacles {
+        let mesh = match *shape {
+            ObstacleShape::Block { size } => meshes.add(Cuboid::from_size(size)),
+            ObstacleShape::Pillar { radius, height } => meshes.add(Cylinder::new(radius, height)),
+        };
+        commands.entity(entity).insert((
+            Mesh3d(mesh),
+            MeshMaterial3d(materials.add(StandardMaterial {
+                base_color: WHITE,
+                ..Default::default()
+            })),
+        ));
+    }
 }
 
-fn build_collider_walls(mut commands: Commands) {
-    let wall_thickness = 0.5;
-    let wall_thickness_half = wall_thickness * 0.5;
-    let transforms_with_collider = [
-        (
-            Transform::from_translation(vec3(MAP_SIZE_HALF * 2.0 + wall_thickness_half, 0.0, 0.0)),
-            Collider::cuboid(wall_thickness, 10., MAP_SIZE_HALF),
-        ),
-        (
-            Transform::from_translation(vec3(-MAP_SIZE_HALF * 2.0 - wall_thickness_half, 0.0, 0.0)),
-            Collider::cuboid(wall_thickness, 10., MAP_SIZE_HALF),
-        ),
-        (
-            Transform::from_translation(vec3(0.0, 0.0, MAP_SIZE_HALF + wall_thickness_half)),
-            Collider::cuboid(MAP_SIZE_HALF * 2.0, 10., wall_thickness),
-        ),
-        (
-            Transform::from_translation(vec3(0.0, 0.0, -MAP_SIZE_HALF - wall_thickness_half)),
-            Collider::cuboid(MAP_SIZE_HALF * 2.0, 10., wall_thickness),
-        ),
+fn spawn_light(mut commands: Commands) {
+    let light_poses = [
+        vec3(20.0, 20.0, -20.0),
+        vec3(-20.0, 20.0, -20.0),
+        vec3(-20.0, 20.0, 20.0),
+        vec3(20.0, 20.0, 20.0),
     ];
 
-    for (t, c) in transforms_with_collider {
+    light_poses.into_iter().for_each(|t| {
         commands.spawn((
-            c,
-            RigidBody::Fixed,
-            ColliderMassProperties::Mass(100.0),
-            Transform::from(t.clone()),
+            PointLight {
+                intensity: 10000000.0,
+                range: 70.,
+                shadows_enabled: true,
+                ..default()
+            },
+            Transform::from_translation(t),
         ));
-    }
+    })
 }
 
+/// Builds floor, walls, ceiling, obstacles and features of the `CurrentArena` and rebuilds them when it changes
 pub struct ArenaPlugin;
 
 impl Plugin for ArenaPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_systems(
-            Startup,
-            (build_arena_walls, build_collider_walls, build_ground),
+        app.init_resource::<CurrentArena>().add_systems(
+            PreUpdate,
+            build_arena.run_if(resource_changed::<CurrentArena>),
         );
     }
 }
+
+pub struct ArenaRenderPlugin;
+
+impl Plugin for ArenaRenderPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_systems(Startup, spawn_light)
+            .add_systems(Update, add_arena_meshes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArenaDefinition, ArenaError};
+    use crate::goals::PlayerType;
+
+    #[test]
+    fn default_when_parsed_then_goal_for_each_player_on_its_attacking_side() {
+        // given
+        let arena = ArenaDefinition::default();
+
+        // when
+        let player1_target = arena.goal(PlayerType::First);
+        let player2_target = arena.goal(PlayerType::Second);
+
+        // then
+        assert!(player1_target.x > 0.0);
+        assert_eq!(player2_target.x, -player1_target.x);
+        assert!(player1_target.x < arena.half_size().x);
+    }
+
+    #[test]
+    fn parse_when_goal_missing_then_error() {
+        // given
+        let ron = r#"(
+            name: "Broken",
+            size: (10.0, 10.0),
+            wall_height: 1.0,
+            walls: [],
+            goals: [(scorer: First, center: (4.0, 0.0), width: 2.0, height: 1.0)],
+            spawns: (player1: (-1.0, 1.0, 0.0), player2: (1.0, 1.0, 0.0), ball: (0.0, 1.0, 0.0)),
+        )"#;
+
+        // when
+        let result = ArenaDefinition::parse(ron.as_bytes());
+
+        // then
+        assert!(matches!(
+            result,
+            Err(ArenaError::MissingGoal(PlayerType::Second))
+        ));
+    }
+}
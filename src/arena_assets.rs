@@ -0,0 +1,205 @@
+use bevy::{
+    app::{Plugin, Startup, Update},
+    asset::{
+        io::Reader, AssetApp, AssetEvent, AssetLoader, AssetServer, Assets, Handle, LoadContext,
+    },
+    ecs::{
+        change_detection::DetectChanges,
+        event::EventReader,
+        resource::Resource,
+        system::{Commands, Res, ResMut},
+    },
+};
+
+use crate::arena::{ArenaDefinition, ArenaError, CurrentArena};
+
+/// Every arena that can be picked in the menu, the first one is the default
+pub const ARENAS: [&str; 3] = [
+    "arenas/default.arena.ron",
+    "arenas/pocket.arena.ron",
+    "arenas/funhouse.arena.ron",
+];
+
+/// Reads `.arena.ron` files into `ArenaDefinition`s
+#[derive(Default)]
+struct ArenaLoader;
+
+impl AssetLoader for ArenaLoader {
+    type Asset = ArenaDefinition;
+    type Settings = ();
+    type Error = ArenaError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<ArenaDefinition, ArenaError> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(ArenaError::Io)?;
+        ArenaDefinition::parse(&bytes)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["arena.ron"]
+    }
+}
+
+/// Handles of all `ARENAS`, in the same order
+#[derive(Resource)]
+pub struct ArenaCatalog(pub Vec<Handle<ArenaDefinition>>);
+
+impl ArenaCatalog {
+    /// Name of the arena at `index`, `None` while it is still loading
+    pub fn name<'a>(&self, index: usize, arenas: &'a Assets<ArenaDefinition>) -> Option<&'a str> {
+        let handle = self.0.get(index)?;
+        arenas.get(handle).map(|arena| arena.name.as_str())
+    }
+}
+
+/// Index into the `ArenaCatalog` of the arena to play in
+#[derive(Resource, Clone, Copy, Default, Debug, PartialEq)]
+pub struct SelectedArena(pub usize);
+
+impl SelectedArena {
+    /// Picks the next arena of `ARENAS`, or the previous one going backwards
+    pub fn cycle(&mut self, forward: bool) {
+        let len = ARENAS.len();
+        let index = self.0 % len;
+        self.0 = if forward {
+            (index + 1) % len
+        } else {
+            (index + len - 1) % len
+        };
+    }
+}
+
+fn load_arenas(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ArenaCatalog(
+        ARENAS.iter().map(|path| asset_server.load(*path)).collect(),
+    ));
+}
+
+/// Plays in the selected arena once it is loaded, again after each time its file is reloaded
+fn apply_selected_arena(
+    selected: Res<SelectedArena>,
+    catalog: Res<ArenaCatalog>,
+    arenas: Res<Assets<ArenaDefinition>>,
+    mut asset_events: EventReader<AssetEvent<ArenaDefinition>>,
+    mut current: ResMut<CurrentArena>,
+) {
+    let Some(handle) = catalog.0.get(selected.0) else {
+        return;
+    };
+    let reloaded = asset_events
+        .read()
+        .any(|ev| ev.is_loaded_with_dependencies(handle) || ev.is_modified(handle));
+    if !reloaded && !selected.is_changed() {
+        return;
+    }
+    if let Some(arena) = arenas.get(handle) {
+        // Rebuilding the field only when something differs keeps the running match untouched
+        if current.0 != *arena {
+            current.0 = arena.clone();
+        }
+    }
+}
+
+/// Reloads arena files edited while the game runs, bevy's own file watcher is not built in.
+/// A file that is half written fails to parse and keeps the arena as it was, a deleted file
+/// is left alone until it is back.
+#[cfg(all(not(target_arch = "wasm32"), debug_assertions))]
+mod hot_reload {
+    use std::time::SystemTime;
+
+    use bevy::{
+        asset::{io::file::FileAssetReader, AssetServer},
+        ecs::system::{Local, Res},
+        log::warn,
+        time::Time,
+    };
+
+    use super::ARENAS;
+
+    /// Seconds between two looks at the files
+    const POLL_INTERVAL: f32 = 1.0;
+
+    fn modified(path: &str) -> Option<SystemTime> {
+        let path = FileAssetReader::get_base_path().join("assets").join(path);
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    pub(super) fn reload_edited_arenas(
+        time: Res<Time>,
+        asset_server: Res<AssetServer>,
+        mut since_poll: Local<f32>,
+        mut last_modified: Local<Vec<Option<SystemTime>>>,
+    ) {
+        *since_poll += time.delta_secs();
+        if *since_poll < POLL_INTERVAL && !last_modified.is_empty() {
+            return;
+        }
+        *since_poll = 0.0;
+        let now = ARENAS.iter().map(|path| modified(path)).collect::<Vec<_>>();
+        if !last_modified.is_empty() {
+            for ((path, before), after) in ARENAS.iter().zip(last_modified.iter()).zip(&now) {
+                if before == after {
+                    continue;
+                }
+                match after {
+                    Some(_) => asset_server.reload(*path),
+                    None => warn!("Arena file {path} is gone, keeping the loaded arena"),
+                }
+            }
+        }
+        *last_modified = now;
+    }
+}
+
+/// Loads the arena files, lets the menu pick one of them and reloads them when edited
+pub struct ArenaAssetPlugin;
+
+impl Plugin for ArenaAssetPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_asset::<ArenaDefinition>()
+            .register_asset_loader(ArenaLoader)
+            .init_resource::<SelectedArena>()
+            .add_systems(Startup, load_arenas)
+            .add_systems(Update, apply_selected_arena);
+        #[cfg(all(not(target_arch = "wasm32"), debug_assertions))]
+        app.add_systems(Update, hot_reload::reload_edited_arenas);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SelectedArena, ARENAS};
+    use crate::arena::{ArenaDefinition, DEFAULT_ARENA};
+
+    #[test]
+    fn cycle_when_going_backwards_from_first_then_last() {
+        // given
+        let mut selected = SelectedArena::default();
+
+        // when
+        selected.cycle(false);
+
+        // then
+        assert_eq!(selected.0, ARENAS.len() - 1);
+        selected.cycle(true);
+        assert_eq!(selected, SelectedArena::default());
+    }
+
+    #[test]
+    fn arenas_when_parsed_then_all_valid_and_first_is_built_in() {
+        for path in ARENAS {
+            let text = std::fs::read_to_string(format!("assets/{path}")).unwrap();
+            assert!(ArenaDefinition::parse(text.as_bytes()).is_ok(), "{path}");
+        }
+        let first = std::fs::read_to_string(format!("assets/{}", ARENAS[0])).unwrap();
+        assert_eq!(first, DEFAULT_ARENA);
+    }
+}
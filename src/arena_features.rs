@@ -0,0 +1,404 @@
+use bevy::prelude::{Cuboid, Cylinder, Mesh3d, MeshMaterial3d, Plane3d, Visibility};
+use bevy::{
+    app::{Plugin, Update},
+    asset::Assets,
+    color::Color,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::EventReader,
+        query::{Added, Or, With, Without},
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    math::{vec3, Vec2, Vec3},
+    pbr::StandardMaterial,
+    render::mesh::{Mesh, Meshable},
+    state::condition::in_state,
+    time::Time,
+    transform::components::Transform,
+};
+use bevy_rapier3d::{
+    dynamics::{RigidBody, Velocity},
+    geometry::{ActiveEvents, Collider, Restitution, Sensor},
+    pipeline::CollisionEvent,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arena::ArenaPart,
+    ball::Ball,
+    colors::{GREEN, ORANGE, WHITE},
+    game_state::{GameplaySet, MatchState},
+    player::{Player1, Player2},
+    sprint::SprintState,
+    tackle::TackleState,
+};
+
+const BUMPER_HEIGHT: f32 = 2.0;
+const BAR_HEIGHT: f32 = 1.0;
+const BAR_THICKNESS: f32 = 0.5;
+/// Boost pads lie flat on the floor, the sensor reaches up to the players
+const PAD_SENSOR_HEIGHT: f32 = 1.0;
+/// Seconds bumped or flung players cannot steer, so the push is not braked right away
+const LAUNCH_TIME: f32 = 0.3;
+
+/// What a boost pad does to a player running over it
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Boost {
+    /// Fills the sprint bar
+    Refill,
+    /// Launches the player where it faces
+    Fling { strength: f32 },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FeatureKind {
+    /// A round post that pushes ball and players away
+    Bumper {
+        radius: f32,
+        strength: f32,
+    },
+    /// A bar spinning around its center, in radians per second
+    RotatingBar {
+        length: f32,
+        speed: f32,
+    },
+    /// A block moving along `path`, given relative to the feature center, and back to the start
+    MovingPlatform {
+        size: Vec3,
+        path: Vec<Vec3>,
+        speed: f32,
+    },
+    BoostPad {
+        size: Vec2,
+        boost: Boost,
+    },
+}
+
+/// Something on the field that moves or acts on ball and players
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ArenaFeature {
+    pub center: Vec3,
+    pub kind: FeatureKind,
+}
+
+#[derive(Component)]
+pub struct Bumper {
+    radius: f32,
+    strength: f32,
+}
+
+#[derive(Component)]
+pub struct RotatingBar {
+    length: f32,
+    speed: f32,
+}
+
+#[derive(Component, Clone)]
+pub struct MovingPlatform {
+    size: Vec3,
+    /// Waypoints in world space, the platform returns to the first one after the last
+    path: Vec<Vec3>,
+    speed: f32,
+    /// Distance along `path` so far, part of the rollback state like the platform's transform
+    travelled: f32,
+}
+
+#[derive(Component)]
+pub struct BoostPad {
+    size: Vec2,
+    boost: Boost,
+}
+
+/// Where something moving at constant speed along the closed `path` is after `distance`
+pub fn point_on_path(path: &[Vec3], distance: f32) -> Vec3 {
+    let segments = path.iter().zip(path.iter().cycle().skip(1));
+    let length = segments.clone().map(|(a, b)| a.distance(*b)).sum::<f32>();
+    if length <= 0.0 {
+        return path.first().copied().unwrap_or(Vec3::ZERO);
+    }
+    let mut left = distance.rem_euclid(length);
+    for (a, b) in segments {
+        let segment = a.distance(*b);
+        // Repeated points give a segment without a direction to lerp along
+        if segment <= f32::EPSILON {
+            continue;
+        }
+        if left <= segment {
+            return a.lerp(*b, left / segment);
+        }
+        left -= segment;
+    }
+    path[0]
+}
+
+/// Adds the collider and behavior of `feature` as a part of the field
+pub(crate) fn spawn_feature(commands: &mut Commands, feature: &ArenaFeature) {
+    let mut entity = commands.spawn((ArenaPart, Transform::from_translation(feature.center)));
+    match &feature.kind {
+        FeatureKind::Bumper { radius, strength } => {
+            entity.insert((
+                Bumper {
+                    radius: *radius,
+                    strength: *strength,
+                },
+                RigidBody::Fixed,
+                Collider::cylinder(BUMPER_HEIGHT * 0.5, *radius),
+                Restitution::coefficient(1.0),
+                ActiveEvents::COLLISION_EVENTS,
+            ));
+        }
+        FeatureKind::RotatingBar { length, speed } => {
+            entity.insert((
+                RotatingBar {
+                    length: *length,
+                    speed: *speed,
+                },
+                RigidBody::KinematicPositionBased,
+                Collider::cuboid(length * 0.5, BAR_HEIGHT * 0.5, BAR_THICKNESS * 0.5),
+            ));
+        }
+        FeatureKind::MovingPlatform { size, path, speed } => {
+            let path = path.iter().map(|p| feature.center + *p).collect::<Vec<_>>();
+            entity.insert((
+                Transform::from_translation(point_on_path(&path, 0.0)),
+                MovingPlatform {
+                    size: *size,
+                    path,
+                    speed: *speed,
+                    travelled: 0.0,
+                },
+                RigidBody::KinematicPositionBased,
+                Collider::cuboid(size.x * 0.5, size.y * 0.5, size.z * 0.5),
+            ));
+        }
+        FeatureKind::BoostPad { size, boost } => {
+            entity.insert((
+                BoostPad {
+                    size: *size,
+                    boost: *boost,
+                },
+                RigidBody::Fixed,
+                Collider::cuboid(size.x * 0.5, PAD_SENSOR_HEIGHT * 0.5, size.y * 0.5),
+                Sensor,
+                ActiveEvents::COLLISION_EVENTS,
+            ));
+        }
+    }
+}
+
+fn move_hazards(
+    time: Res<Time>,
+    mut q_bars: Query<(&RotatingBar, &mut Transform)>,
+    mut q_platforms: Query<(&mut MovingPlatform, &mut Transform), Without<RotatingBar>>,
+) {
+    let dt = time.delta_secs();
+    for (bar, mut transform) in &mut q_bars {
+        transform.rotate_y(bar.speed * dt);
+    }
+    for (mut platform, mut transform) in &mut q_platforms {
+        platform.travelled += platform.speed * dt;
+        transform.translation = point_on_path(&platform.path, platform.travelled);
+    }
+}
+
+/// The other entity of a contact that started with one of `q_features`
+fn started_with<'a, T: Component>(
+    ev: &CollisionEvent,
+    q_features: &'a Query<(&T, &Transform)>,
+) -> Option<(&'a T, &'a Transform, Entity)> {
+    let CollisionEvent::Started(first, second, _) = ev else {
+        return None;
+    };
+    if let Ok((feature, transform)) = q_features.get(*first) {
+        Some((feature, transform, *second))
+    } else if let Ok((feature, transform)) = q_features.get(*second) {
+        Some((feature, transform, *first))
+    } else {
+        None
+    }
+}
+
+/// Bumpers push everything that touches them straight away from their center
+#[allow(clippy::type_complexity)]
+fn bump(
+    mut collision_events: EventReader<CollisionEvent>,
+    q_bumpers: Query<(&Bumper, &Transform)>,
+    mut q_bodies: Query<
+        (&Transform, &mut Velocity, Option<&mut TackleState>),
+        Or<(With<Ball>, With<Player1>, With<Player2>)>,
+    >,
+) {
+    for ev in collision_events.read() {
+        let Some((bumper, bumper_transform, other)) = started_with(ev, &q_bumpers) else {
+            continue;
+        };
+        let Ok((transform, mut velocity, tackle)) = q_bodies.get_mut(other) else {
+            continue;
+        };
+        let offset = transform.translation - bumper_transform.translation;
+        let away = vec3(offset.x, 0.0, offset.z).normalize_or_zero();
+        velocity.linvel += away * bumper.strength;
+        if let Some(mut tackle) = tackle {
+            tackle.stun_for(LAUNCH_TIME);
+        }
+    }
+}
+
+/// Refills or flings players running onto a boost pad
+#[allow(clippy::type_complexity)]
+fn boost(
+    mut collision_events: EventReader<CollisionEvent>,
+    q_pads: Query<(&BoostPad, &Transform)>,
+    mut q_players: Query<
+        (
+            &Transform,
+            &mut Velocity,
+            &mut SprintState,
+            &mut TackleState,
+        ),
+        Or<(With<Player1>, With<Player2>)>,
+    >,
+) {
+    for ev in collision_events.read() {
+        let Some((pad, _, other)) = started_with(ev, &q_pads) else {
+            continue;
+        };
+        let Ok((transform, mut velocity, mut sprint, mut tackle)) = q_players.get_mut(other) else {
+            continue;
+        };
+        match pad.boost {
+            Boost::Refill => sprint.resupply(f32::MAX, 0.0),
+            Boost::Fling { strength } => {
+                // `apply_movement` turns the player's +z towards where it moves
+                let facing = transform.rotation * Vec3::Z;
+                velocity.linvel += vec3(facing.x, 0.3, facing.z) * strength;
+                tackle.stun_for(LAUNCH_TIME);
+            }
+        }
+    }
+}
+
+/// Spins the bars, moves the platforms and lets bumpers and boost pads act on contact
+pub struct ArenaFeaturesPlugin;
+
+impl Plugin for ArenaFeaturesPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_systems(
+            Update,
+            (
+                move_hazards.in_set(GameplaySet::Movement),
+                (bump, boost).in_set(GameplaySet::Resolve),
+            )
+                .run_if(in_state(MatchState::Playing)),
+        );
+    }
+}
+
+fn material(
+    materials: &mut Assets<StandardMaterial>,
+    color: Color,
+) -> MeshMaterial3d<StandardMaterial> {
+    MeshMaterial3d(materials.add(StandardMaterial {
+        base_color: color,
+        ..Default::default()
+    }))
+}
+
+#[allow(clippy::type_complexity)]
+fn add_feature_meshes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    q_bumpers: Query<(Entity, &Bumper), Added<Bumper>>,
+    q_bars: Query<(Entity, &RotatingBar), Added<RotatingBar>>,
+    q_platforms: Query<(Entity, &MovingPlatform), Added<MovingPlatform>>,
+    q_pads: Query<(Entity, &BoostPad), Added<BoostPad>>,
+) {
+    for (entity, bumper) in &q_bumpers {
+        commands.entity(entity).insert((
+            Mesh3d(meshes.add(Cylinder::new(bumper.radius, BUMPER_HEIGHT))),
+            material(&mut materials, Color::srgb(1.0, 0.2, 0.8)),
+        ));
+    }
+    for (entity, bar) in &q_bars {
+        commands.entity(entity).insert((
+            Mesh3d(meshes.add(Cuboid::new(bar.length, BAR_HEIGHT, BAR_THICKNESS))),
+            material(&mut materials, WHITE),
+        ));
+    }
+    for (entity, platform) in &q_platforms {
+        commands.entity(entity).insert((
+            Mesh3d(meshes.add(Cuboid::from_size(platform.size))),
+            material(&mut materials, Color::srgb(0.5, 0.5, 0.6)),
+        ));
+    }
+    for (entity, pad) in &q_pads {
+        let color = match pad.boost {
+            Boost::Refill => GREEN,
+            Boost::Fling { .. } => ORANGE,
+        };
+        // The pad is drawn on the floor, below the middle of its sensor
+        commands
+            .entity(entity)
+            .insert(Visibility::default())
+            .with_child((
+                Mesh3d(meshes.add(Plane3d::default().mesh().size(pad.size.x, pad.size.y))),
+                material(&mut materials, color),
+                Transform::from_xyz(0.0, 0.01 - PAD_SENSOR_HEIGHT * 0.5, 0.0),
+            ));
+    }
+}
+
+pub struct ArenaFeaturesRenderPlugin;
+
+impl Plugin for ArenaFeaturesRenderPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_systems(Update, add_feature_meshes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{vec3, Vec3};
+
+    use super::point_on_path;
+
+    #[test]
+    fn point_on_path_when_past_the_end_then_back_towards_start() {
+        // given
+        let path = [Vec3::ZERO, vec3(4.0, 0.0, 0.0)];
+
+        // when
+        let halfway = point_on_path(&path, 2.0);
+        let returning = point_on_path(&path, 6.0);
+        let around = point_on_path(&path, 8.0);
+
+        // then
+        assert_eq!(halfway, vec3(2.0, 0.0, 0.0));
+        assert_eq!(returning, vec3(2.0, 0.0, 0.0));
+        assert_eq!(around, Vec3::ZERO);
+    }
+
+    #[test]
+    fn point_on_path_when_single_point_then_stays() {
+        assert_eq!(point_on_path(&[Vec3::ONE], 3.0), Vec3::ONE);
+    }
+
+    #[test]
+    fn point_on_path_when_point_repeated_then_skips_it() {
+        // given
+        let path = [Vec3::ZERO, Vec3::ZERO, vec3(4.0, 0.0, 0.0)];
+
+        // when
+        let start = point_on_path(&path, 0.0);
+        let halfway = point_on_path(&path, 2.0);
+        let returning = point_on_path(&path, 6.0);
+
+        // then
+        assert_eq!(start, Vec3::ZERO);
+        assert_eq!(halfway, vec3(2.0, 0.0, 0.0));
+        assert_eq!(returning, vec3(2.0, 0.0, 0.0));
+    }
+}
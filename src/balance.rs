@@ -0,0 +1,266 @@
+use std::collections::BTreeMap;
+
+use bevy::{
+    app::App,
+    ecs::{
+        entity::Entity,
+        event::Events,
+        query::{Has, Or, With},
+        world::World,
+    },
+    state::state::{NextState, State},
+};
+use serde::Serialize;
+
+use crate::{
+    ai::{AiController, Difficulty},
+    constants::GAME_TIME,
+    game_state::{GameTime, MatchState},
+    goals::{GoalEvent, PlayerType},
+    player::{Player1, Player2},
+    simulation::{headless_app, TICK_RATE},
+    sprint::SprintState,
+};
+
+/// Stops a match that never ends, about ten times the regular match length
+const MAX_TICKS: u32 = (GAME_TIME as f32 * TICK_RATE * 10.0) as u32;
+
+/// The outcome of one computer vs computer match
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct MatchStats {
+    pub player_1_goals: u32,
+    pub player_2_goals: u32,
+    /// Seconds of play until the first goal, `None` for a goalless match
+    pub first_goal_seconds: Option<f32>,
+    /// Seconds of sprint used by each player
+    pub sprint_seconds: [f32; 2],
+}
+
+/// What a batch of matches looks like on average
+#[derive(Serialize, Debug, PartialEq)]
+pub struct BatchReport {
+    pub matches: usize,
+    pub difficulties: [Difficulty; 2],
+    pub player_1_wins: usize,
+    pub player_2_wins: usize,
+    pub draw_rate: f32,
+    pub average_goals: f32,
+    /// How many matches ended with each score, keyed "player 1 goals-player 2 goals"
+    pub score_distribution: BTreeMap<String, usize>,
+    /// Averaged over the matches with at least one goal
+    pub average_first_goal_seconds: Option<f32>,
+    pub average_sprint_seconds: [f32; 2],
+    pub per_match: Vec<MatchStats>,
+}
+
+fn average(values: impl Iterator<Item = f32>) -> Option<f32> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+    (count > 0).then(|| sum / count as f32)
+}
+
+impl BatchReport {
+    pub fn new(difficulties: [Difficulty; 2], per_match: Vec<MatchStats>) -> Self {
+        let matches = per_match.len();
+        let count = |won: fn(&MatchStats) -> bool| per_match.iter().filter(|m| won(m)).count();
+        let draws = count(|m| m.player_1_goals == m.player_2_goals);
+        let mut score_distribution = BTreeMap::new();
+        for m in &per_match {
+            let score = format!("{}-{}", m.player_1_goals, m.player_2_goals);
+            *score_distribution.entry(score).or_default() += 1;
+        }
+        let goals = per_match
+            .iter()
+            .map(|m| (m.player_1_goals + m.player_2_goals) as f32);
+        BatchReport {
+            matches,
+            difficulties,
+            player_1_wins: count(|m| m.player_1_goals > m.player_2_goals),
+            player_2_wins: count(|m| m.player_2_goals > m.player_1_goals),
+            draw_rate: draws as f32 / matches.max(1) as f32,
+            average_goals: average(goals).unwrap_or_default(),
+            score_distribution,
+            average_first_goal_seconds: average(
+                per_match.iter().filter_map(|m| m.first_goal_seconds),
+            ),
+            average_sprint_seconds: [0, 1].map(|player| {
+                average(per_match.iter().map(|m| m.sprint_seconds[player])).unwrap_or_default()
+            }),
+            per_match,
+        }
+    }
+
+    /// A header and one line with the summary, so reports of several runs can be put together
+    pub fn to_csv(&self) -> String {
+        let scores = self
+            .score_distribution
+            .iter()
+            .map(|(score, count)| format!("{score}:{count}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let first_goal = self
+            .average_first_goal_seconds
+            .map(|s| s.to_string())
+            .unwrap_or_default();
+        format!(
+            "matches,player_1_difficulty,player_2_difficulty,player_1_wins,player_2_wins,\
+            draw_rate,average_goals,average_first_goal_seconds,player_1_sprint_seconds,\
+            player_2_sprint_seconds,score_distribution\n\
+            {},{},{},{},{},{},{},{},{},{},{}\n",
+            self.matches,
+            self.difficulties[0].name(),
+            self.difficulties[1].name(),
+            self.player_1_wins,
+            self.player_2_wins,
+            self.draw_rate,
+            self.average_goals,
+            first_goal,
+            self.average_sprint_seconds[0],
+            self.average_sprint_seconds[1],
+            scores,
+        )
+    }
+}
+
+fn sprint_left(world: &mut World) -> [f32; 2] {
+    let mut left = [0.0; 2];
+    let mut query = world.query::<(&SprintState, Has<Player1>)>();
+    for (sprint, is_player_1) in query.iter(world) {
+        left[if is_player_1 { 0 } else { 1 }] = sprint.remaining();
+    }
+    left
+}
+
+fn seconds_played(world: &mut World) -> f32 {
+    world
+        .query::<&GameTime>()
+        .single(world)
+        .map(|time| time.elapsed().as_secs_f32())
+        .unwrap_or_default()
+}
+
+fn state(app: &App) -> MatchState {
+    *app.world().resource::<State<MatchState>>().get()
+}
+
+/// Plays one full match between two computers in a headless app, as fast as possible.
+/// The same difficulties and seed always give the same match.
+pub fn simulate_match(difficulties: [Difficulty; 2], seed: u64) -> MatchStats {
+    let mut app = headless_app();
+    app.update();
+    app.world_mut()
+        .resource_mut::<NextState<MatchState>>()
+        .set(MatchState::Kickoff);
+    app.update();
+
+    let world = app.world_mut();
+    let players = world
+        .query_filtered::<(Entity, Has<Player1>), Or<(With<Player1>, With<Player2>)>>()
+        .iter(world)
+        .collect::<Vec<_>>();
+    for (entity, is_player_1) in players {
+        let player = if is_player_1 { 0 } else { 1 };
+        let ai = AiController::new(difficulties[player], seed * 2 + player as u64 + 1);
+        world.entity_mut(entity).insert(ai);
+    }
+
+    let mut stats = MatchStats {
+        player_1_goals: 0,
+        player_2_goals: 0,
+        first_goal_seconds: None,
+        sprint_seconds: [0.0; 2],
+    };
+    let mut last_sprint = sprint_left(app.world_mut());
+    // `Points` start over every round, the goals of the whole match are counted as they fall
+    let mut goals = app
+        .world()
+        .resource::<Events<GoalEvent>>()
+        .get_cursor_current();
+    for _ in 0..MAX_TICKS {
+        app.update();
+        let world = app.world_mut();
+        let scored = goals
+            .read(world.resource::<Events<GoalEvent>>())
+            .map(|goal| (goal.player, goal.amount))
+            .collect::<Vec<_>>();
+        for (player, amount) in scored {
+            if stats.first_goal_seconds.is_none() {
+                stats.first_goal_seconds = Some(seconds_played(world));
+            }
+            match player {
+                PlayerType::First => stats.player_1_goals += amount,
+                PlayerType::Second => stats.player_2_goals += amount,
+            }
+        }
+        // The goal that decides the match is already counted
+        if state(&app) == MatchState::MatchOver {
+            break;
+        }
+        let world = app.world_mut();
+        let sprint = sprint_left(world);
+        for player in 0..2 {
+            stats.sprint_seconds[player] += (last_sprint[player] - sprint[player]).max(0.0);
+        }
+        last_sprint = sprint;
+    }
+    stats
+}
+
+/// Plays `matches` matches, each with its own seed starting at `seed`
+pub fn simulate_batch(difficulties: [Difficulty; 2], matches: usize, seed: u64) -> BatchReport {
+    let per_match = (0..matches as u64)
+        .map(|index| simulate_match(difficulties, seed + index))
+        .collect();
+    BatchReport::new(difficulties, per_match)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BatchReport, MatchStats};
+    use crate::ai::Difficulty;
+
+    fn stats(player_1_goals: u32, player_2_goals: u32, first_goal: Option<f32>) -> MatchStats {
+        MatchStats {
+            player_1_goals,
+            player_2_goals,
+            first_goal_seconds: first_goal,
+            sprint_seconds: [2.0, 4.0],
+        }
+    }
+
+    #[test]
+    fn batch_report_when_matches_given_then_summarizes_them() {
+        // given
+        let matches = vec![
+            stats(2, 1, Some(10.0)),
+            stats(0, 0, None),
+            stats(2, 1, Some(30.0)),
+            stats(1, 3, Some(20.0)),
+        ];
+
+        // when
+        let report = BatchReport::new([Difficulty::Hard, Difficulty::Easy], matches);
+
+        // then
+        assert_eq!(report.player_1_wins, 2);
+        assert_eq!(report.player_2_wins, 1);
+        assert_eq!(report.draw_rate, 0.25);
+        assert_eq!(report.average_goals, 2.5);
+        assert_eq!(report.average_first_goal_seconds, Some(20.0));
+        assert_eq!(report.score_distribution["2-1"], 2);
+        assert_eq!(report.average_sprint_seconds, [2.0, 4.0]);
+    }
+
+    #[test]
+    fn to_csv_when_called_then_header_and_one_row_with_same_columns() {
+        // given
+        let report = BatchReport::new([Difficulty::Medium; 2], vec![stats(1, 0, Some(5.0))]);
+
+        // when
+        let csv = report.to_csv();
+
+        // then
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].split(',').count(), lines[1].split(',').count());
+    }
+}
@@ -0,0 +1,234 @@
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    arena::{ArenaDefinition, CurrentArena},
+    constants::BALL_RADIUS,
+    game_state::{GameplaySet, InMatch, MatchState},
+};
+
+// A ball the player can kick around, lol
+#[derive(Component)]
+pub struct Ball;
+
+/// How bouncy and fast the ball is and when it is put back into play
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct BallRules {
+    /// Above 1 every bounce adds energy
+    pub restitution: f32,
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+    pub max_speed: f32,
+    /// Slower than this, above the floor, the ball counts as stuck
+    pub stuck_speed: f32,
+    /// Seconds the ball may be stuck before it is dropped at the center
+    pub stuck_time: f32,
+}
+
+impl Default for BallRules {
+    fn default() -> Self {
+        BallRules {
+            restitution: 0.9,
+            linear_damping: 0.2,
+            angular_damping: 0.5,
+            max_speed: 40.0,
+            stuck_speed: 0.5,
+            stuck_time: 3.0,
+        }
+    }
+}
+
+/// Seconds the ball has been lying somewhere no player can reach it
+#[derive(Component, Clone, Default, Debug, PartialEq)]
+pub struct StuckTime(pub f32);
+
+/// Whether `position` left the field: through the walls, below the floor or above the ceiling
+pub fn out_of_bounds(position: Vec3, arena: &ArenaDefinition) -> bool {
+    let half_size = arena.half_size();
+    position.x.abs() > half_size.x
+        || position.z.abs() > half_size.y
+        || position.y < -BALL_RADIUS
+        || position.y > arena.ceiling
+}
+
+/// The closest point to `position` inside the field where the ball can be dropped
+pub fn legal_spot(position: Vec3, arena: &ArenaDefinition) -> Vec3 {
+    let inside = arena.half_size() - Vec2::splat(BALL_RADIUS * 2.0);
+    vec3(
+        position.x.clamp(-inside.x, inside.x),
+        arena.spawns.ball.y,
+        position.z.clamp(-inside.y, inside.y),
+    )
+}
+
+fn spawn_ball(mut commands: Commands, arena: Res<CurrentArena>, rules: Res<BallRules>) {
+    /* Create the bouncing ball. */
+    commands
+        .spawn(RigidBody::Dynamic)
+        .insert(Ball)
+        .insert(Collider::ball(BALL_RADIUS))
+        .insert(Velocity::default())
+        .insert(ExternalImpulse::default())
+        .insert(ActiveEvents::COLLISION_EVENTS)
+        .insert(Restitution::coefficient(rules.restitution))
+        .insert(Damping {
+            linear_damping: rules.linear_damping,
+            angular_damping: rules.angular_damping,
+        })
+        .insert(StuckTime::default())
+        .insert(Transform::from_translation(arena.spawns.ball))
+        .insert(StateScoped(InMatch));
+}
+
+fn cap_ball_speed(rules: Res<BallRules>, mut q_ball: Query<&mut Velocity, With<Ball>>) {
+    for mut velocity in &mut q_ball {
+        velocity.linvel = velocity.linvel.clamp_length_max(rules.max_speed);
+    }
+}
+
+/// Puts a ball that left the field back at the nearest legal spot and drops a stuck ball at the center
+fn recover_ball(
+    time: Res<Time>,
+    rules: Res<BallRules>,
+    arena: Res<CurrentArena>,
+    mut q_ball: Query<(&mut Transform, &mut Velocity, &mut StuckTime), With<Ball>>,
+) {
+    for (mut transform, mut velocity, mut stuck) in &mut q_ball {
+        let on_floor = transform.translation.y <= BALL_RADIUS * 2.0;
+        if velocity.linvel.length() < rules.stuck_speed && !on_floor {
+            stuck.0 += time.delta_secs();
+        } else {
+            stuck.0 = 0.0;
+        }
+
+        let position = transform.translation;
+        let spot = if out_of_bounds(position, &arena) {
+            legal_spot(position, &arena)
+        } else if stuck.0 >= rules.stuck_time {
+            arena.spawns.ball
+        } else {
+            continue;
+        };
+        transform.translation = spot;
+        *velocity = Velocity::zero();
+        stuck.0 = 0.0;
+    }
+}
+
+fn add_ball_mesh(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    q_ball: Query<Entity, Added<Ball>>,
+) {
+    for entity in q_ball.iter() {
+        let debug_material = materials.add(StandardMaterial {
+            base_color_texture: Some(images.add(uv_debug_texture())),
+            ..default()
+        });
+        commands.entity(entity).insert((
+            Mesh3d(meshes.add(Sphere::new(BALL_RADIUS))),
+            MeshMaterial3d(debug_material),
+        ));
+    }
+}
+
+fn uv_debug_texture() -> Image {
+    const TEXTURE_SIZE: usize = 8;
+
+    let mut palette: [u8; 32] = [
+        255, 102, 159, 255, 255, 159, 102, 255, 236, 255, 102, 255, 121, 255, 102, 255, 102, 255,
+        198, 255, 102, 198, 255, 255, 121, 102, 255, 255, 236, 102, 255, 255,
+    ];
+
+    let mut texture_data = [0; TEXTURE_SIZE * TEXTURE_SIZE * 4];
+    for y in 0..TEXTURE_SIZE {
+        let offset = TEXTURE_SIZE * y * 4;
+        texture_data[offset..(offset + TEXTURE_SIZE * 4)].copy_from_slice(&palette);
+        palette.rotate_right(4);
+    }
+
+    Image::new_fill(
+        Extent3d {
+            width: TEXTURE_SIZE as u32,
+            height: TEXTURE_SIZE as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &texture_data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    )
+}
+
+/// Spawns the ball, keeps its speed in check and puts it back into play when it leaves the field
+pub struct BallPlugin;
+
+impl Plugin for BallPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BallRules>()
+            .add_systems(OnEnter(InMatch), spawn_ball)
+            .add_systems(
+                Update,
+                (cap_ball_speed, recover_ball)
+                    .chain()
+                    .run_if(in_state(MatchState::Playing))
+                    .in_set(GameplaySet::Resolve),
+            );
+    }
+}
+
+pub struct BallRenderPlugin;
+
+impl Plugin for BallRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, add_ball_mesh);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::vec3;
+
+    use super::{legal_spot, out_of_bounds};
+    use crate::arena::ArenaDefinition;
+
+    #[test]
+    fn out_of_bounds_when_through_wall_or_ceiling_then_true() {
+        // given
+        let arena = ArenaDefinition::default();
+        let half_size = arena.half_size();
+
+        // when
+        let inside = out_of_bounds(vec3(0.0, 1.0, 0.0), &arena);
+        let behind_wall = out_of_bounds(vec3(half_size.x + 1.0, 1.0, 0.0), &arena);
+        let above_ceiling = out_of_bounds(vec3(0.0, arena.ceiling + 1.0, 0.0), &arena);
+
+        // then
+        assert!(!inside);
+        assert!(behind_wall);
+        assert!(above_ceiling);
+    }
+
+    #[test]
+    fn legal_spot_when_outside_then_closest_point_inside() {
+        // given
+        let arena = ArenaDefinition::default();
+        let half_size = arena.half_size();
+        let escaped = vec3(half_size.x + 5.0, -3.0, 2.0);
+
+        // when
+        let spot = legal_spot(escaped, &arena);
+
+        // then
+        assert!(!out_of_bounds(spot, &arena));
+        assert!(spot.x > half_size.x - 2.0);
+        assert_eq!(spot.z, 2.0);
+        assert_eq!(spot.y, arena.spawns.ball.y);
+    }
+}
@@ -0,0 +1,222 @@
+use bevy::prelude::{BackgroundColor, Display, Node};
+use bevy::{
+    app::{Plugin, Update},
+    color::Color,
+    ecs::{
+        component::Component,
+        query::{Has, With, Without},
+        resource::Resource,
+        system::{Commands, Query, Res, ResMut},
+    },
+    input::{keyboard::KeyCode, ButtonInput},
+    math::{vec2, vec3, Vec3},
+    state::{state::OnEnter, state_scoped::StateScoped},
+    time::Time,
+    transform::components::{GlobalTransform, Transform},
+    ui::{PositionType, Val},
+};
+use bevy_vector_shapes::prelude::*;
+
+use crate::{
+    camera::MainCamera,
+    colors::{GREEN, GREY, ORANGE, RED},
+    game_state::InMatch,
+    player::Player1,
+    sprint::{ShowBars, SprintState},
+};
+
+const BAR_WIDTH: f32 = 2.0;
+const BAR_HEIGHT: f32 = 0.25;
+/// Above the head of a player
+const BAR_OFFSET: f32 = 1.5;
+/// Times per second an exhausted bar switches between team color and red
+const FLASH_RATE: f32 = 4.0;
+const HUD_BAR_WIDTH: f32 = 200.0;
+const HUD_BAR_HEIGHT: f32 = 16.0;
+
+/// Where the stamina bars are drawn, F2 toggles the corner bars
+#[derive(Resource, Debug)]
+pub struct StaminaBars {
+    pub above_players: bool,
+    pub corners: bool,
+}
+
+impl Default for StaminaBars {
+    fn default() -> Self {
+        StaminaBars {
+            above_players: true,
+            corners: false,
+        }
+    }
+}
+
+fn team_color(is_player_1: bool) -> Color {
+    if is_player_1 {
+        GREEN
+    } else {
+        ORANGE
+    }
+}
+
+/// The team color, alternating with red while the player is exhausted
+pub fn bar_color(team: Color, exhausted: bool, elapsed: f32) -> Color {
+    let flash_off = (elapsed * FLASH_RATE).fract() >= 0.5;
+    if exhausted && flash_off {
+        RED
+    } else {
+        team
+    }
+}
+
+/// Moves a bar filled to `fraction` so it stays aligned to the left end of the full bar
+pub fn fill_offset(fraction: f32, width: f32) -> f32 {
+    -(1.0 - fraction) * width / 2.0
+}
+
+fn draw_world_bars(
+    bars: Res<StaminaBars>,
+    time: Res<Time>,
+    mut painter: ShapePainter,
+    q_camera: Query<&GlobalTransform, With<MainCamera>>,
+    q_players: Query<(&Transform, &SprintState, Has<Player1>), With<ShowBars>>,
+) {
+    if !bars.above_players {
+        return;
+    }
+    let Ok(camera) = q_camera.single() else {
+        return;
+    };
+    let rotation = camera.compute_transform().rotation;
+    for (transform, sprint, is_player_1) in &q_players {
+        let fraction = sprint.fraction();
+        painter.set_translation(transform.translation + Vec3::Y * BAR_OFFSET);
+        painter.set_rotation(rotation);
+        painter.set_color(GREY);
+        painter.rect(vec2(BAR_WIDTH, BAR_HEIGHT));
+
+        // Slightly towards the camera, so the fill is drawn on top of the background
+        painter.translate(vec3(fill_offset(fraction, BAR_WIDTH), 0.0, 0.01));
+        painter.set_color(bar_color(
+            team_color(is_player_1),
+            sprint.is_exhausted(),
+            time.elapsed_secs(),
+        ));
+        painter.rect(vec2(BAR_WIDTH * fraction, BAR_HEIGHT));
+    }
+}
+
+#[derive(Component)]
+struct HudBar;
+
+#[derive(Component)]
+struct HudBarFill(usize);
+
+fn spawn_hud_bars(mut commands: Commands) {
+    for player in 0..2 {
+        let mut node = Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(16.0),
+            width: Val::Px(HUD_BAR_WIDTH),
+            height: Val::Px(HUD_BAR_HEIGHT),
+            display: Display::None,
+            ..Default::default()
+        };
+        if player == 0 {
+            node.left = Val::Px(16.0);
+        } else {
+            node.right = Val::Px(16.0);
+        }
+        commands
+            .spawn((HudBar, node, BackgroundColor(GREY), StateScoped(InMatch)))
+            .with_child((
+                HudBarFill(player),
+                Node {
+                    height: Val::Percent(100.0),
+                    ..Default::default()
+                },
+                BackgroundColor(team_color(player == 0)),
+            ));
+    }
+}
+
+fn toggle_hud_bars(input: Res<ButtonInput<KeyCode>>, mut bars: ResMut<StaminaBars>) {
+    if input.just_pressed(KeyCode::F2) {
+        bars.corners = !bars.corners;
+    }
+}
+
+fn update_hud_bars(
+    bars: Res<StaminaBars>,
+    time: Res<Time>,
+    mut q_roots: Query<&mut Node, With<HudBar>>,
+    mut q_fills: Query<(&HudBarFill, &mut Node, &mut BackgroundColor), Without<HudBar>>,
+    q_players: Query<(&SprintState, Has<Player1>), With<ShowBars>>,
+) {
+    for mut node in &mut q_roots {
+        node.display = if bars.corners {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+    for (sprint, is_player_1) in &q_players {
+        let player = if is_player_1 { 0 } else { 1 };
+        for (fill, mut node, mut color) in &mut q_fills {
+            if fill.0 != player {
+                continue;
+            }
+            node.width = Val::Percent(sprint.fraction() * 100.0);
+            color.0 = bar_color(
+                team_color(is_player_1),
+                sprint.is_exhausted(),
+                time.elapsed_secs(),
+            );
+        }
+    }
+}
+
+/// Draws the stamina of every `ShowBars` player above its head and, toggled with F2, in the
+/// bottom corners of the screen
+pub struct StaminaBarsPlugin;
+
+impl Plugin for StaminaBarsPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<StaminaBars>()
+            .add_systems(OnEnter(InMatch), spawn_hud_bars)
+            .add_systems(Update, (draw_world_bars, toggle_hud_bars, update_hud_bars));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bar_color, fill_offset};
+    use crate::colors::{GREEN, RED};
+
+    #[test]
+    fn fill_offset_when_half_full_then_left_ends_line_up() {
+        // given
+        let width = 2.0;
+
+        // when
+        let offset = fill_offset(0.5, width);
+
+        // then
+        assert_eq!(offset - 0.5 * width / 2.0, -width / 2.0);
+        assert_eq!(fill_offset(1.0, width), 0.0);
+    }
+
+    #[test]
+    fn bar_color_when_exhausted_then_alternates_with_red() {
+        // given
+        let team = GREEN;
+
+        // when
+        let on = bar_color(team, true, 0.0);
+        let off = bar_color(team, true, 0.15);
+
+        // then
+        assert_eq!(on, GREEN);
+        assert_eq!(off, RED);
+        assert_eq!(bar_color(team, false, 0.15), GREEN);
+    }
+}
@@ -0,0 +1,50 @@
+//! Plays computer vs computer matches headless and prints statistics to compare balance changes:
+//! `balance [--matches N] [--difficulty easy|medium|hard] [--player2 easy|medium|hard]
+//! [--seed N] [--format json|csv]`
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    use slatchhammer::{ai::Difficulty, balance::simulate_batch};
+
+    fn difficulty(name: &str) -> Difficulty {
+        Difficulty::ALL
+            .into_iter()
+            .find(|d| d.name().eq_ignore_ascii_case(name))
+            .unwrap_or_else(|| panic!("Unknown difficulty {name}, use easy, medium or hard"))
+    }
+
+    let args = std::env::args().collect::<Vec<_>>();
+    let option = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|index| args.get(index + 1))
+            .map(String::as_str)
+    };
+    let number = |name: &str, default: u64| {
+        option(name).map_or(default, |value| {
+            value
+                .parse()
+                .unwrap_or_else(|_| panic!("{name} needs a number"))
+        })
+    };
+
+    let player_1 = option("--difficulty").map_or(Difficulty::Medium, difficulty);
+    let player_2 = option("--player2").map_or(player_1, difficulty);
+    let matches = number("--matches", 20) as usize;
+    let seed = number("--seed", 0);
+
+    let report = simulate_batch([player_1, player_2], matches, seed);
+    match option("--format").unwrap_or("json") {
+        "csv" => print!("{}", report.to_csv()),
+        "json" => println!(
+            "{}",
+            serde_json::to_string_pretty(&report).expect("The report is always valid JSON")
+        ),
+        other => panic!("Unknown format {other}, use json or csv"),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    panic!("The balance runner only runs natively");
+}
@@ -0,0 +1,20 @@
+//! Headless authoritative server for online matches: `server [address]`
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    use bevy::log::{info, tracing_subscriber};
+    use slatchhammer::{headless_app, network::DEFAULT_SERVER_ADDRESS, server::Server};
+
+    tracing_subscriber::fmt().init();
+    let address = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_SERVER_ADDRESS.to_string());
+    let server = Server::bind(&address, headless_app).expect("Could not bind the server address");
+    info!("Listening on ws://{address}");
+    server.run();
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    panic!("The server only runs natively");
+}
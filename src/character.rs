@@ -0,0 +1,104 @@
+use bevy::ecs::{component::Component, resource::Resource};
+
+use crate::{constants::PLAYER_MOVEMENT_SPEED, sprint::SPRINT_DURATION};
+
+/// How a player moves, collides and kicks, the same for everyone who picks it
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct Character {
+    pub name: &'static str,
+    /// Running speed without sprint
+    pub speed: f32,
+    /// How quickly the velocity follows the input
+    pub acceleration: f32,
+    pub mass: f32,
+    /// Bounciness of the player, balls and opponents bounce off harder
+    pub restitution: f32,
+    /// Radius of the capsule
+    pub radius: f32,
+    /// Sprint seconds of a full bar
+    pub stamina: f32,
+    /// Multiplies the impulse of a kick
+    pub kick_power: f32,
+}
+
+/// Every character that can be picked, the first one is the default
+pub const ROSTER: [Character; 3] = [
+    Character {
+        name: "Allrounder",
+        speed: PLAYER_MOVEMENT_SPEED,
+        acceleration: 10.0,
+        mass: 1.3,
+        restitution: 0.0,
+        radius: 0.5,
+        stamina: SPRINT_DURATION,
+        kick_power: 1.0,
+    },
+    Character {
+        name: "Runner",
+        speed: 12.0,
+        acceleration: 14.0,
+        mass: 0.9,
+        restitution: 0.2,
+        radius: 0.4,
+        stamina: 8.0,
+        kick_power: 0.8,
+    },
+    Character {
+        name: "Hammer",
+        speed: 8.5,
+        acceleration: 7.0,
+        mass: 2.2,
+        restitution: 0.6,
+        radius: 0.65,
+        stamina: 12.0,
+        kick_power: 1.3,
+    },
+];
+
+impl Default for Character {
+    fn default() -> Self {
+        ROSTER[0]
+    }
+}
+
+/// Index into the `ROSTER` for each player, index 0 is player 1
+#[derive(Resource, Clone, Copy, Default, Debug, PartialEq)]
+pub struct SelectedCharacters(pub [usize; 2]);
+
+impl SelectedCharacters {
+    pub fn character(&self, player: usize) -> Character {
+        ROSTER[self.0[player] % ROSTER.len()]
+    }
+
+    /// Picks the next character of the roster for `player`, or the previous one going backwards
+    pub fn cycle(&mut self, player: usize, forward: bool) {
+        let len = ROSTER.len();
+        let index = self.0[player] % len;
+        self.0[player] = if forward {
+            (index + 1) % len
+        } else {
+            (index + len - 1) % len
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Character, SelectedCharacters, ROSTER};
+
+    #[test]
+    fn cycle_when_at_either_end_then_wraps_around() {
+        // given
+        let mut selected = SelectedCharacters::default();
+
+        // when
+        selected.cycle(0, false);
+        selected.cycle(1, true);
+
+        // then
+        assert_eq!(selected.character(0), ROSTER[ROSTER.len() - 1]);
+        assert_eq!(selected.character(1), ROSTER[1]);
+        selected.cycle(0, true);
+        assert_eq!(selected.character(0), Character::default());
+    }
+}
@@ -0,0 +1,257 @@
+use bevy::prelude::{Interaction, Node, Text};
+use bevy::{
+    app::{Plugin, Update},
+    asset::Assets,
+    ecs::{
+        component::Component,
+        query::{Changed, With},
+        resource::Resource,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    input::{gamepad::Gamepad, keyboard::KeyCode, ButtonInput},
+    state::{
+        condition::in_state,
+        state::{NextState, OnEnter},
+    },
+    ui::{AlignItems, FlexDirection, Val},
+};
+
+use crate::{
+    arena::ArenaDefinition,
+    arena_assets::{ArenaCatalog, SelectedArena},
+    character::{Character, SelectedCharacters},
+    controls::{
+        any_gamepad_just_pressed, device_just_pressed, PlayerDevices, GAMEPAD_BACK, GAMEPAD_START,
+    },
+    game_state::MatchState,
+    input_map::{Action, InputMap},
+    match_rules::{MatchRules, RULE_PRESETS},
+    menu::{button, spawn_screen, text, MenuScreen},
+};
+
+/// Picks the previous or next character of a player
+#[derive(Component)]
+struct CycleCharacterButton {
+    player: usize,
+    forward: bool,
+}
+
+#[derive(Component)]
+struct CharacterText(usize);
+
+#[derive(Component)]
+struct KickoffButton;
+
+/// Picks the previous or next arena
+#[derive(Component)]
+struct CycleArenaButton(bool);
+
+#[derive(Component)]
+struct ArenaText;
+
+/// Picks the previous or next of the `RULE_PRESETS`
+#[derive(Component)]
+struct CycleRulesButton(bool);
+
+#[derive(Component)]
+struct RulesText;
+
+/// Index into `RULE_PRESETS` of the rules for the next match
+#[derive(Resource, Default)]
+struct SelectedRules(usize);
+
+fn describe(character: &Character) -> String {
+    format!(
+        "{}: speed {}, mass {}, stamina {}s, kick {}x",
+        character.name, character.speed, character.mass, character.stamina, character.kick_power
+    )
+}
+
+fn setup_character_screen(mut commands: Commands) {
+    spawn_screen(&mut commands, MenuScreen::Characters, |parent| {
+        parent.spawn(text("Choose your character", 48.0));
+        for player in 0..2 {
+            parent.spawn(text(&format!("Player {}", player + 1), 28.0));
+            parent
+                .spawn(Node {
+                    flex_direction: FlexDirection::Row,
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(12.0),
+                    ..Default::default()
+                })
+                .with_children(|row| {
+                    button(
+                        row,
+                        CycleCharacterButton {
+                            player,
+                            forward: false,
+                        },
+                        "<",
+                    );
+                    row.spawn((CharacterText(player), text("", 24.0)));
+                    button(
+                        row,
+                        CycleCharacterButton {
+                            player,
+                            forward: true,
+                        },
+                        ">",
+                    );
+                });
+        }
+        parent.spawn(text("Arena", 28.0));
+        parent
+            .spawn(Node {
+                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::Center,
+                column_gap: Val::Px(12.0),
+                ..Default::default()
+            })
+            .with_children(|row| {
+                button(row, CycleArenaButton(false), "<");
+                row.spawn((ArenaText, text("", 24.0)));
+                button(row, CycleArenaButton(true), ">");
+            });
+        parent.spawn(text("Rules", 28.0));
+        parent
+            .spawn(Node {
+                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::Center,
+                column_gap: Val::Px(12.0),
+                ..Default::default()
+            })
+            .with_children(|row| {
+                button(row, CycleRulesButton(false), "<");
+                row.spawn((RulesText, text("", 24.0)));
+                button(row, CycleRulesButton(true), ">");
+            });
+        button(parent, KickoffButton, "Kick off");
+        parent.spawn(text(
+            "Move left or right to change your character, Tab to change the arena, \
+            R to change the rules, Enter or Start to begin, Esc or Select to go back",
+            20.0,
+        ));
+    });
+}
+
+/// Each player changes their own character with left and right on the device they joined with
+fn cycle_characters(
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    devices: Res<PlayerDevices>,
+    input_map: Res<InputMap>,
+    q_buttons: Query<(&Interaction, &CycleCharacterButton), Changed<Interaction>>,
+    mut selected: ResMut<SelectedCharacters>,
+    mut q_texts: Query<(&CharacterText, &mut Text)>,
+) {
+    for (player, device) in devices.0.iter().enumerate() {
+        let Some(device) = *device else {
+            continue;
+        };
+        let pressed =
+            |action| device_just_pressed(device, player, action, &input_map, &keys, &gamepads);
+        if pressed(Action::MoveLeft) {
+            selected.cycle(player, false);
+        }
+        if pressed(Action::MoveRight) {
+            selected.cycle(player, true);
+        }
+    }
+    for (interaction, button) in &q_buttons {
+        if *interaction == Interaction::Pressed {
+            selected.cycle(button.player, button.forward);
+        }
+    }
+    for (slot, mut slot_text) in &mut q_texts {
+        slot_text.0 = describe(&selected.character(slot.0));
+    }
+}
+
+fn cycle_arenas(
+    keys: Res<ButtonInput<KeyCode>>,
+    q_buttons: Query<(&Interaction, &CycleArenaButton), Changed<Interaction>>,
+    catalog: Res<ArenaCatalog>,
+    arenas: Res<Assets<ArenaDefinition>>,
+    mut selected: ResMut<SelectedArena>,
+    mut q_text: Query<&mut Text, With<ArenaText>>,
+) {
+    if keys.just_pressed(KeyCode::Tab) {
+        selected.cycle(true);
+    }
+    for (interaction, CycleArenaButton(forward)) in &q_buttons {
+        if *interaction == Interaction::Pressed {
+            selected.cycle(*forward);
+        }
+    }
+    for mut arena_text in &mut q_text {
+        arena_text.0 = catalog
+            .name(selected.0, &arenas)
+            .unwrap_or("Loading...")
+            .to_string();
+    }
+}
+
+fn cycle_rules(
+    keys: Res<ButtonInput<KeyCode>>,
+    q_buttons: Query<(&Interaction, &CycleRulesButton), Changed<Interaction>>,
+    mut selected: ResMut<SelectedRules>,
+    mut rules: ResMut<MatchRules>,
+    mut q_text: Query<&mut Text, With<RulesText>>,
+) {
+    let len = RULE_PRESETS.len();
+    if keys.just_pressed(KeyCode::KeyR) {
+        selected.0 = (selected.0 + 1) % len;
+    }
+    for (interaction, CycleRulesButton(forward)) in &q_buttons {
+        if *interaction == Interaction::Pressed {
+            selected.0 = if *forward {
+                (selected.0 + 1) % len
+            } else {
+                (selected.0 + len - 1) % len
+            };
+        }
+    }
+    let (name, preset) = RULE_PRESETS[selected.0];
+    if *rules != preset {
+        *rules = preset;
+    }
+    for mut rules_text in &mut q_text {
+        rules_text.0 = name.to_string();
+    }
+}
+
+fn kick_off(
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    q_button: Query<&Interaction, (Changed<Interaction>, With<KickoffButton>)>,
+    mut next_state: ResMut<NextState<MatchState>>,
+    mut next_screen: ResMut<NextState<MenuScreen>>,
+) {
+    let start = q_button.iter().any(|i| *i == Interaction::Pressed)
+        || keys.just_pressed(KeyCode::Enter)
+        || any_gamepad_just_pressed(&gamepads, GAMEPAD_START);
+    if start {
+        next_state.set(MatchState::Kickoff);
+    } else if keys.just_pressed(KeyCode::Escape)
+        || any_gamepad_just_pressed(&gamepads, GAMEPAD_BACK)
+    {
+        next_screen.set(MenuScreen::Join);
+    }
+}
+
+/// The screen between joining and kickoff where both players pick a character, the arena and
+/// the rules
+pub struct CharacterMenuPlugin;
+
+impl Plugin for CharacterMenuPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<SelectedRules>()
+            .add_systems(OnEnter(MenuScreen::Characters), setup_character_screen)
+            .add_systems(
+                Update,
+                (cycle_characters, cycle_arenas, cycle_rules, kick_off)
+                    .run_if(in_state(MenuScreen::Characters)),
+            );
+    }
+}
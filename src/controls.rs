@@ -0,0 +1,368 @@
+use bevy::{
+    app::{Plugin, Update},
+    ecs::{
+        entity::Entity,
+        query::{Added, Has, Or, With, Without},
+        resource::Resource,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res},
+    },
+    input::{
+        gamepad::{Gamepad, GamepadButton},
+        keyboard::KeyCode,
+        ButtonInput,
+    },
+    math::{vec3, Quat, Vec2, Vec3},
+    state::condition::in_state,
+    transform::components::Transform,
+};
+
+use crate::{
+    ai::{AiController, Difficulty},
+    camera::MainCamera,
+    game_state::{GameplaySet, MatchState},
+    goals::PlayerType,
+    input_map::{Action, InputMap, PlayerBindings},
+    player::{Player1, Player2, PlayerInput},
+    touch::{read_touches, TouchControls, TouchControlsPlugin},
+};
+
+/// Something a player controls their player with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputDevice {
+    /// The keys bound for player 1, WASD and left shift by default
+    KeyboardLeft,
+    /// The keys bound for player 2, arrow keys and right shift by default
+    KeyboardRight,
+    Gamepad(Entity),
+    /// The stick and sprint button on the player's half of the screen
+    Touch,
+    /// An `AiController` plays instead of a person
+    Computer(Difficulty),
+}
+
+impl InputDevice {
+    pub fn name(&self) -> String {
+        match self {
+            InputDevice::KeyboardLeft => "Keyboard (player 1 keys)".into(),
+            InputDevice::KeyboardRight => "Keyboard (player 2 keys)".into(),
+            InputDevice::Gamepad(_) => "Gamepad".into(),
+            InputDevice::Touch => "Touch screen".into(),
+            InputDevice::Computer(difficulty) => format!("Computer ({})", difficulty.name()),
+        }
+    }
+}
+
+/// The device each player controls their player with, a shared keyboard unless chosen otherwise
+#[derive(Resource, Debug, PartialEq)]
+pub struct PlayerDevices(pub [Option<InputDevice>; 2]);
+
+impl Default for PlayerDevices {
+    fn default() -> Self {
+        PlayerDevices([
+            Some(InputDevice::KeyboardLeft),
+            Some(InputDevice::KeyboardRight),
+        ])
+    }
+}
+
+impl PlayerDevices {
+    pub fn none() -> Self {
+        PlayerDevices([None, None])
+    }
+
+    /// Binds the device to the first player without one, unless it already controls a player
+    pub fn join(&mut self, device: InputDevice) -> Option<PlayerType> {
+        let slot = self.0.iter().position(Option::is_none)?;
+        self.join_as(slot, device)
+    }
+
+    /// Binds the device to the player at `slot`, if they have none yet
+    pub fn join_as(&mut self, slot: usize, device: InputDevice) -> Option<PlayerType> {
+        if self.0[slot].is_some()
+            || (device != InputDevice::Touch && self.0.contains(&Some(device)))
+        {
+            return None;
+        }
+        self.0[slot] = Some(device);
+        Some(match slot {
+            0 => PlayerType::First,
+            _ => PlayerType::Second,
+        })
+    }
+
+    pub fn all_joined(&self) -> bool {
+        self.0.iter().all(Option::is_some)
+    }
+}
+
+/// Index into the `InputMap` of the keys a keyboard device uses
+fn key_set(device: InputDevice) -> Option<usize> {
+    match device {
+        InputDevice::KeyboardLeft => Some(0),
+        InputDevice::KeyboardRight => Some(1),
+        InputDevice::Gamepad(_) | InputDevice::Touch | InputDevice::Computer(_) => None,
+    }
+}
+
+fn movement_axis(negative: bool, positive: bool) -> f32 {
+    match (negative, positive) {
+        (true, false) => -1.0,
+        (false, true) => 1.0,
+        _ => 0f32,
+    }
+}
+
+/// Movement and sprint from whichever of the player's bound actions are held
+fn bound_input(
+    bindings: &PlayerBindings,
+    pressed: impl Fn(&PlayerBindings, Action) -> bool,
+) -> PlayerInput {
+    let x = movement_axis(
+        pressed(bindings, Action::MoveLeft),
+        pressed(bindings, Action::MoveRight),
+    );
+    let z = movement_axis(
+        pressed(bindings, Action::MoveUp),
+        pressed(bindings, Action::MoveDown),
+    );
+    PlayerInput {
+        movement: vec3(x, 0.0, z).normalize_or_zero(),
+        sprint: pressed(bindings, Action::Sprint),
+        kick: pressed(bindings, Action::Kick),
+        tackle: pressed(bindings, Action::Tackle),
+    }
+}
+
+// Menu buttons stay clear of the gameplay buttons, so a press never also acts in the match
+pub(crate) const GAMEPAD_START: GamepadButton = GamepadButton::Start;
+pub(crate) const GAMEPAD_BACK: GamepadButton = GamepadButton::Select;
+pub(crate) const GAMEPAD_CHANGE: GamepadButton = GamepadButton::North;
+
+/// Stick up moves away from the camera, a half tilted stick moves at half speed
+pub fn stick_movement(stick: Vec2) -> Vec3 {
+    vec3(stick.x, 0.0, -stick.y).clamp_length_max(1.0)
+}
+
+/// Keyboards use the key set they stand for, gamepads the buttons of the player they control
+fn device_input(
+    device: InputDevice,
+    player: usize,
+    input_map: &InputMap,
+    keys: &ButtonInput<KeyCode>,
+    gamepads: &Query<&Gamepad>,
+) -> PlayerInput {
+    if let Some(set) = key_set(device) {
+        return bound_input(&input_map.0[set], |bindings, action| {
+            bindings.key(action).is_some_and(|k| keys.pressed(k))
+        });
+    }
+    match device {
+        InputDevice::Gamepad(entity) => match gamepads.get(entity) {
+            Ok(gamepad) => {
+                let buttons = bound_input(&input_map.0[player], |bindings, action| {
+                    bindings.button(action).is_some_and(|b| gamepad.pressed(b))
+                });
+                let stick = stick_movement(gamepad.left_stick());
+                PlayerInput {
+                    movement: if stick == Vec3::ZERO {
+                        buttons.movement
+                    } else {
+                        stick
+                    },
+                    ..buttons
+                }
+            }
+            // Disconnected
+            Err(_) => PlayerInput::default(),
+        },
+        _ => PlayerInput::default(),
+    }
+}
+
+/// The player pressed the key or gamepad button bound to `action` this frame
+pub(crate) fn device_just_pressed(
+    device: InputDevice,
+    player: usize,
+    action: Action,
+    input_map: &InputMap,
+    keys: &ButtonInput<KeyCode>,
+    gamepads: &Query<&Gamepad>,
+) -> bool {
+    if let Some(set) = key_set(device) {
+        return input_map.0[set]
+            .key(action)
+            .is_some_and(|k| keys.just_pressed(k));
+    }
+    match device {
+        InputDevice::Gamepad(entity) => gamepads.get(entity).is_ok_and(|gamepad| {
+            input_map.0[player]
+                .button(action)
+                .is_some_and(|b| gamepad.just_pressed(b))
+        }),
+        _ => false,
+    }
+}
+
+/// Devices that pressed their join button this frame
+pub(crate) fn joining_devices(
+    input_map: &InputMap,
+    keys: &ButtonInput<KeyCode>,
+    gamepads: &Query<(Entity, &Gamepad)>,
+) -> Vec<InputDevice> {
+    let keyboard = [InputDevice::KeyboardLeft, InputDevice::KeyboardRight]
+        .into_iter()
+        .filter(|device| {
+            key_set(*device).is_some_and(|set| keys.any_just_pressed(input_map.0[set].keys()))
+        });
+    let gamepad = gamepads
+        .iter()
+        .filter(|(_, gamepad)| gamepad.just_pressed(GAMEPAD_START))
+        .map(|(entity, _)| InputDevice::Gamepad(entity));
+    keyboard.chain(gamepad).collect()
+}
+
+/// Any gamepad pressed `button` this frame
+pub(crate) fn any_gamepad_just_pressed(gamepads: &Query<&Gamepad>, button: GamepadButton) -> bool {
+    gamepads.iter().any(|gamepad| gamepad.just_pressed(button))
+}
+
+#[allow(clippy::type_complexity)]
+fn movement_input(
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    devices: Res<PlayerDevices>,
+    input_map: Res<InputMap>,
+    touch: Res<TouchControls>,
+    mut query: Query<
+        (&mut PlayerInput, Has<Player1>),
+        (Or<(With<Player1>, With<Player2>)>, Without<AiController>),
+    >,
+    camera: Query<&Transform, With<MainCamera>>,
+) {
+    let rotation = camera
+        .single()
+        .map(|t| Quat::from_axis_angle(Vec3::Y, t.right().y))
+        .unwrap_or_default();
+
+    for (mut player_input, is_player_1) in query.iter_mut() {
+        let player = if is_player_1 { 0 } else { 1 };
+        let bound = devices.0[player]
+            .map(|device| device_input(device, player, &input_map, &keys, &gamepads))
+            .unwrap_or_default();
+        // Fingers on the player's half of the screen take over, whatever device they joined with
+        let input = touch.input(player).unwrap_or(bound);
+        *player_input = PlayerInput {
+            movement: rotation * input.movement,
+            ..input
+        };
+    }
+}
+
+/// Hands players who joined as a computer over to an `AiController`
+fn attach_computer_players(
+    mut commands: Commands,
+    devices: Res<PlayerDevices>,
+    q_players: Query<(Entity, Has<Player1>), Added<PlayerInput>>,
+) {
+    for (entity, is_player_1) in q_players.iter() {
+        let player = if is_player_1 { 0 } else { 1 };
+        if let Some(InputDevice::Computer(difficulty)) = devices.0[player] {
+            commands
+                .entity(entity)
+                .insert(AiController::new(difficulty, player as u64 + 1));
+        }
+    }
+}
+
+/// Fills `PlayerInput` from the keyboards, gamepads and touch screen, through the `InputMap`
+pub struct PlayerControlsPlugin;
+
+impl Plugin for PlayerControlsPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_plugins(TouchControlsPlugin)
+            .init_resource::<PlayerDevices>()
+            .insert_resource(InputMap::load())
+            .add_systems(
+                Update,
+                (
+                    attach_computer_players,
+                    movement_input
+                        .run_if(in_state(MatchState::Playing))
+                        .in_set(GameplaySet::Input)
+                        .after(read_touches),
+                ),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        ecs::entity::Entity,
+        math::{vec2, Vec2},
+    };
+
+    use super::{stick_movement, InputDevice, PlayerDevices};
+    use crate::goals::PlayerType;
+
+    #[test]
+    fn join_when_device_already_joined_then_none() {
+        // given
+        let mut devices = PlayerDevices::none();
+        devices.join(InputDevice::KeyboardLeft);
+
+        // when
+        let joined = devices.join(InputDevice::KeyboardLeft);
+
+        // then
+        assert_eq!(joined, None);
+        assert!(!devices.all_joined());
+    }
+
+    #[test]
+    fn join_when_one_player_joined_then_second_player() {
+        // given
+        let mut devices = PlayerDevices::none();
+        devices.join(InputDevice::KeyboardRight);
+
+        // when
+        let joined = devices.join(InputDevice::Gamepad(Entity::from_raw(7)));
+
+        // then
+        assert_eq!(joined, Some(PlayerType::Second));
+        assert!(devices.all_joined());
+    }
+
+    #[test]
+    fn join_as_when_both_players_use_touch_then_both_joined() {
+        // given
+        let mut devices = PlayerDevices::none();
+        devices.join_as(1, InputDevice::Touch);
+
+        // when
+        let joined = devices.join_as(0, InputDevice::Touch);
+
+        // then
+        assert_eq!(joined, Some(PlayerType::First));
+        assert!(devices.all_joined());
+    }
+
+    #[test]
+    fn stick_movement_when_half_tilted_then_half_length() {
+        // given
+        let stick = vec2(0.0, 0.5);
+
+        // when
+        let movement = stick_movement(stick);
+
+        // then
+        assert_eq!(movement.length(), 0.5);
+        assert!(movement.z < 0.0);
+    }
+
+    #[test]
+    fn stick_movement_when_pushed_into_corner_then_at_most_full_speed() {
+        assert!(stick_movement(Vec2::ONE).length() <= 1.0 + f32::EPSILON);
+    }
+}
@@ -0,0 +1,273 @@
+use bevy::prelude::{BackgroundColor, Button, Interaction, Node, Text};
+use bevy::{
+    app::{Plugin, Update},
+    ecs::{
+        component::Component,
+        hierarchy::ChildSpawnerCommands,
+        query::{Changed, With, Without},
+        resource::Resource,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    input::{
+        gamepad::{Gamepad, GamepadButton},
+        keyboard::KeyCode,
+        ButtonInput,
+    },
+    state::{
+        condition::in_state,
+        state::{NextState, OnEnter},
+    },
+    ui::{AlignItems, FlexDirection, UiRect, Val},
+};
+
+use crate::{
+    colors::GREY,
+    controls::{any_gamepad_just_pressed, GAMEPAD_BACK},
+    input_map::{Action, BindingConflict, InputMap},
+    menu::{button, spawn_screen, text, MenuScreen},
+};
+
+/// One key or gamepad button of the input map
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct BindingSlot {
+    player: usize,
+    action: Action,
+    gamepad: bool,
+}
+
+#[derive(Component)]
+struct BindingButton(BindingSlot);
+
+#[derive(Component)]
+struct BindingLabel(BindingSlot);
+
+#[derive(Component)]
+struct ResetControlsButton;
+
+#[derive(Component)]
+struct RebindNoticeText;
+
+/// The binding waiting for a key or button press, and what happened to the last one
+#[derive(Resource, Default)]
+struct Rebinding {
+    waiting: Option<BindingSlot>,
+    notice: String,
+}
+
+fn key_name(key: KeyCode) -> String {
+    let name = format!("{key:?}");
+    name.strip_prefix("Key").unwrap_or(&name).to_string()
+}
+
+fn button_name(button: GamepadButton) -> String {
+    format!("{button:?}")
+}
+
+fn binding_button(parent: &mut ChildSpawnerCommands, slot: BindingSlot) {
+    parent
+        .spawn((
+            BindingButton(slot),
+            Button,
+            Node {
+                width: Val::Px(200.0),
+                padding: UiRect::axes(Val::Px(12.0), Val::Px(4.0)),
+                ..Default::default()
+            },
+            BackgroundColor(GREY),
+        ))
+        .with_children(|button| {
+            button.spawn((BindingLabel(slot), text("", 20.0)));
+        });
+}
+
+fn setup_controls_screen(mut commands: Commands, mut rebinding: ResMut<Rebinding>) {
+    *rebinding = Rebinding::default();
+    spawn_screen(&mut commands, MenuScreen::Controls, |parent| {
+        parent.spawn(text("Controls", 48.0));
+        for player in 0..2 {
+            parent.spawn(text(&format!("Player {}", player + 1), 28.0));
+            for action in Action::ALL {
+                parent
+                    .spawn(Node {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(12.0),
+                        ..Default::default()
+                    })
+                    .with_children(|row| {
+                        row.spawn((
+                            Node {
+                                width: Val::Px(140.0),
+                                ..Default::default()
+                            },
+                            text(action.name(), 20.0),
+                        ));
+                        for gamepad in [false, true] {
+                            binding_button(
+                                row,
+                                BindingSlot {
+                                    player,
+                                    action,
+                                    gamepad,
+                                },
+                            );
+                        }
+                    });
+            }
+        }
+        button(parent, ResetControlsButton, "Reset to defaults");
+        parent.spawn((RebindNoticeText, text("", 20.0)));
+        parent.spawn(text(
+            "Click a binding to change it, Esc or Select to go back",
+            20.0,
+        ));
+    });
+}
+
+fn conflict_notice(conflict: BindingConflict) -> String {
+    format!(
+        "Already bound to {} of player {}",
+        conflict.action.name(),
+        conflict.player + 1
+    )
+}
+
+fn select_binding(
+    q_buttons: Query<(&Interaction, &BindingButton), Changed<Interaction>>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    for (_, button) in q_buttons
+        .iter()
+        .filter(|(i, _)| **i == Interaction::Pressed)
+    {
+        let device = if button.0.gamepad {
+            "gamepad button"
+        } else {
+            "key"
+        };
+        rebinding.waiting = Some(button.0);
+        rebinding.notice = format!(
+            "Press a {device} for {}, Esc to cancel",
+            button.0.action.name()
+        );
+    }
+}
+
+/// Binds the next pressed key or button, a conflicting one is refused and the old binding kept
+fn capture_binding(
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut input_map: ResMut<InputMap>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    let Some(slot) = rebinding.waiting else {
+        return;
+    };
+    if keys.just_pressed(KeyCode::Escape) {
+        *rebinding = Rebinding::default();
+        return;
+    }
+    let bound = if slot.gamepad {
+        let Some(button) = gamepads
+            .iter()
+            .find_map(|g| g.get_just_pressed().next().copied())
+        else {
+            return;
+        };
+        input_map.bind_button(slot.player, slot.action, button)
+    } else {
+        let Some(key) = keys.get_just_pressed().next().copied() else {
+            return;
+        };
+        input_map.bind_key(slot.player, slot.action, key)
+    };
+    *rebinding = match bound {
+        Ok(()) => {
+            input_map.save();
+            Rebinding::default()
+        }
+        Err(conflict) => Rebinding {
+            waiting: Some(slot),
+            notice: conflict_notice(conflict),
+        },
+    };
+}
+
+fn handle_controls_screen(
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    q_reset: Query<&Interaction, (Changed<Interaction>, With<ResetControlsButton>)>,
+    rebinding: Res<Rebinding>,
+    mut input_map: ResMut<InputMap>,
+    mut next_screen: ResMut<NextState<MenuScreen>>,
+) {
+    if q_reset.iter().any(|i| *i == Interaction::Pressed) {
+        *input_map = InputMap::default();
+        input_map.save();
+    }
+    let back =
+        keys.just_pressed(KeyCode::Escape) || any_gamepad_just_pressed(&gamepads, GAMEPAD_BACK);
+    if back && rebinding.waiting.is_none() {
+        next_screen.set(MenuScreen::Title);
+    }
+}
+
+fn update_binding_labels(
+    input_map: Res<InputMap>,
+    rebinding: Res<Rebinding>,
+    mut q_labels: Query<(&BindingLabel, &mut Text)>,
+    mut q_notice: Query<&mut Text, (With<RebindNoticeText>, Without<BindingLabel>)>,
+) {
+    for (label, mut label_text) in q_labels.iter_mut() {
+        let slot = label.0;
+        let bindings = &input_map.0[slot.player];
+        label_text.0 = if rebinding.waiting == Some(slot) {
+            "...".to_string()
+        } else if slot.gamepad {
+            bindings
+                .button(slot.action)
+                .map(button_name)
+                .unwrap_or_default()
+        } else {
+            bindings.key(slot.action).map(key_name).unwrap_or_default()
+        };
+    }
+    for mut notice in q_notice.iter_mut() {
+        notice.0 = rebinding.notice.clone();
+    }
+}
+
+/// Lists every binding of both players and rebinds the clicked one
+pub struct ControlsMenuPlugin;
+
+impl Plugin for ControlsMenuPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<Rebinding>()
+            .add_systems(OnEnter(MenuScreen::Controls), setup_controls_screen)
+            .add_systems(
+                Update,
+                (
+                    handle_controls_screen,
+                    capture_binding,
+                    select_binding,
+                    update_binding_labels,
+                )
+                    .chain()
+                    .run_if(in_state(MenuScreen::Controls)),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::keyboard::KeyCode;
+
+    use super::key_name;
+
+    #[test]
+    fn key_name_when_letter_then_without_prefix() {
+        assert_eq!(key_name(KeyCode::KeyW), "W");
+        assert_eq!(key_name(KeyCode::ShiftLeft), "ShiftLeft");
+    }
+}
@@ -1,171 +1,358 @@
 use std::time::Duration;
 
 use bevy::{
-    app::{Plugin, Startup, Update},
+    app::{Plugin, Update},
     ecs::{
         component::Component,
         event::{Event, EventReader, EventWriter},
         query::{With, Without},
-        system::{Commands, Query, Res},
+        resource::Resource,
+        schedule::{
+            common_conditions::{not, resource_exists},
+            Condition, IntoScheduleConfigs, SystemSet,
+        },
+        system::{Commands, Query, Res, ResMut},
+    },
+    prelude::{Deref, DerefMut},
+    state::{
+        app::AppExtStates,
+        condition::in_state,
+        state::{ComputedStates, NextState, OnEnter, OnExit, State, States},
+        state_scoped::StateScoped,
     },
     time::{Time, Timer, TimerMode},
     transform::components::Transform,
 };
-use bevy_rapier3d::dynamics::Velocity;
+use bevy_rapier3d::{dynamics::Velocity, plugin::RapierConfiguration};
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    arena::CurrentArena,
+    ball::Ball,
     constants::{
-        BALL_STARTING_POINT, BALL_STARTING_VELOCITY, GAME_TIME, PLAYER1_STARTING_POINT,
-        PLAYER2_STARTING_POINT,
+        BALL_STARTING_VELOCITY, DISPLAY_DESPAWN_TIME, GOAL_CELEBRATION_TIME, KICKOFF_COUNTDOWN_TIME,
     },
     goals::GoalEvent,
+    match_rules::{MatchRules, RoundOutcome, Rounds},
     player::{Player1, Player2},
     points::Points,
-    sprint::SprintState,
-    Ball,
 };
 
-fn reset_p1_after_goal(
-    mut goal_event: EventReader<GoalEvent>,
-    mut q: Query<&mut Transform, With<Player1>>,
+/// Lifecycle of a single match, from the main menu to the final whistle
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum MatchState {
+    #[default]
+    MainMenu,
+    Kickoff,
+    Playing,
+    Paused,
+    GoalScored,
+    MatchOver,
+}
+
+/// Order of the gameplay systems within a tick. Every event is read in the tick it is sent,
+/// so a tick can be simulated again from a saved state.
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameplaySet {
+    /// Fills `PlayerInput`
+    Input,
+    /// Turns `PlayerInput` into velocities and sprint events
+    Movement,
+    /// Applies sprint events and turns ball contacts into goals
+    Resolve,
+    /// Counts the goals
+    Score,
+    /// Timers and the match flow
+    Flow,
+}
+
+/// Exists while a match is running, match entities are scoped to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InMatch;
+
+impl ComputedStates for InMatch {
+    type SourceStates = MatchState;
+
+    fn compute(state: MatchState) -> Option<Self> {
+        match state {
+            MatchState::MainMenu => None,
+            _ => Some(InMatch),
+        }
+    }
+}
+
+/// Keeps players and ball at their starting points while the kickoff countdown runs
+#[allow(clippy::type_complexity)]
+fn hold_kickoff_positions(
+    arena: Res<CurrentArena>,
+    mut q_p1: Query<(&mut Transform, &mut Velocity), (With<Player1>, Without<Player2>)>,
+    mut q_p2: Query<(&mut Transform, &mut Velocity), (With<Player2>, Without<Player1>)>,
+    mut q_ball: Query<
+        (&mut Transform, &mut Velocity),
+        (With<Ball>, Without<Player1>, Without<Player2>),
+    >,
 ) {
-    for _ in goal_event.read() {
-        let mut t = q.single_mut().unwrap();
-        t.translation = PLAYER1_STARTING_POINT;
+    let positions = q_p1
+        .iter_mut()
+        .map(|q| (q, arena.spawns.player1))
+        .chain(q_p2.iter_mut().map(|q| (q, arena.spawns.player2)))
+        .chain(q_ball.iter_mut().map(|q| (q, arena.spawns.ball)));
+    for ((mut t, mut v), starting_point) in positions {
+        t.translation = starting_point;
+        *v = Velocity::zero();
+    }
+}
+
+fn release_ball(mut q_ball: Query<&mut Velocity, With<Ball>>) {
+    for mut v in q_ball.iter_mut() {
+        v.linvel = BALL_STARTING_VELOCITY;
     }
 }
-fn reset_p2_after_goal(
+
+/// Inserted by online clients, where the server decides when the match moves on
+#[derive(Resource)]
+pub struct RemoteMatchFlow;
+
+fn enter_goal_scored(
     mut goal_event: EventReader<GoalEvent>,
-    mut q: Query<&mut Transform, With<Player2>>,
+    mut next_state: ResMut<NextState<MatchState>>,
 ) {
-    for _ in goal_event.read() {
-        let mut t = q.single_mut().unwrap();
-        t.translation = PLAYER2_STARTING_POINT;
+    if goal_event.read().count() > 0 {
+        next_state.set(MatchState::GoalScored);
     }
 }
-fn reset_ball_after_goal(
-    mut goal_event: EventReader<GoalEvent>,
-    mut q: Query<(&mut Transform, &mut Velocity), With<Ball>>,
+
+/// Counts down the timed phases: kickoff, goal celebration and match over
+#[derive(Resource, Clone, Deref, DerefMut)]
+pub struct PhaseTimer(Timer);
+
+impl PhaseTimer {
+    pub fn seconds_left(&self) -> u32 {
+        self.remaining_secs().ceil() as u32
+    }
+}
+
+fn start_phase_timer(seconds: f32) -> impl FnMut(Commands) {
+    move |mut commands: Commands| {
+        commands.insert_resource(PhaseTimer(Timer::from_seconds(seconds, TimerMode::Once)));
+    }
+}
+
+fn tick_phase_timer(time: Res<Time>, mut timer: ResMut<PhaseTimer>) {
+    timer.tick(time.delta());
+}
+
+fn advance_timed_phase(
+    timer: Res<PhaseTimer>,
+    state: Res<State<MatchState>>,
+    mut next_state: ResMut<NextState<MatchState>>,
 ) {
-    for _ in goal_event.read() {
-        let (mut t, mut v) = q.single_mut().unwrap();
-        t.translation = BALL_STARTING_POINT;
-        v.linvel = BALL_STARTING_VELOCITY;
+    if !timer.just_finished() {
+        return;
     }
+    let next = match state.get() {
+        MatchState::Kickoff => MatchState::Playing,
+        MatchState::GoalScored => MatchState::Kickoff,
+        MatchState::MatchOver => MatchState::MainMenu,
+        _ => return,
+    };
+    next_state.set(next);
 }
 
-#[derive(Component)]
+/// The clock of the running round
+#[derive(Component, Clone)]
 pub struct GameTime {
     time: Timer,
+    /// Time played after the clock ran out on a tie, while waiting for a golden goal
+    overtime: Option<Duration>,
 }
 
-fn reset_game(
-    mut q_time: Query<&mut GameTime>,
-    mut q_points: Query<&mut Points>,
-    mut game_end_event: EventReader<GameEndEvent>,
-    mut q_p1: Query<
-        (&mut Transform, &mut SprintState),
-        (With<Player1>, Without<Player2>, Without<Ball>),
-    >,
-    mut q_p2: Query<
-        (&mut Transform, &mut SprintState),
-        (With<Player2>, Without<Player1>, Without<Ball>),
-    >,
-    mut q_ball: Query<
-        (&mut Transform, &mut Velocity),
-        (With<Ball>, Without<Player2>, Without<Player1>),
-    >,
-) {
-    for _ in game_end_event.read() {
-        if let Ok(mut timer) = q_time.single_mut() {
-            timer.time.reset();
+impl GameTime {
+    /// A clock running for `limit` seconds, without a limit it never runs out
+    pub fn new(limit: Option<u64>) -> Self {
+        let duration = limit.map_or(Duration::MAX, Duration::from_secs);
+        GameTime {
+            time: Timer::new(duration, TimerMode::Once),
+            overtime: None,
         }
-
-        let mut points = q_points.single_mut().unwrap();
-        points.player_1 = 0;
-        points.player_2 = 0;
-        let (mut t_p1, mut sprint_p1) = q_p1.single_mut().unwrap();
-        t_p1.translation = PLAYER1_STARTING_POINT;
-        sprint_p1.reset();
-        let (mut t_p2, mut sprint_p2) = q_p2.single_mut().unwrap();
-        t_p2.translation = PLAYER2_STARTING_POINT;
-        sprint_p2.reset();
-        let (mut t, mut v) = q_ball.single_mut().unwrap();
-        t.translation = BALL_STARTING_POINT;
-        v.linvel = BALL_STARTING_VELOCITY;
     }
-}
 
-impl GameTime {
     pub fn current_time(&self) -> Duration {
         self.time.duration() - self.time.elapsed()
     }
 
+    /// Time played in this round, overtime included
+    pub fn elapsed(&self) -> Duration {
+        self.time.elapsed() + self.overtime.unwrap_or_default()
+    }
+
+    pub fn overtime(&self) -> Option<Duration> {
+        self.overtime
+    }
+
+    pub fn start_overtime(&mut self) {
+        self.overtime = Some(Duration::ZERO);
+    }
+
     pub fn just_finished(&self) -> bool {
         self.time.just_finished()
     }
+
+    pub fn set_current_time(&mut self, time_left: Duration) {
+        let elapsed = self.time.duration().saturating_sub(time_left);
+        self.time.set_elapsed(elapsed);
+    }
 }
 
 fn update_game_timer(time: Res<Time>, mut q_time: Query<&mut GameTime>) {
     if let Ok(mut timer) = q_time.single_mut() {
         timer.time.tick(time.delta());
+        if let Some(overtime) = &mut timer.overtime {
+            *overtime += time.delta();
+        }
     }
 }
 
-fn spawn_game_timer(mut commands: Commands) {
-    commands.spawn(GameTime {
-        time: Timer::new(Duration::from_secs(GAME_TIME), TimerMode::Once),
-    });
+fn spawn_game_timer(mut commands: Commands, rules: Res<MatchRules>) {
+    commands.spawn((
+        GameTime::new(rules.time_limit),
+        Rounds::default(),
+        StateScoped(InMatch),
+    ));
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum EndState {
     Player1Won,
     Player2Won,
     Draw,
 }
 
+impl EndState {
+    pub fn from_points(points: &Points) -> Self {
+        if points.player_1 > points.player_2 {
+            EndState::Player1Won
+        } else if points.player_2 > points.player_1 {
+            EndState::Player2Won
+        } else {
+            EndState::Draw
+        }
+    }
+}
+
 #[derive(Event)]
 pub struct GameEndEvent {
     pub end_state: EndState,
 }
 
+/// Ends the round as the `MatchRules` say, then starts the next round or ends the match
 fn check_game_end(
-    q_game_time: Query<&GameTime>,
-    q_points: Query<&Points>,
+    rules: Res<MatchRules>,
+    mut q_game_time: Query<(&mut GameTime, &mut Rounds)>,
+    mut q_points: Query<&mut Points>,
     mut event_writer: EventWriter<GameEndEvent>,
+    mut next_state: ResMut<NextState<MatchState>>,
 ) {
-    let timer = q_game_time.single().unwrap();
-    if timer.just_finished() {
-        let points = q_points.single().unwrap();
-        let end_state = {
-            if points.player_1 > points.player_2 {
-                EndState::Player1Won
-            } else if points.player_2 > points.player_1 {
-                EndState::Player2Won
-            } else {
-                EndState::Draw
-            }
-        };
-
+    let (Ok((mut timer, mut rounds)), Ok(mut points)) =
+        (q_game_time.single_mut(), q_points.single_mut())
+    else {
+        return;
+    };
+    let outcome = rules.judge_round(&points, timer.just_finished(), timer.overtime().is_some());
+    match outcome {
+        RoundOutcome::Continue => return,
+        RoundOutcome::Overtime => {
+            timer.start_overtime();
+            return;
+        }
+        RoundOutcome::Won(_) | RoundOutcome::Draw => rounds.record(outcome),
+    }
+    if let Some(end_state) = rules.match_result(&rounds) {
         event_writer.write(GameEndEvent { end_state });
+        next_state.set(MatchState::MatchOver);
+    } else {
+        *points = Points::default();
+        *timer = GameTime::new(rules.time_limit);
+        next_state.set(MatchState::Kickoff);
+    }
+}
+
+fn pause_physics(mut q_config: Query<&mut RapierConfiguration>) {
+    for mut config in q_config.iter_mut() {
+        config.physics_pipeline_active = false;
+    }
+}
+
+fn resume_physics(mut q_config: Query<&mut RapierConfiguration>) {
+    for mut config in q_config.iter_mut() {
+        config.physics_pipeline_active = true;
     }
 }
 
 pub struct GameStatePlugin;
 impl Plugin for GameStatePlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_event::<GameEndEvent>()
-            .add_systems(Startup, spawn_game_timer)
-            .add_systems(
+        app.init_resource::<MatchRules>()
+            .init_state::<MatchState>()
+            .add_computed_state::<InMatch>()
+            .enable_state_scoped_entities::<MatchState>()
+            .enable_state_scoped_entities::<InMatch>()
+            .add_event::<GameEndEvent>()
+            .configure_sets(
                 Update,
                 (
-                    reset_p1_after_goal,
-                    reset_p2_after_goal,
-                    reset_ball_after_goal,
-                    update_game_timer,
-                    check_game_end,
-                    reset_game,
+                    GameplaySet::Input,
+                    GameplaySet::Movement,
+                    GameplaySet::Resolve,
+                    GameplaySet::Score,
+                    GameplaySet::Flow,
+                )
+                    .chain(),
+            )
+            .add_systems(OnEnter(InMatch), spawn_game_timer)
+            .add_systems(
+                OnEnter(MatchState::Kickoff),
+                (
+                    hold_kickoff_positions,
+                    start_phase_timer(KICKOFF_COUNTDOWN_TIME),
                 ),
+            )
+            .add_systems(OnExit(MatchState::Kickoff), release_ball)
+            .add_systems(
+                OnEnter(MatchState::GoalScored),
+                start_phase_timer(GOAL_CELEBRATION_TIME),
+            )
+            .add_systems(
+                OnEnter(MatchState::MatchOver),
+                start_phase_timer(DISPLAY_DESPAWN_TIME),
+            )
+            .add_systems(OnEnter(MatchState::Paused), pause_physics)
+            .add_systems(OnExit(MatchState::Paused), resume_physics)
+            .add_systems(
+                Update,
+                (
+                    hold_kickoff_positions.run_if(in_state(MatchState::Kickoff)),
+                    (
+                        update_game_timer,
+                        // A goal that decides the round ends it instead of being celebrated
+                        (enter_goal_scored, check_game_end)
+                            .chain()
+                            .run_if(not(resource_exists::<RemoteMatchFlow>)),
+                    )
+                        .chain()
+                        .run_if(in_state(MatchState::Playing)),
+                    (
+                        tick_phase_timer,
+                        advance_timed_phase.run_if(not(resource_exists::<RemoteMatchFlow>)),
+                    )
+                        .chain()
+                        .run_if(
+                            in_state(MatchState::Kickoff)
+                                .or(in_state(MatchState::GoalScored))
+                                .or(in_state(MatchState::MatchOver)),
+                        ),
+                )
+                    .in_set(GameplaySet::Flow),
             );
     }
 }
@@ -174,16 +361,13 @@ impl Plugin for GameStatePlugin {
 mod tests {
     use std::time::Duration;
 
-    use bevy::time::{Timer, TimerMode};
-
-    use super::GameTime;
+    use super::{GameTime, InMatch, MatchState};
+    use bevy::state::state::ComputedStates;
 
     #[test]
     fn current_time_when_called_return_duration() {
         // given
-        let time = GameTime {
-            time: Timer::new(Duration::from_secs(2), TimerMode::Once),
-        };
+        let time = GameTime::new(Some(2));
 
         // when
         let dur = time.current_time();
@@ -191,4 +375,41 @@ mod tests {
         // then
         assert_eq!(dur, Duration::from_secs(2));
     }
+
+    #[test]
+    fn set_current_time_when_called_then_current_time_matches() {
+        // given
+        let mut time = GameTime::new(Some(120));
+
+        // when
+        time.set_current_time(Duration::from_secs(30));
+
+        // then
+        assert_eq!(time.current_time(), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn elapsed_when_in_overtime_then_counts_on_after_the_clock() {
+        // given
+        let mut time = GameTime::new(Some(10));
+        time.set_current_time(Duration::ZERO);
+
+        // when
+        time.start_overtime();
+        time.overtime = time.overtime.map(|o| o + Duration::from_secs(3));
+
+        // then
+        assert_eq!(time.current_time(), Duration::ZERO);
+        assert_eq!(time.elapsed(), Duration::from_secs(13));
+    }
+
+    #[test]
+    fn in_match_when_in_main_menu_then_none() {
+        assert_eq!(InMatch::compute(MatchState::MainMenu), None);
+    }
+
+    #[test]
+    fn in_match_when_paused_then_some() {
+        assert_eq!(InMatch::compute(MatchState::Paused), Some(InMatch));
+    }
 }
@@ -1,118 +1,124 @@
 use std::f32::consts::FRAC_PI_2;
 
+use crate::arena::CurrentArena;
+use crate::ball::Ball;
 use crate::colors::{GREEN, ORANGE};
-use bevy::prelude::{Cuboid, Mesh3d};
+use crate::constants::BALL_RADIUS;
+use crate::game_state::{GameplaySet, MatchState};
+use bevy::pbr::MeshMaterial3d;
+use bevy::prelude::{Cuboid, Mesh3d, Visibility};
 use bevy::{
-    app::{Plugin, Startup, Update},
+    app::{Plugin, PreUpdate, Update},
     asset::Assets,
     ecs::{
         component::Component,
+        entity::Entity,
         event::{Event, EventReader, EventWriter},
-        system::{Commands, Query, ResMut},
+        query::{Added, With},
+        schedule::{common_conditions::resource_changed, IntoScheduleConfigs},
+        system::{Commands, Query, Res, ResMut},
     },
     math::{vec3, Quat},
     pbr::{NotShadowCaster, StandardMaterial},
     render::mesh::Mesh,
+    state::{condition::in_state, state::OnEnter},
     transform::components::Transform,
 };
-use bevy::pbr::MeshMaterial3d;
 use bevy_rapier3d::{
     dynamics::RigidBody,
-    geometry::{Collider, ColliderMassProperties},
+    geometry::{Collider, Sensor},
     pipeline::CollisionEvent,
 };
+use serde::{Deserialize, Serialize};
 
-const MAP_SIZE_HALF: f32 = 15.0;
-const GOAL_SIZE: f32 = 10.0;
 const GOAL_THICKNESS: f32 = 1.0;
-const GOAL_HEIGHT: f32 = 4.0;
+const GOAL_SENSOR_DEPTH: f32 = 2.0;
 
-fn build_goal_meshes(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-) {
-    let mesh = meshes.add(Cuboid::new(GOAL_SIZE, GOAL_HEIGHT * 2.0, GOAL_THICKNESS));
-    let material_green = materials.add(StandardMaterial {
-        base_color: GREEN,
-        ..Default::default()
-    });
+/// The sensor starts one ball diameter behind the goal line, so the whole ball has to cross it.
+/// It reaches into the back wall, which keeps the ball from passing through it.
+fn goal_sensor_offset() -> f32 {
+    BALL_RADIUS * 2.0 + GOAL_SENSOR_DEPTH * 0.5
+}
 
-    let material_orange = materials.add(StandardMaterial {
-        base_color: ORANGE,
-        ..Default::default()
-    });
-    let transforms_with_mesh = [
-        (
-            Transform::from_translation(vec3(
-                MAP_SIZE_HALF * 2.0 - GOAL_THICKNESS,
-                GOAL_HEIGHT * 0.5,
-                0.0,
-            ))
-            .with_rotation(Quat::from_rotation_y(-FRAC_PI_2)),
-            mesh.clone(),
-            material_orange,
-        ),
-        (
-            Transform::from_translation(vec3(
-                -MAP_SIZE_HALF * 2.0 + GOAL_THICKNESS,
-                GOAL_HEIGHT * 0.5,
-                0.0,
-            ))
-            .with_rotation(Quat::from_rotation_y(FRAC_PI_2)),
-            mesh.clone(),
-            material_green,
-        ),
-    ];
+/// Size of the goal, drawn as a box behind the goal line
+#[derive(Component)]
+struct GoalBox {
+    width: f32,
+    height: f32,
+    side: f32,
+}
 
-    for (t, m, color) in transforms_with_mesh {
-        commands.spawn((
-            NotShadowCaster,
-            Transform::from(t),
-            Mesh3d(m.clone()),
-            MeshMaterial3d(color.clone()),
-        ));
+fn build_goal_colliders(
+    mut commands: Commands,
+    arena: Res<CurrentArena>,
+    q_goals: Query<Entity, With<GoalType>>,
+) {
+    for goal in &q_goals {
+        commands.entity(goal).despawn();
     }
-}
-fn build_goal_colliders(mut commands: Commands) {
-    let transforms_with_collider = [
-        (
-            Transform::from_translation(vec3(
-                MAP_SIZE_HALF * 2.0 - GOAL_THICKNESS,
-                GOAL_HEIGHT * 0.5,
-                0.0,
-            )),
-            Collider::cuboid(GOAL_THICKNESS * 0.5, GOAL_HEIGHT, GOAL_SIZE * 0.5),
-            GoalType::First,
-        ),
-        (
-            Transform::from_translation(vec3(
-                -MAP_SIZE_HALF * 2.0 + GOAL_THICKNESS,
-                GOAL_HEIGHT * 0.5,
-                0.0,
-            )),
-            Collider::cuboid(GOAL_THICKNESS * 0.5, GOAL_HEIGHT, GOAL_SIZE * 0.5),
-            GoalType::Second,
-        ),
-    ];
-
-    for (t, c, goal_type) in transforms_with_collider {
+    for goal in &arena.goals {
+        let goal_type = match goal.scorer {
+            PlayerType::First => GoalType::First,
+            PlayerType::Second => GoalType::Second,
+        };
+        let x = goal.center.x + goal.side() * goal_sensor_offset();
         commands.spawn((
-            c,
+            Collider::cuboid(GOAL_SENSOR_DEPTH * 0.5, goal.height, goal.width * 0.5),
+            Sensor,
             RigidBody::Fixed,
-            ColliderMassProperties::Mass(100.0),
-            Transform::from(t),
+            Transform::from_translation(vec3(x, goal.height * 0.5, goal.center.y)),
             goal_type,
+            GoalBox {
+                width: goal.width,
+                height: goal.height,
+                side: goal.side(),
+            },
+            BallsInGoal::default(),
         ));
     }
 }
 
+fn add_goal_meshes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    q_goals: Query<(Entity, &GoalType, &GoalBox), Added<GoalBox>>,
+) {
+    for (entity, goal_type, goal) in &q_goals {
+        // Each goal has the color of the player defending it
+        let color = match goal_type {
+            GoalType::First => ORANGE,
+            GoalType::Second => GREEN,
+        };
+        // From the sensor back to just behind the goal line
+        let offset = -goal.side * (goal_sensor_offset() - GOAL_THICKNESS * 0.5);
+        commands
+            .entity(entity)
+            .insert(Visibility::default())
+            .with_child((
+                NotShadowCaster,
+                Mesh3d(meshes.add(Cuboid::new(goal.width, goal.height * 2.0, GOAL_THICKNESS))),
+                MeshMaterial3d(materials.add(StandardMaterial {
+                    base_color: color,
+                    ..Default::default()
+                })),
+                Transform::from_translation(vec3(offset, 0.0, 0.0))
+                    .with_rotation(Quat::from_rotation_y(-goal.side * FRAC_PI_2)),
+            ));
+    }
+}
+
 #[derive(Component)]
 pub enum GoalType {
     First,
     Second,
 }
 
+/// Balls currently inside a goal sensor, so one crossing only scores once
+#[derive(Component, Clone, Default)]
+pub(crate) struct BallsInGoal(Vec<Entity>);
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum PlayerType {
     First,
     Second,
@@ -138,28 +144,216 @@ fn send_goal_event(goal_type: &GoalType, goal_event_writer: &mut EventWriter<Goa
 fn check_collision_for_goals(
     mut collision_events: EventReader<CollisionEvent>,
     mut goal_event_writer: EventWriter<GoalEvent>,
-    q_goal_type: Query<&GoalType>,
+    mut q_goals: Query<(&GoalType, &mut BallsInGoal)>,
+    q_ball: Query<(), With<Ball>>,
 ) {
     for ev in collision_events.read() {
-        match ev {
-            CollisionEvent::Started(first_collider, second_collider, _) => {
-                if let Ok(goal_type) = q_goal_type.get(*second_collider) {
-                    send_goal_event(goal_type, &mut goal_event_writer)
-                }
-
-                if let Ok(goal_type) = q_goal_type.get(*first_collider) {
-                    send_goal_event(goal_type, &mut goal_event_writer)
-                }
-            }
-            CollisionEvent::Stopped(_, _, _) => {}
+        let (first_collider, second_collider, started) = match ev {
+            CollisionEvent::Started(first, second, _) => (*first, *second, true),
+            CollisionEvent::Stopped(first, second, _) => (*first, *second, false),
+        };
+        let (goal, ball) = if q_ball.contains(first_collider) {
+            (second_collider, first_collider)
+        } else if q_ball.contains(second_collider) {
+            (first_collider, second_collider)
+        } else {
+            continue;
+        };
+        let Ok((goal_type, mut balls_in_goal)) = q_goals.get_mut(goal) else {
+            continue;
+        };
+
+        if !started {
+            balls_in_goal.0.retain(|e| *e != ball);
+        } else if !balls_in_goal.0.contains(&ball) {
+            balls_in_goal.0.push(ball);
+            send_goal_event(goal_type, &mut goal_event_writer);
         }
     }
 }
+
+/// The ball is teleported back for kickoff while goal detection is not running
+fn clear_balls_in_goal(mut q_goals: Query<&mut BallsInGoal>) {
+    for mut balls_in_goal in q_goals.iter_mut() {
+        balls_in_goal.0.clear();
+    }
+}
+
 pub struct GoalPlugin;
 impl Plugin for GoalPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_systems(Startup, (build_goal_meshes, build_goal_colliders))
-            .add_systems(Update, check_collision_for_goals)
-            .add_event::<GoalEvent>();
+        app.add_systems(
+            PreUpdate,
+            build_goal_colliders.run_if(resource_changed::<CurrentArena>),
+        )
+        .add_systems(OnEnter(MatchState::Kickoff), clear_balls_in_goal)
+        .add_systems(
+            Update,
+            check_collision_for_goals
+                .run_if(in_state(MatchState::Playing))
+                .in_set(GameplaySet::Resolve),
+        )
+        .add_event::<GoalEvent>();
+    }
+}
+
+pub struct GoalRenderPlugin;
+impl Plugin for GoalRenderPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_systems(Update, add_goal_meshes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{
+        app::{App, Startup, Update},
+        ecs::{
+            entity::Entity, event::EventReader, query::With, resource::Resource,
+            schedule::IntoScheduleConfigs, system::ResMut,
+        },
+        math::{vec3, Vec3},
+        time::TimeUpdateStrategy,
+        transform::{components::Transform, TransformPlugin},
+        MinimalPlugins,
+    };
+    use bevy_rapier3d::{
+        dynamics::{GravityScale, RigidBody, Velocity},
+        geometry::{ActiveEvents, Collider},
+        pipeline::CollisionEvent,
+        plugin::{NoUserData, RapierPhysicsPlugin, TimestepMode},
+        rapier::geometry::CollisionEventFlags,
+    };
+
+    use super::{build_goal_colliders, check_collision_for_goals, GoalEvent, GoalType, PlayerType};
+    use crate::{arena::CurrentArena, ball::Ball, constants::BALL_RADIUS, player::Player1};
+
+    #[derive(Resource, Default)]
+    struct ScoredGoals(Vec<PlayerType>);
+
+    fn collect_goals(mut goals: EventReader<GoalEvent>, mut scored: ResMut<ScoredGoals>) {
+        scored.0.extend(goals.read().map(|ev| ev.player));
+    }
+
+    fn goal_test_app() -> App {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            RapierPhysicsPlugin::<NoUserData>::default(),
+        ))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+            1.0 / 60.0,
+        )))
+        .insert_resource(TimestepMode::Fixed {
+            dt: 1.0 / 60.0,
+            substeps: 1,
+        })
+        .init_resource::<ScoredGoals>()
+        .init_resource::<CurrentArena>()
+        .add_event::<GoalEvent>()
+        .add_systems(Startup, build_goal_colliders)
+        .add_systems(Update, (check_collision_for_goals, collect_goals).chain());
+        app.finish();
+        app.cleanup();
+        app
+    }
+
+    fn spawn_moving_body(app: &mut App, x: f32, linvel: Vec3) -> Entity {
+        app.world_mut()
+            .spawn((
+                RigidBody::Dynamic,
+                Velocity::linear(linvel),
+                GravityScale(0.0),
+                ActiveEvents::COLLISION_EVENTS,
+                Transform::from_xyz(x, 1.0, 0.0),
+            ))
+            .id()
+    }
+
+    fn run(app: &mut App, frames: usize) {
+        for _ in 0..frames {
+            app.update();
+        }
+    }
+
+    #[test]
+    fn check_collision_for_goals_when_ball_crosses_line_then_scores_once() {
+        // given
+        let mut app = goal_test_app();
+        let ball = spawn_moving_body(&mut app, 20.0, vec3(20.0, 0.0, 0.0));
+        app.world_mut()
+            .entity_mut(ball)
+            .insert((Ball, Collider::ball(BALL_RADIUS)));
+
+        // when
+        run(&mut app, 60);
+
+        // then
+        let scored = &app.world().resource::<ScoredGoals>().0;
+        assert_eq!(scored, &vec![PlayerType::First]);
+    }
+
+    #[test]
+    fn check_collision_for_goals_when_ball_stops_before_line_then_no_goal() {
+        // given
+        let mut app = goal_test_app();
+        let goal_line = CurrentArena::default().goal(PlayerType::First).x;
+        let ball = spawn_moving_body(&mut app, goal_line, Vec3::ZERO);
+        app.world_mut()
+            .entity_mut(ball)
+            .insert((Ball, Collider::ball(BALL_RADIUS)));
+
+        // when
+        run(&mut app, 10);
+
+        // then
+        assert!(app.world().resource::<ScoredGoals>().0.is_empty());
+    }
+
+    #[test]
+    fn check_collision_for_goals_when_player_runs_into_goal_then_no_goal() {
+        // given
+        let mut app = goal_test_app();
+        let player = spawn_moving_body(&mut app, -20.0, vec3(-20.0, 0.0, 0.0));
+        app.world_mut()
+            .entity_mut(player)
+            .insert((Player1, Collider::capsule(Vec3::ZERO, Vec3::Y, 0.5)));
+
+        // when
+        run(&mut app, 60);
+
+        // then
+        assert!(app.world().resource::<ScoredGoals>().0.is_empty());
+    }
+
+    #[test]
+    fn check_collision_for_goals_when_started_is_sent_twice_then_scores_once() {
+        // given
+        let mut app = goal_test_app();
+        run(&mut app, 1);
+        let ball = spawn_moving_body(&mut app, 0.0, Vec3::ZERO);
+        app.world_mut().entity_mut(ball).insert(Ball);
+        let goal = app
+            .world_mut()
+            .query_filtered::<Entity, With<GoalType>>()
+            .iter(app.world())
+            .next()
+            .unwrap();
+
+        // when
+        for _ in 0..2 {
+            app.world_mut().send_event(CollisionEvent::Started(
+                goal,
+                ball,
+                CollisionEventFlags::SENSOR,
+            ));
+        }
+        run(&mut app, 1);
+
+        // then
+        assert_eq!(app.world().resource::<ScoredGoals>().0.len(), 1);
     }
 }
@@ -0,0 +1,413 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::prelude::{Deref, DerefMut, JustifyContent, Node, Text, TextColor, TextFont};
+use bevy::{
+    app::{Plugin, Update},
+    color::Color,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::{Event, EventReader},
+        query::{Changed, Has, Or, With, Without},
+        resource::Resource,
+        schedule::{common_conditions::any_match_filter, IntoScheduleConfigs},
+        system::{Commands, Query, Res},
+    },
+    math::{Quat, Vec3},
+    state::{
+        condition::in_state,
+        state::{OnEnter, OnExit},
+        state_scoped::StateScoped,
+    },
+    time::{Time, Timer, TimerMode},
+    transform::components::Transform,
+    ui::{PositionType, Val},
+};
+use bevy_rapier3d::{dynamics::Velocity, geometry::ColliderDisabled};
+use bevy_vector_shapes::prelude::*;
+
+use crate::{
+    character::Character,
+    colors::{RED, WHITE},
+    game_state::{GameplaySet, InMatch, MatchState},
+    goals::{GoalType, PlayerType},
+    match_rules::Rounds,
+    player::{Player1, Player2},
+    powerup::{collect_pickups, expire},
+    sprint::SprintState,
+    tackle::TackleState,
+};
+
+/// Seconds a handicap message stays on screen
+const MESSAGE_TIME: f32 = 2.0;
+/// Seconds a drained player is marked with a ring
+const DRAIN_MARK_TIME: f32 = 1.0;
+
+/// An item that hinders the opponent of the player who collects it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Handicap {
+    InvertedControls,
+    Slowed,
+    DrainedSprint,
+    Frozen,
+    LockedGoal,
+}
+
+impl Handicap {
+    pub const ALL: [Handicap; 5] = [
+        Handicap::InvertedControls,
+        Handicap::Slowed,
+        Handicap::DrainedSprint,
+        Handicap::Frozen,
+        Handicap::LockedGoal,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Handicap::InvertedControls => "Inverted controls",
+            Handicap::Slowed => "Slowed",
+            Handicap::DrainedSprint => "Sprint drained",
+            Handicap::Frozen => "Frozen",
+            Handicap::LockedGoal => "Can't score",
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            Handicap::InvertedControls => Color::srgb(0.7, 0.2, 0.9),
+            Handicap::Slowed => Color::srgb(0.4, 0.3, 0.2),
+            Handicap::DrainedSprint => RED,
+            Handicap::Frozen => Color::srgb(0.6, 0.9, 1.0),
+            Handicap::LockedGoal => Color::srgb(0.2, 0.2, 0.2),
+        }
+    }
+}
+
+/// Movement goes the opposite way, the seconds left
+#[derive(Component, Clone, Deref, DerefMut)]
+pub struct InvertedControls(pub f32);
+
+/// Runs slower, the seconds left
+#[derive(Component, Clone, Deref, DerefMut)]
+pub struct Slowed(pub f32);
+
+/// Can't move or tackle, the seconds left
+#[derive(Component, Clone, Deref, DerefMut)]
+pub struct Frozen(pub f32);
+
+/// Marks a player whose sprint was just drained, the seconds the mark is shown
+#[derive(Component, Clone, Deref, DerefMut)]
+pub struct DrainedSprint(pub f32);
+
+/// On a goal sensor that counts no goals, the seconds left
+#[derive(Component, Clone, Deref, DerefMut)]
+pub struct LockedGoal(pub f32);
+
+/// How long and how hard handicaps hinder the opponent
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct HandicapRules {
+    /// Seconds inverted controls and slowing last
+    pub duration: f32,
+    /// Multiplies speed and acceleration of a slowed player
+    pub slow_factor: f32,
+    pub freeze_time: f32,
+    pub goal_lock_time: f32,
+}
+
+impl Default for HandicapRules {
+    fn default() -> Self {
+        HandicapRules {
+            duration: 5.0,
+            slow_factor: 0.5,
+            freeze_time: 1.5,
+            goal_lock_time: 6.0,
+        }
+    }
+}
+
+/// A player collected a handicap, which hits its opponent
+#[derive(Event, Clone, Debug)]
+pub struct HandicapEvent {
+    pub handicap: Handicap,
+    pub collector: Entity,
+}
+
+/// The character of a slowed player
+pub fn slowed(character: Character, rules: &HandicapRules) -> Character {
+    Character {
+        speed: character.speed * rules.slow_factor,
+        acceleration: character.acceleration * rules.slow_factor,
+        ..character
+    }
+}
+
+/// The player who scores in `goal`
+pub(crate) fn scorer(goal: &GoalType) -> PlayerType {
+    match goal {
+        GoalType::First => PlayerType::First,
+        GoalType::Second => PlayerType::Second,
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn apply_handicaps(
+    mut commands: Commands,
+    rules: Res<HandicapRules>,
+    mut handicap_events: EventReader<HandicapEvent>,
+    mut q_players: Query<
+        (
+            Entity,
+            &mut SprintState,
+            &mut TackleState,
+            &mut Velocity,
+            Has<Player1>,
+        ),
+        Or<(With<Player1>, With<Player2>)>,
+    >,
+    q_goals: Query<(Entity, &GoalType)>,
+) {
+    for ev in handicap_events.read() {
+        let Some((victim, mut sprint, mut tackle, mut velocity, is_player_1)) = q_players
+            .iter_mut()
+            .find(|(entity, ..)| *entity != ev.collector)
+        else {
+            continue;
+        };
+        match ev.handicap {
+            Handicap::InvertedControls => {
+                commands
+                    .entity(victim)
+                    .insert(InvertedControls(rules.duration));
+            }
+            Handicap::Slowed => {
+                commands.entity(victim).insert(Slowed(rules.duration));
+            }
+            Handicap::DrainedSprint => {
+                sprint.reduce(f32::MAX);
+                commands
+                    .entity(victim)
+                    .insert(DrainedSprint(DRAIN_MARK_TIME));
+            }
+            Handicap::Frozen => {
+                tackle.stun_for(rules.freeze_time);
+                velocity.linvel = Vec3::new(0.0, velocity.linvel.y, 0.0);
+                commands.entity(victim).insert(Frozen(rules.freeze_time));
+            }
+            Handicap::LockedGoal => {
+                let victim_type = if is_player_1 {
+                    PlayerType::First
+                } else {
+                    PlayerType::Second
+                };
+                for (goal, goal_type) in &q_goals {
+                    if scorer(goal_type) == victim_type {
+                        commands
+                            .entity(goal)
+                            .insert((LockedGoal(rules.goal_lock_time), ColliderDisabled));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Goals count again once their lock ran out
+#[allow(clippy::type_complexity)]
+fn unlock_goals(
+    mut commands: Commands,
+    q_goals: Query<Entity, (With<GoalType>, With<ColliderDisabled>, Without<LockedGoal>)>,
+) {
+    for goal in &q_goals {
+        commands.entity(goal).remove::<ColliderDisabled>();
+    }
+}
+
+/// Seconds the goal `player` scores in stays locked, if it is
+pub(crate) fn goal_lock_left(
+    q_locks: &Query<(&LockedGoal, &GoalType)>,
+    player: PlayerType,
+) -> Option<f32> {
+    q_locks
+        .iter()
+        .find(|(_, goal)| scorer(goal) == player)
+        .map(|(lock, _)| lock.0)
+}
+
+/// Goals outlive rounds and matches, so a lock must not carry over into the next one
+fn reset_goals(mut commands: Commands, q_goals: Query<Entity, With<GoalType>>) {
+    for goal in &q_goals {
+        commands
+            .entity(goal)
+            .remove::<(LockedGoal, ColliderDisabled)>();
+    }
+}
+
+/// Applies collected handicaps to the opponent and lets them run out
+pub struct HandicapPlugin;
+
+impl Plugin for HandicapPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<HandicapRules>()
+            .add_event::<HandicapEvent>()
+            .add_systems(OnExit(InMatch), reset_goals)
+            // Rounds only change when one ends, a kickoff after a goal keeps the lock
+            .add_systems(
+                OnEnter(MatchState::Kickoff),
+                reset_goals.run_if(any_match_filter::<Changed<Rounds>>),
+            )
+            .add_systems(
+                Update,
+                (
+                    apply_handicaps
+                        .after(collect_pickups)
+                        .in_set(GameplaySet::Resolve),
+                    (
+                        expire::<InvertedControls>,
+                        expire::<Slowed>,
+                        expire::<Frozen>,
+                        expire::<DrainedSprint>,
+                        expire::<LockedGoal>,
+                        unlock_goals,
+                    )
+                        .chain()
+                        .in_set(GameplaySet::Flow),
+                )
+                    .run_if(in_state(MatchState::Playing)),
+            );
+    }
+}
+
+/// Flat rings around the feet of a hindered player, one per handicap
+#[allow(clippy::type_complexity)]
+fn draw_handicap_rings(
+    mut painter: ShapePainter,
+    q_players: Query<
+        (
+            &Transform,
+            &Character,
+            Has<Player1>,
+            Has<InvertedControls>,
+            Has<Slowed>,
+            Has<DrainedSprint>,
+            Has<Frozen>,
+        ),
+        Or<(With<Player1>, With<Player2>)>,
+    >,
+    q_locks: Query<(&LockedGoal, &GoalType)>,
+) {
+    for (transform, character, is_player_1, inverted, slowed, drained, frozen) in &q_players {
+        let player = if is_player_1 {
+            PlayerType::First
+        } else {
+            PlayerType::Second
+        };
+        let locked = goal_lock_left(&q_locks, player).is_some();
+        let active = [
+            (inverted, Handicap::InvertedControls),
+            (slowed, Handicap::Slowed),
+            (drained, Handicap::DrainedSprint),
+            (frozen, Handicap::Frozen),
+            (locked, Handicap::LockedGoal),
+        ];
+        painter.set_translation(transform.translation + Vec3::Y * 0.05);
+        painter.set_rotation(Quat::from_rotation_x(FRAC_PI_2));
+        painter.hollow = true;
+        painter.thickness = 0.1;
+        let mut radius = character.radius + 0.3;
+        for (_, handicap) in active.iter().filter(|(on, _)| *on) {
+            painter.set_color(handicap.color());
+            painter.circle(radius);
+            radius += 0.2;
+        }
+    }
+    painter.hollow = false;
+}
+
+#[derive(Component, Deref, DerefMut)]
+struct HandicapMessage(Timer);
+
+fn show_handicap_message(
+    mut commands: Commands,
+    mut handicap_events: EventReader<HandicapEvent>,
+    q_players: Query<Has<Player1>>,
+    q_messages: Query<Entity, With<HandicapMessage>>,
+) {
+    for ev in handicap_events.read() {
+        let Ok(collector_is_player_1) = q_players.get(ev.collector) else {
+            continue;
+        };
+        let victim = if collector_is_player_1 { 2 } else { 1 };
+        for message in &q_messages {
+            commands.entity(message).despawn();
+        }
+        commands.spawn((
+            HandicapMessage(Timer::from_seconds(MESSAGE_TIME, TimerMode::Once)),
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                top: Val::Px(64.0),
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            Text(format!("Player {victim}: {}!", ev.handicap.name())),
+            TextColor(WHITE),
+            TextFont {
+                font_size: 32.0,
+                ..Default::default()
+            },
+            StateScoped(InMatch),
+        ));
+    }
+}
+
+fn hide_handicap_message(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut q_messages: Query<(Entity, &mut HandicapMessage)>,
+) {
+    for (entity, mut message) in &mut q_messages {
+        if message.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Marks hindered players with rings and announces every handicap
+pub struct HandicapRenderPlugin;
+
+impl Plugin for HandicapRenderPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_systems(
+            Update,
+            (
+                draw_handicap_rings,
+                show_handicap_message,
+                hide_handicap_message,
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{slowed, HandicapRules};
+    use crate::character::Character;
+
+    #[test]
+    fn slowed_when_applied_then_only_speed_and_acceleration_shrink() {
+        // given
+        let base = Character::default();
+        let rules = HandicapRules::default();
+
+        // when
+        let character = slowed(base, &rules);
+
+        // then
+        assert_eq!(character.speed, base.speed * rules.slow_factor);
+        assert_eq!(
+            character.acceleration,
+            base.acceleration * rules.slow_factor
+        );
+        assert_eq!(character.mass, base.mass);
+    }
+}
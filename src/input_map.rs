@@ -0,0 +1,380 @@
+use bevy::{
+    ecs::resource::Resource,
+    input::{gamepad::GamepadButton, keyboard::KeyCode},
+    log::warn,
+};
+use serde::{Deserialize, Serialize};
+
+/// Something a player can do, bound to a key and a gamepad button
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Sprint,
+    Kick,
+    Tackle,
+}
+
+impl Action {
+    pub const ALL: [Action; 7] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Sprint,
+        Action::Kick,
+        Action::Tackle,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "Move up",
+            Action::MoveDown => "Move down",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Sprint => "Sprint",
+            Action::Kick => "Kick",
+            Action::Tackle => "Tackle",
+        }
+    }
+}
+
+/// The key and gamepad button of one action
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Binding {
+    pub action: Action,
+    pub key: KeyCode,
+    pub button: GamepadButton,
+}
+
+/// Bindings of one player, one per action
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PlayerBindings(pub Vec<Binding>);
+
+impl PlayerBindings {
+    fn new(keys: [KeyCode; 7]) -> Self {
+        let buttons = [
+            GamepadButton::DPadUp,
+            GamepadButton::DPadDown,
+            GamepadButton::DPadLeft,
+            GamepadButton::DPadRight,
+            GamepadButton::South,
+            GamepadButton::West,
+            GamepadButton::East,
+        ];
+        PlayerBindings(
+            Action::ALL
+                .into_iter()
+                .zip(keys)
+                .zip(buttons)
+                .map(|((action, key), button)| Binding {
+                    action,
+                    key,
+                    button,
+                })
+                .collect(),
+        )
+    }
+
+    fn binding(&self, action: Action) -> Option<&Binding> {
+        self.0.iter().find(|b| b.action == action)
+    }
+
+    fn binding_mut(&mut self, action: Action) -> Option<&mut Binding> {
+        self.0.iter_mut().find(|b| b.action == action)
+    }
+
+    pub fn key(&self, action: Action) -> Option<KeyCode> {
+        self.binding(action).map(|b| b.key)
+    }
+
+    pub fn button(&self, action: Action) -> Option<GamepadButton> {
+        self.binding(action).map(|b| b.button)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = KeyCode> + '_ {
+        self.0.iter().map(|b| b.key)
+    }
+}
+
+/// A key or button is already bound to another action
+#[derive(Debug, PartialEq)]
+pub struct BindingConflict {
+    /// Index of the player whose binding is in the way
+    pub player: usize,
+    pub action: Action,
+}
+
+/// Key and gamepad bindings of both players, index 0 is player 1
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct InputMap(pub [PlayerBindings; 2]);
+
+impl Default for InputMap {
+    fn default() -> Self {
+        InputMap([
+            PlayerBindings::new([
+                KeyCode::KeyW,
+                KeyCode::KeyS,
+                KeyCode::KeyA,
+                KeyCode::KeyD,
+                KeyCode::ShiftLeft,
+                KeyCode::Space,
+                KeyCode::KeyE,
+            ]),
+            PlayerBindings::new([
+                KeyCode::ArrowUp,
+                KeyCode::ArrowDown,
+                KeyCode::ArrowLeft,
+                KeyCode::ArrowRight,
+                KeyCode::ShiftRight,
+                KeyCode::ControlRight,
+                KeyCode::Slash,
+            ]),
+        ])
+    }
+}
+
+impl InputMap {
+    /// Both players share the keyboard, so a key may only be bound once on the whole map
+    pub fn key_conflict(
+        &self,
+        player: usize,
+        action: Action,
+        key: KeyCode,
+    ) -> Option<BindingConflict> {
+        self.0
+            .iter()
+            .enumerate()
+            .find_map(|(other_player, bindings)| {
+                bindings
+                    .0
+                    .iter()
+                    .find(|b| b.key == key && (other_player, b.action) != (player, action))
+                    .map(|b| BindingConflict {
+                        player: other_player,
+                        action: b.action,
+                    })
+            })
+    }
+
+    /// Every player has their own gamepad, so buttons only clash within one player's bindings
+    pub fn button_conflict(
+        &self,
+        player: usize,
+        action: Action,
+        button: GamepadButton,
+    ) -> Option<BindingConflict> {
+        self.0[player]
+            .0
+            .iter()
+            .find(|b| b.button == button && b.action != action)
+            .map(|b| BindingConflict {
+                player,
+                action: b.action,
+            })
+    }
+
+    pub fn bind_key(
+        &mut self,
+        player: usize,
+        action: Action,
+        key: KeyCode,
+    ) -> Result<(), BindingConflict> {
+        if let Some(conflict) = self.key_conflict(player, action, key) {
+            return Err(conflict);
+        }
+        if let Some(binding) = self.0[player].binding_mut(action) {
+            binding.key = key;
+        }
+        Ok(())
+    }
+
+    pub fn bind_button(
+        &mut self,
+        player: usize,
+        action: Action,
+        button: GamepadButton,
+    ) -> Result<(), BindingConflict> {
+        if let Some(conflict) = self.button_conflict(player, action, button) {
+            return Err(conflict);
+        }
+        if let Some(binding) = self.0[player].binding_mut(action) {
+            binding.button = button;
+        }
+        Ok(())
+    }
+
+    /// Every key or button bound twice, which happens when the config file was edited by hand
+    pub fn conflicts(&self) -> Vec<BindingConflict> {
+        self.0
+            .iter()
+            .enumerate()
+            .flat_map(|(player, bindings)| {
+                bindings.0.iter().filter_map(move |b| {
+                    self.key_conflict(player, b.action, b.key)
+                        .or_else(|| self.button_conflict(player, b.action, b.button))
+                })
+            })
+            .collect()
+    }
+
+    /// Reads the saved bindings, falling back to the defaults if none are saved or they are invalid
+    pub fn load() -> Self {
+        let Some(saved) = storage::read() else {
+            return InputMap::default();
+        };
+        match ron::from_str::<InputMap>(&saved) {
+            Ok(map) if map.is_complete() && map.conflicts().is_empty() => map,
+            Ok(_) => {
+                warn!("Saved controls are incomplete or conflicting, using the defaults");
+                InputMap::default()
+            }
+            Err(e) => {
+                warn!("Could not read the saved controls: {e}");
+                InputMap::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(serialized) => storage::write(&serialized),
+            Err(e) => warn!("Could not save the controls: {e}"),
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.0.iter().all(|bindings| {
+            Action::ALL
+                .iter()
+                .all(|action| bindings.binding(*action).is_some())
+        })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod storage {
+    use bevy::log::warn;
+
+    /// Next to where the game is started from
+    const CONTROLS_FILE: &str = "controls.ron";
+
+    pub fn read() -> Option<String> {
+        std::fs::read_to_string(CONTROLS_FILE).ok()
+    }
+
+    pub fn write(serialized: &str) {
+        if let Err(e) = std::fs::write(CONTROLS_FILE, serialized) {
+            warn!("Could not write {CONTROLS_FILE}: {e}");
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod storage {
+    use bevy::log::warn;
+
+    const CONTROLS_KEY: &str = "slatchhammer.controls";
+
+    fn local_storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok()?
+    }
+
+    pub fn read() -> Option<String> {
+        local_storage()?.get_item(CONTROLS_KEY).ok()?
+    }
+
+    pub fn write(serialized: &str) {
+        let saved = local_storage().map(|storage| storage.set_item(CONTROLS_KEY, serialized));
+        if !matches!(saved, Some(Ok(()))) {
+            warn!("Could not save the controls to the local storage");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::{gamepad::GamepadButton, keyboard::KeyCode};
+
+    use super::{Action, BindingConflict, InputMap};
+
+    #[test]
+    fn bind_key_when_other_player_uses_key_then_conflict() {
+        // given
+        let mut map = InputMap::default();
+
+        // when
+        let bound = map.bind_key(1, Action::MoveUp, KeyCode::KeyW);
+
+        // then
+        assert_eq!(
+            bound,
+            Err(BindingConflict {
+                player: 0,
+                action: Action::MoveUp
+            })
+        );
+        assert_eq!(map.0[1].key(Action::MoveUp), Some(KeyCode::ArrowUp));
+    }
+
+    #[test]
+    fn bind_key_when_key_is_free_then_rebound() {
+        // given
+        let mut map = InputMap::default();
+
+        // when
+        let bound = map.bind_key(0, Action::Sprint, KeyCode::KeyQ);
+
+        // then
+        assert_eq!(bound, Ok(()));
+        assert_eq!(map.0[0].key(Action::Sprint), Some(KeyCode::KeyQ));
+        assert!(map.conflicts().is_empty());
+    }
+
+    #[test]
+    fn bind_button_when_other_player_uses_button_then_no_conflict() {
+        // given
+        let mut map = InputMap::default();
+        map.bind_button(0, Action::Sprint, GamepadButton::RightTrigger)
+            .unwrap();
+
+        // when
+        let bound = map.bind_button(1, Action::Sprint, GamepadButton::RightTrigger);
+
+        // then
+        assert_eq!(bound, Ok(()));
+    }
+
+    #[test]
+    fn bind_button_when_same_player_uses_button_then_conflict() {
+        // given
+        let mut map = InputMap::default();
+
+        // when
+        let bound = map.bind_button(0, Action::Sprint, GamepadButton::DPadUp);
+
+        // then
+        assert_eq!(
+            bound,
+            Err(BindingConflict {
+                player: 0,
+                action: Action::MoveUp
+            })
+        );
+    }
+
+    #[test]
+    fn input_map_when_serialized_then_reads_back_the_same() {
+        // given
+        let mut map = InputMap::default();
+        map.bind_key(1, Action::MoveLeft, KeyCode::KeyJ).unwrap();
+
+        // when
+        let serialized = ron::to_string(&map).unwrap();
+
+        // then
+        assert_eq!(ron::from_str::<InputMap>(&serialized).unwrap(), map);
+    }
+}
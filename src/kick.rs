@@ -0,0 +1,234 @@
+use bevy::{
+    app::{Plugin, Update},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::EventWriter,
+        query::{With, Without},
+        resource::Resource,
+        schedule::IntoScheduleConfigs,
+        system::{Query, Res},
+    },
+    math::{vec3, Vec3},
+    state::condition::in_state,
+    time::Time,
+    transform::components::Transform,
+};
+use bevy_rapier3d::dynamics::ExternalImpulse;
+
+use crate::{
+    ball::Ball,
+    character::Character,
+    game_state::{GameplaySet, MatchState},
+    player::PlayerInput,
+    sprint::ApplySprintEvent,
+};
+
+/// How hard, how often and how close players kick
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct KickRules {
+    /// Largest distance between player and ball, measured flat on the ground
+    pub range: f32,
+    /// Cosine of the largest angle between facing direction and ball
+    pub min_facing: f32,
+    /// Impulse of a kick released right away
+    pub min_impulse: f32,
+    /// Impulse of a fully charged kick
+    pub max_impulse: f32,
+    /// Seconds of holding the kick until it is fully charged
+    pub charge_time: f32,
+    /// Seconds after a kick before the next one
+    pub cooldown: f32,
+    /// Sprint seconds a fully charged kick costs, weaker kicks cost less. Zero kicks for free.
+    pub stamina_cost: f32,
+}
+
+impl Default for KickRules {
+    fn default() -> Self {
+        KickRules {
+            range: 2.0,
+            min_facing: 0.5,
+            min_impulse: 4.0,
+            max_impulse: 14.0,
+            charge_time: 1.0,
+            cooldown: 0.5,
+            stamina_cost: 1.0,
+        }
+    }
+}
+
+/// Charge and cooldown of a player's kick
+#[derive(Component, Clone, Default, Debug, PartialEq)]
+pub struct KickState {
+    /// Seconds the kick has been held
+    charge: f32,
+    holding: bool,
+    cooldown: f32,
+}
+
+impl KickState {
+    /// Charges while the kick is held. Returns the charge from 0 to 1 once it is released
+    /// and the cooldown is over.
+    pub fn update(&mut self, kick: bool, dt: f32, rules: &KickRules) -> Option<f32> {
+        self.cooldown = (self.cooldown - dt).max(0.0);
+        if kick {
+            self.holding = true;
+            self.charge = (self.charge + dt).min(rules.charge_time);
+            return None;
+        }
+        let released = std::mem::take(&mut self.holding);
+        let charge = std::mem::take(&mut self.charge);
+        (released && self.cooldown <= 0.0).then(|| charge / rules.charge_time.max(f32::EPSILON))
+    }
+
+    pub fn start_cooldown(&mut self, rules: &KickRules) {
+        self.cooldown = rules.cooldown;
+    }
+
+    /// The player kicked the ball a moment ago
+    pub fn is_cooling_down(&self) -> bool {
+        self.cooldown > 0.0
+    }
+}
+
+/// The ball is close enough and in front of the player
+pub fn in_kick_range(player: Vec3, facing: Vec3, ball: Vec3, rules: &KickRules) -> bool {
+    let offset = vec3(ball.x - player.x, 0.0, ball.z - player.z);
+    let facing = vec3(facing.x, 0.0, facing.z).normalize_or_zero();
+    offset.length() <= rules.range && offset.normalize_or_zero().dot(facing) >= rules.min_facing
+}
+
+/// Where the ball flies when kicked with `charge` from 0 to 1. Kicks stay flat on the ground,
+/// lifted balls would fly over the walls.
+pub fn kick_impulse(facing: Vec3, charge: f32, rules: &KickRules) -> Vec3 {
+    let power = rules.min_impulse + (rules.max_impulse - rules.min_impulse) * charge;
+    vec3(facing.x, 0.0, facing.z).normalize_or_zero() * power
+}
+
+fn kick_ball(
+    time: Res<Time>,
+    rules: Res<KickRules>,
+    mut q_players: Query<
+        (Entity, &PlayerInput, &mut KickState, &Character, &Transform),
+        Without<Ball>,
+    >,
+    mut q_ball: Query<(&Transform, &mut ExternalImpulse), With<Ball>>,
+    mut sprint_events: EventWriter<ApplySprintEvent>,
+) {
+    let Ok((ball, mut ball_impulse)) = q_ball.single_mut() else {
+        return;
+    };
+    for (entity, input, mut kick, character, transform) in q_players.iter_mut() {
+        let Some(charge) = kick.update(input.kick, time.delta_secs(), &rules) else {
+            continue;
+        };
+        // `apply_movement` turns the player's +z towards where it moves
+        let facing = transform.rotation * Vec3::Z;
+        if !in_kick_range(transform.translation, facing, ball.translation, &rules) {
+            continue;
+        }
+        ball_impulse.impulse += kick_impulse(facing, charge, &rules) * character.kick_power;
+        kick.start_cooldown(&rules);
+        let stamina = rules.stamina_cost * charge;
+        if stamina > 0.0 {
+            sprint_events.write(ApplySprintEvent {
+                amount: -stamina,
+                target: entity,
+            });
+        }
+    }
+}
+
+/// Kicks the ball away from players who release their kick next to it
+pub struct KickPlugin;
+
+impl Plugin for KickPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<KickRules>().add_systems(
+            Update,
+            kick_ball
+                .run_if(in_state(MatchState::Playing))
+                .in_set(GameplaySet::Movement),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{vec3, Vec3};
+
+    use super::{in_kick_range, kick_impulse, KickRules, KickState};
+
+    #[test]
+    fn update_when_held_then_charges_and_kicks_on_release() {
+        // given
+        let rules = KickRules::default();
+        let mut kick = KickState::default();
+
+        // when
+        let held = kick.update(true, rules.charge_time / 2.0, &rules);
+        let released = kick.update(false, 0.0, &rules);
+
+        // then
+        assert_eq!(held, None);
+        assert_eq!(released, Some(0.5));
+        assert_eq!(kick.update(false, 0.0, &rules), None);
+    }
+
+    #[test]
+    fn update_when_held_longer_than_charge_time_then_full_power() {
+        // given
+        let rules = KickRules::default();
+        let mut kick = KickState::default();
+        kick.update(true, rules.charge_time * 3.0, &rules);
+
+        // when
+        let released = kick.update(false, 0.0, &rules);
+
+        // then
+        assert_eq!(released, Some(1.0));
+    }
+
+    #[test]
+    fn update_when_cooling_down_then_release_does_not_kick() {
+        // given
+        let rules = KickRules::default();
+        let mut kick = KickState::default();
+        kick.start_cooldown(&rules);
+        kick.update(true, rules.cooldown / 4.0, &rules);
+
+        // when
+        let released = kick.update(false, rules.cooldown / 4.0, &rules);
+
+        // then
+        assert_eq!(released, None);
+    }
+
+    #[test]
+    fn in_kick_range_when_ball_behind_or_far_then_false() {
+        let rules = KickRules::default();
+        let player = vec3(0.0, 1.0, 0.0);
+        assert!(in_kick_range(player, Vec3::X, vec3(1.0, 0.5, 0.2), &rules));
+        assert!(!in_kick_range(
+            player,
+            Vec3::X,
+            vec3(-1.0, 0.5, 0.0),
+            &rules
+        ));
+        assert!(!in_kick_range(player, Vec3::X, vec3(5.0, 0.5, 0.0), &rules));
+    }
+
+    #[test]
+    fn kick_impulse_when_charged_then_stronger_and_facing_forward() {
+        // given
+        let rules = KickRules::default();
+
+        // when
+        let weak = kick_impulse(Vec3::X, 0.0, &rules);
+        let strong = kick_impulse(Vec3::X, 1.0, &rules);
+
+        // then
+        assert!(strong.length() > weak.length());
+        assert!(strong.x > 0.0 && strong.y == 0.0 && strong.z == 0.0);
+    }
+}
@@ -0,0 +1,83 @@
+pub mod ai;
+pub mod arena;
+pub mod arena_assets;
+pub mod arena_features;
+pub mod balance;
+pub mod ball;
+pub mod bars;
+pub mod camera;
+pub mod character;
+pub mod character_menu;
+pub mod colors;
+pub mod constants;
+pub mod controls;
+pub mod controls_menu;
+pub mod game_state;
+pub mod goals;
+pub mod handicap;
+pub mod input_map;
+pub mod kick;
+pub mod lobby;
+pub mod lobby_menu;
+pub mod match_rules;
+pub mod menu;
+pub mod network;
+pub mod online;
+pub mod player;
+pub mod points;
+pub mod powerup;
+pub mod prediction;
+pub mod rollback;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod server;
+pub mod simulation;
+pub mod sprint;
+pub mod tackle;
+pub mod touch;
+pub mod ui;
+
+use bevy::app::{PluginGroup, PluginGroupBuilder};
+
+pub use ball::Ball;
+pub use game_state::{EndState, GameEndEvent, GameTime, InMatch, MatchState};
+pub use goals::{GoalEvent, PlayerType};
+pub use player::{Player1, Player2, PlayerInput};
+pub use points::Points;
+pub use simulation::{headless_app, SlatchhammerGamePlugin, TICK_RATE};
+pub use sprint::{ShowBars, SprintState};
+
+use arena::ArenaRenderPlugin;
+use arena_assets::ArenaAssetPlugin;
+use arena_features::ArenaFeaturesRenderPlugin;
+use ball::BallRenderPlugin;
+use bars::StaminaBarsPlugin;
+use camera::CameraPlugin;
+use controls::PlayerControlsPlugin;
+use goals::GoalRenderPlugin;
+use handicap::HandicapRenderPlugin;
+use menu::MenuPlugin;
+use player::PlayerRenderPlugin;
+use powerup::PowerUpRenderPlugin;
+use ui::UiPlugin;
+
+/// Everything the windowed game adds on top of the simulation: camera, meshes, controls and UI
+pub struct SlatchhammerClientPlugins;
+
+impl PluginGroup for SlatchhammerClientPlugins {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(CameraPlugin)
+            .add(ArenaAssetPlugin)
+            .add(ArenaRenderPlugin)
+            .add(ArenaFeaturesRenderPlugin)
+            .add(GoalRenderPlugin)
+            .add(BallRenderPlugin)
+            .add(PlayerRenderPlugin)
+            .add(StaminaBarsPlugin)
+            .add(PowerUpRenderPlugin)
+            .add(HandicapRenderPlugin)
+            .add(PlayerControlsPlugin)
+            .add(UiPlugin)
+            .add(MenuPlugin)
+    }
+}
@@ -0,0 +1,377 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    app::App,
+    ecs::{
+        component::Component,
+        query::With,
+        world::{Mut, World},
+    },
+    math::{vec3, Vec3},
+    state::state::{NextState, State},
+    transform::components::Transform,
+};
+use bevy_rapier3d::dynamics::Velocity;
+
+use crate::{
+    ball::Ball,
+    game_state::{GameTime, MatchState, PhaseTimer},
+    goals::PlayerType,
+    network::{BodySnapshot, LobbyInfo, Snapshot, WorldSnapshot, LOBBY_CODE_LENGTH},
+    player::{Player1, Player2, PlayerInput},
+    points::Points,
+    powerup::PowerUpRules,
+};
+
+pub type ClientId = u64;
+
+/// Inputs a client may send ahead of the match, older ones are dropped
+const MAX_QUEUED_INPUTS: usize = 8;
+
+enum LobbyState {
+    Waiting,
+    /// Every running lobby simulates its match in its own app, so matches never interfere
+    Running {
+        app: Box<App>,
+        tick: u32,
+    },
+}
+
+/// Two player slots and the match they play, once both are ready
+pub struct Lobby {
+    pub code: String,
+    pub players: [Option<ClientId>; 2],
+    ready: [bool; 2],
+    /// The input each player currently plays with
+    inputs: [PlayerInput; 2],
+    /// Inputs sent by the clients, the match applies one per tick
+    queued_inputs: [VecDeque<(u32, PlayerInput)>; 2],
+    /// Tick of the last input applied for each player, so clients can reconcile their prediction
+    applied_input_ticks: [Option<u32>; 2],
+    state: LobbyState,
+    /// Set when the lobby info has to be sent to the players again
+    changed: bool,
+}
+
+impl Lobby {
+    pub fn new(code: String) -> Self {
+        Lobby {
+            code,
+            players: [None, None],
+            ready: [false; 2],
+            inputs: [PlayerInput::default(); 2],
+            queued_inputs: Default::default(),
+            applied_input_ticks: [None; 2],
+            state: LobbyState::Waiting,
+            changed: true,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(self.state, LobbyState::Running { .. })
+    }
+
+    pub fn has_free_slot(&self) -> bool {
+        !self.is_running() && self.players.contains(&None)
+    }
+
+    pub fn slot_of(&self, client: ClientId) -> Option<usize> {
+        self.players.iter().position(|p| *p == Some(client))
+    }
+
+    /// Puts the client into the first free slot
+    pub fn join(&mut self, client: ClientId) -> Option<PlayerType> {
+        if !self.has_free_slot() {
+            return None;
+        }
+        let slot = self.players.iter().position(Option::is_none)?;
+        self.players[slot] = Some(client);
+        self.changed = true;
+        Some(player_type(slot))
+    }
+
+    pub fn set_ready(&mut self, client: ClientId) {
+        if let Some(slot) = self.slot_of(client) {
+            self.ready[slot] = true;
+            self.changed = true;
+        }
+    }
+
+    pub fn queue_input(&mut self, client: ClientId, tick: u32, input: PlayerInput) {
+        let Some(slot) = self.slot_of(client) else {
+            return;
+        };
+        // Clients can send anything, players only ever move flat and at most at full speed.
+        // NaN or infinite movement would poison the physics of both players, so it stands still.
+        let movement = vec3(input.movement.x, 0.0, input.movement.z);
+        let movement = match movement.is_finite() {
+            true => movement.clamp_length_max(1.0),
+            false => Vec3::ZERO,
+        };
+        let input = PlayerInput { movement, ..input };
+        let queue = &mut self.queued_inputs[slot];
+        queue.push_back((tick, input));
+        if queue.len() > MAX_QUEUED_INPUTS {
+            queue.pop_front();
+        }
+    }
+
+    pub fn info_for(&self, client: ClientId) -> Option<LobbyInfo> {
+        Some(LobbyInfo {
+            code: self.code.clone(),
+            you: player_type(self.slot_of(client)?),
+            joined: self.players.map(|p| p.is_some()),
+            ready: self.ready,
+        })
+    }
+
+    /// Returns true once, after something the players should know about changed
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    /// Starts the match once both players are ready, then simulates one tick of it.
+    /// Returns the snapshot after the tick while the match runs.
+    pub fn step(&mut self, new_match: fn() -> App) -> Option<Snapshot> {
+        if !self.is_running() && self.players.iter().all(Option::is_some) && self.ready == [true; 2]
+        {
+            let mut app = Box::new(new_match());
+            // Pickups are not part of the snapshots yet
+            app.insert_resource(PowerUpRules::disabled());
+            app.world_mut()
+                .resource_mut::<NextState<MatchState>>()
+                .set(MatchState::Kickoff);
+            self.state = LobbyState::Running { app, tick: 0 };
+            self.reset_inputs();
+        }
+        let LobbyState::Running { app, tick } = &mut self.state else {
+            return None;
+        };
+
+        for slot in 0..2 {
+            // Without a new input the player keeps doing what it did
+            if let Some((input_tick, input)) = self.queued_inputs[slot].pop_front() {
+                self.inputs[slot] = input;
+                self.applied_input_ticks[slot] = Some(input_tick);
+            }
+        }
+        let [input_1, input_2] = self.inputs;
+        let world = app.world_mut();
+        if let Some(mut input) = player_input::<Player1>(world) {
+            *input = input_1;
+        }
+        if let Some(mut input) = player_input::<Player2>(world) {
+            *input = input_2;
+        }
+        app.update();
+        *tick += 1;
+        let snapshot = Snapshot {
+            tick: *tick,
+            input_ticks: self.applied_input_ticks,
+            ..snapshot(app.world_mut())
+        };
+
+        if snapshot.state == MatchState::MainMenu {
+            // The match is over, a rematch needs both players to get ready again
+            self.state = LobbyState::Waiting;
+            self.ready = [false; 2];
+            self.reset_inputs();
+            self.changed = true;
+        }
+        Some(snapshot)
+    }
+
+    fn reset_inputs(&mut self) {
+        self.inputs = [PlayerInput::default(); 2];
+        self.queued_inputs = Default::default();
+        self.applied_input_ticks = [None; 2];
+    }
+}
+
+/// Turns a running number into a short code that is easy to type.
+/// Different numbers give different codes, until all of them are used up.
+pub fn lobby_code(n: u32) -> String {
+    let code_count = 26u64.pow(LOBBY_CODE_LENGTH as u32);
+    // 7919 is prime and shares no factor with 26^4, so this is a permutation of all codes
+    let mut x = (n as u64 * 7919 + 4711) % code_count;
+    let mut code = String::with_capacity(LOBBY_CODE_LENGTH);
+    for _ in 0..LOBBY_CODE_LENGTH {
+        code.push((b'A' + (x % 26) as u8) as char);
+        x /= 26;
+    }
+    code
+}
+
+fn player_type(slot: usize) -> PlayerType {
+    match slot {
+        0 => PlayerType::First,
+        _ => PlayerType::Second,
+    }
+}
+
+pub(crate) fn player_input<T: Component>(world: &mut World) -> Option<Mut<'_, PlayerInput>> {
+    world
+        .query_filtered::<&mut PlayerInput, With<T>>()
+        .single_mut(world)
+        .ok()
+}
+
+pub(crate) fn snapshot(world: &mut World) -> Snapshot {
+    Snapshot {
+        tick: 0,
+        input_ticks: [None; 2],
+        state: *world.resource::<State<MatchState>>().get(),
+        phase_time_left: world
+            .get_resource::<PhaseTimer>()
+            .map(|timer| timer.remaining())
+            .unwrap_or_default(),
+        world: world_snapshot(world),
+    }
+}
+
+fn body<T: Component>(world: &mut World) -> Option<BodySnapshot> {
+    world
+        .query_filtered::<(&Transform, &Velocity), With<T>>()
+        .single(world)
+        .ok()
+        .map(|(transform, velocity)| BodySnapshot::new(transform, velocity))
+}
+
+fn world_snapshot(world: &mut World) -> Option<WorldSnapshot> {
+    Some(WorldSnapshot {
+        player_1: body::<Player1>(world)?,
+        player_2: body::<Player2>(world)?,
+        ball: body::<Ball>(world)?,
+        points: *world.query::<&Points>().single(world).ok()?,
+        game_time_left: world
+            .query::<&GameTime>()
+            .single(world)
+            .ok()?
+            .current_time(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use bevy::math::{vec3, Vec3};
+
+    use super::{lobby_code, Lobby};
+    use crate::{
+        game_state::MatchState, goals::PlayerType, player::PlayerInput, simulation::headless_app,
+    };
+
+    #[test]
+    fn join_when_lobby_is_full_then_none() {
+        // given
+        let mut lobby = Lobby::new("ABCD".into());
+        lobby.join(1);
+        lobby.join(2);
+
+        // when
+        let joined = lobby.join(3);
+
+        // then
+        assert_eq!(joined, None);
+        assert!(!lobby.has_free_slot());
+    }
+
+    #[test]
+    fn join_when_first_player_left_then_takes_free_slot() {
+        // given
+        let mut lobby = Lobby::new("ABCD".into());
+        lobby.join(1);
+        lobby.join(2);
+        lobby.players[0] = None;
+
+        // when
+        let joined = lobby.join(3);
+
+        // then
+        assert_eq!(joined, Some(PlayerType::First));
+    }
+
+    #[test]
+    fn step_when_only_one_player_is_ready_then_waits() {
+        // given
+        let mut lobby = Lobby::new("ABCD".into());
+        lobby.join(1);
+        lobby.join(2);
+        lobby.set_ready(1);
+
+        // when
+        let snapshot = lobby.step(headless_app);
+
+        // then
+        assert!(snapshot.is_none());
+        assert!(!lobby.is_running());
+    }
+
+    #[test]
+    fn step_when_both_players_are_ready_then_match_kicks_off() {
+        // given
+        let mut lobby = Lobby::new("ABCD".into());
+        lobby.join(1);
+        lobby.join(2);
+        lobby.set_ready(1);
+        lobby.set_ready(2);
+
+        // when
+        let snapshot = lobby.step(headless_app);
+
+        // then
+        assert_eq!(snapshot.map(|s| s.state), Some(MatchState::Kickoff));
+        assert!(lobby.is_running());
+    }
+
+    #[test]
+    fn step_when_inputs_are_queued_then_applies_one_per_tick() {
+        // given
+        let mut lobby = Lobby::new("ABCD".into());
+        lobby.join(1);
+        lobby.join(2);
+        lobby.set_ready(1);
+        lobby.set_ready(2);
+        lobby.step(headless_app);
+        lobby.queue_input(1, 7, PlayerInput::default());
+        lobby.queue_input(1, 8, PlayerInput::default());
+
+        // when
+        let snapshot = lobby.step(headless_app);
+
+        // then
+        assert_eq!(snapshot.map(|s| s.input_ticks), Some([Some(7), None]));
+    }
+
+    #[test]
+    fn step_when_movement_is_not_finite_then_player_stands_still() {
+        // given
+        let mut lobby = Lobby::new("ABCD".into());
+        lobby.join(1);
+        lobby.join(2);
+        lobby.set_ready(1);
+        lobby.set_ready(2);
+        lobby.step(headless_app);
+        let input = PlayerInput {
+            movement: vec3(f32::NAN, 0.0, f32::INFINITY),
+            ..Default::default()
+        };
+        lobby.queue_input(1, 1, input);
+
+        // when
+        let snapshot = lobby.step(headless_app).and_then(|s| s.world);
+
+        // then
+        assert_eq!(lobby.inputs[0].movement, Vec3::ZERO);
+        let player_1 = snapshot.expect("world snapshot").player_1;
+        assert!(player_1.translation.is_finite());
+    }
+
+    #[test]
+    fn lobby_code_when_called_for_many_numbers_then_codes_are_unique() {
+        let codes: HashSet<String> = (0..10_000).map(lobby_code).collect();
+        assert_eq!(codes.len(), 10_000);
+        assert!(codes.iter().all(|c| c.len() == 4));
+    }
+}
@@ -0,0 +1,252 @@
+use bevy::prelude::{Interaction, SubStates, Text};
+use bevy::{
+    app::{Plugin, Update},
+    ecs::{
+        component::Component,
+        event::{EventReader, EventWriter},
+        query::{Changed, QueryFilter, With},
+        resource::Resource,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    input::{
+        keyboard::{Key, KeyCode, KeyboardInput},
+        ButtonInput, ButtonState,
+    },
+    state::{
+        app::AppExtStates,
+        condition::in_state,
+        state::{NextState, OnEnter, State, StateSet},
+    },
+};
+
+use crate::{
+    game_state::MatchState,
+    goals::PlayerType,
+    menu::{button, spawn_screen, text},
+    network::{ClientMsg, LobbyInfo, LOBBY_CODE_LENGTH},
+    online::{CurrentLobby, LobbyNotice, SendToServer},
+};
+
+/// Which part of the lobby menu is shown while no match runs
+#[derive(SubStates, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[source(MatchState = MatchState::MainMenu)]
+enum LobbyScreen {
+    /// Quick match, create a lobby or join one by its code
+    #[default]
+    Browse,
+    /// In a lobby, waiting for both players to get ready
+    Joined,
+}
+
+#[derive(Component)]
+struct QuickJoinButton;
+
+#[derive(Component)]
+struct CreateLobbyButton;
+
+#[derive(Component)]
+struct ReadyButton;
+
+#[derive(Component)]
+struct LeaveLobbyButton;
+
+#[derive(Component)]
+struct CodeText;
+
+#[derive(Component)]
+struct NoticeText;
+
+#[derive(Component)]
+struct LobbyText;
+
+/// The lobby code typed so far
+#[derive(Resource, Default)]
+struct TypedCode(String);
+
+fn setup_browse_screen(mut commands: Commands) {
+    spawn_screen(&mut commands, LobbyScreen::Browse, |parent| {
+        parent.spawn(text("Slatchhammer", 64.0));
+        button(parent, QuickJoinButton, "Quick Match");
+        button(parent, CreateLobbyButton, "Create Lobby");
+        parent.spawn(text("Or type a lobby code and press Enter to join", 20.0));
+        parent.spawn((CodeText, text("", 32.0)));
+        parent.spawn((NoticeText, text("", 20.0)));
+    });
+}
+
+fn setup_joined_screen(mut commands: Commands) {
+    spawn_screen(&mut commands, LobbyScreen::Joined, |parent| {
+        parent.spawn((LobbyText, text("", 32.0)));
+        button(parent, ReadyButton, "Ready");
+        button(parent, LeaveLobbyButton, "Leave");
+        parent.spawn(text("Enter to get ready, Esc to leave", 20.0));
+    });
+}
+
+fn show_lobby_screen(
+    lobby: Res<CurrentLobby>,
+    screen: Res<State<LobbyScreen>>,
+    mut next_screen: ResMut<NextState<LobbyScreen>>,
+) {
+    let wanted = match lobby.0 {
+        Some(_) => LobbyScreen::Joined,
+        None => LobbyScreen::Browse,
+    };
+    if *screen.get() != wanted {
+        next_screen.set(wanted);
+    }
+}
+
+fn pressed<F: QueryFilter>(query: &Query<&Interaction, F>) -> bool {
+    query.iter().any(|i| *i == Interaction::Pressed)
+}
+
+fn type_lobby_code(
+    mut keys: EventReader<KeyboardInput>,
+    mut code: ResMut<TypedCode>,
+    mut send: EventWriter<SendToServer>,
+) {
+    for key in keys.read().filter(|k| k.state == ButtonState::Pressed) {
+        match &key.logical_key {
+            Key::Character(chars) => {
+                for c in chars.chars().filter(char::is_ascii_alphabetic) {
+                    if code.0.len() < LOBBY_CODE_LENGTH {
+                        code.0.push(c.to_ascii_uppercase());
+                    }
+                }
+            }
+            Key::Backspace => {
+                code.0.pop();
+            }
+            Key::Enter if code.0.len() == LOBBY_CODE_LENGTH => {
+                let code = std::mem::take(&mut code.0);
+                send.write(SendToServer(ClientMsg::JoinLobby { code }));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn handle_browse_buttons(
+    q_quick_join: Query<&Interaction, (Changed<Interaction>, With<QuickJoinButton>)>,
+    q_create: Query<&Interaction, (Changed<Interaction>, With<CreateLobbyButton>)>,
+    mut send: EventWriter<SendToServer>,
+) {
+    if pressed(&q_quick_join) {
+        send.write(SendToServer(ClientMsg::QuickJoin));
+    }
+    if pressed(&q_create) {
+        send.write(SendToServer(ClientMsg::CreateLobby));
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn handle_joined_input(
+    input: Res<ButtonInput<KeyCode>>,
+    q_ready: Query<&Interaction, (Changed<Interaction>, With<ReadyButton>)>,
+    q_leave: Query<&Interaction, (Changed<Interaction>, With<LeaveLobbyButton>)>,
+    mut lobby: ResMut<CurrentLobby>,
+    mut send: EventWriter<SendToServer>,
+) {
+    if pressed(&q_ready) || input.just_pressed(KeyCode::Enter) {
+        send.write(SendToServer(ClientMsg::Ready));
+    }
+    if pressed(&q_leave) || input.just_pressed(KeyCode::Escape) {
+        send.write(SendToServer(ClientMsg::LeaveLobby));
+        lobby.0 = None;
+    }
+}
+
+fn update_code_text(code: Res<TypedCode>, mut query: Query<&mut Text, With<CodeText>>) {
+    for mut text in &mut query {
+        text.0 = format!("{:_<width$}", code.0, width = LOBBY_CODE_LENGTH);
+    }
+}
+
+fn update_notice_text(notice: Res<LobbyNotice>, mut query: Query<&mut Text, With<NoticeText>>) {
+    for mut text in &mut query {
+        text.0 = notice.0.clone().unwrap_or_default();
+    }
+}
+
+fn lobby_description(info: &LobbyInfo) -> String {
+    let players = [PlayerType::First, PlayerType::Second]
+        .into_iter()
+        .enumerate()
+        .map(|(slot, player)| {
+            let status = match (info.joined[slot], info.ready[slot]) {
+                (false, _) => "waiting...",
+                (true, false) => "not ready",
+                (true, true) => "ready",
+            };
+            let you = if player == info.you { " (you)" } else { "" };
+            format!("Player {}{you}: {status}", slot + 1)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("Lobby {}\n\n{players}", info.code)
+}
+
+fn update_lobby_text(lobby: Res<CurrentLobby>, mut query: Query<&mut Text, With<LobbyText>>) {
+    let Some(info) = &lobby.0 else {
+        return;
+    };
+    for mut text in &mut query {
+        text.0 = lobby_description(info);
+    }
+}
+
+/// Menu of the online client to get into a lobby and get ready
+pub struct LobbyMenuPlugin;
+
+impl Plugin for LobbyMenuPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_sub_state::<LobbyScreen>()
+            .enable_state_scoped_entities::<LobbyScreen>()
+            .init_resource::<TypedCode>()
+            .add_systems(OnEnter(LobbyScreen::Browse), setup_browse_screen)
+            .add_systems(OnEnter(LobbyScreen::Joined), setup_joined_screen)
+            .add_systems(
+                Update,
+                (
+                    show_lobby_screen.run_if(in_state(MatchState::MainMenu)),
+                    (
+                        type_lobby_code,
+                        handle_browse_buttons,
+                        update_code_text,
+                        update_notice_text,
+                    )
+                        .run_if(in_state(LobbyScreen::Browse)),
+                    (handle_joined_input, update_lobby_text).run_if(in_state(LobbyScreen::Joined)),
+                ),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lobby_description;
+    use crate::{goals::PlayerType, network::LobbyInfo};
+
+    #[test]
+    fn lobby_description_when_opponent_missing_then_waiting() {
+        // given
+        let info = LobbyInfo {
+            code: "ABCD".into(),
+            you: PlayerType::First,
+            joined: [true, false],
+            ready: [true, false],
+        };
+
+        // when
+        let description = lobby_description(&info);
+
+        // then
+        assert_eq!(
+            description,
+            "Lobby ABCD\n\nPlayer 1 (you): ready\nPlayer 2: waiting..."
+        );
+    }
+}
@@ -0,0 +1,376 @@
+use bevy::ecs::{component::Component, resource::Resource};
+
+use crate::{constants::GAME_TIME, game_state::EndState, goals::PlayerType, points::Points};
+
+/// When a round and the whole match are over
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct MatchRules {
+    /// Seconds a round lasts, `None` plays without a clock
+    pub time_limit: Option<u64>,
+    /// The first player to score this many goals wins the round
+    pub score_limit: Option<u32>,
+    /// A round tied when the time runs out goes on until the next goal
+    pub golden_goal: bool,
+    /// How many rounds are played at most, whoever wins more than half of them wins the match
+    pub best_of: u32,
+    /// A round ends early once a player leads by this many goals
+    pub mercy_lead: Option<u32>,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        MatchRules {
+            time_limit: Some(GAME_TIME),
+            score_limit: None,
+            golden_goal: false,
+            best_of: 1,
+            mercy_lead: None,
+        }
+    }
+}
+
+/// The rules that can be picked in the menu, the first one is the default
+pub const RULE_PRESETS: [(&str, MatchRules); 5] = [
+    (
+        "Classic: 2 minutes",
+        MatchRules {
+            time_limit: Some(GAME_TIME),
+            score_limit: None,
+            golden_goal: false,
+            best_of: 1,
+            mercy_lead: None,
+        },
+    ),
+    (
+        "First to 5",
+        MatchRules {
+            time_limit: None,
+            score_limit: Some(5),
+            golden_goal: false,
+            best_of: 1,
+            mercy_lead: None,
+        },
+    ),
+    (
+        "2 minutes, golden goal",
+        MatchRules {
+            time_limit: Some(GAME_TIME),
+            score_limit: None,
+            golden_goal: true,
+            best_of: 1,
+            mercy_lead: None,
+        },
+    ),
+    (
+        "Best of 3: 1 minute, first to 3",
+        MatchRules {
+            time_limit: Some(60),
+            score_limit: Some(3),
+            golden_goal: true,
+            best_of: 3,
+            mercy_lead: None,
+        },
+    ),
+    (
+        "2 minutes, mercy at 5 ahead",
+        MatchRules {
+            time_limit: Some(GAME_TIME),
+            score_limit: None,
+            golden_goal: false,
+            best_of: 1,
+            mercy_lead: Some(5),
+        },
+    ),
+];
+
+/// How a round stands after a tick
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RoundOutcome {
+    Continue,
+    /// The time ran out on a tie, the next goal wins
+    Overtime,
+    Won(PlayerType),
+    Draw,
+}
+
+impl MatchRules {
+    /// Judges the round from its score, whether its clock ran out and whether it is in overtime
+    pub fn judge_round(&self, points: &Points, time_up: bool, overtime: bool) -> RoundOutcome {
+        let leader = match points.player_1.cmp(&points.player_2) {
+            std::cmp::Ordering::Greater => Some(PlayerType::First),
+            std::cmp::Ordering::Less => Some(PlayerType::Second),
+            std::cmp::Ordering::Equal => None,
+        };
+        let lead = points.player_1.abs_diff(points.player_2);
+        let top_score = points.player_1.max(points.player_2);
+        let decided = self.mercy_lead.is_some_and(|mercy| lead >= mercy)
+            || self.score_limit.is_some_and(|limit| top_score >= limit)
+            || overtime
+            || time_up;
+        match (leader, decided) {
+            (Some(leader), true) => RoundOutcome::Won(leader),
+            (None, true) if time_up && self.golden_goal => RoundOutcome::Overtime,
+            (None, true) if time_up => RoundOutcome::Draw,
+            _ => RoundOutcome::Continue,
+        }
+    }
+
+    /// Rounds a player has to win to take the match
+    pub fn rounds_to_win(&self) -> u32 {
+        self.best_of / 2 + 1
+    }
+
+    /// The end of the match once it is decided, `None` while more rounds are to be played
+    pub fn match_result(&self, rounds: &Rounds) -> Option<EndState> {
+        let to_win = self.rounds_to_win();
+        if rounds.player_1 >= to_win {
+            Some(EndState::Player1Won)
+        } else if rounds.player_2 >= to_win {
+            Some(EndState::Player2Won)
+        } else if rounds.played >= self.best_of.max(1) {
+            Some(rounds.end_state())
+        } else {
+            None
+        }
+    }
+}
+
+/// Rounds won by each player, the points only count within the current round
+#[derive(Component, Clone, Copy, Default, Debug, PartialEq)]
+pub struct Rounds {
+    pub player_1: u32,
+    pub player_2: u32,
+    /// Includes drawn rounds
+    pub played: u32,
+}
+
+impl Rounds {
+    pub fn record(&mut self, outcome: RoundOutcome) {
+        match outcome {
+            RoundOutcome::Won(PlayerType::First) => self.player_1 += 1,
+            RoundOutcome::Won(PlayerType::Second) => self.player_2 += 1,
+            RoundOutcome::Draw => {}
+            RoundOutcome::Continue | RoundOutcome::Overtime => return,
+        }
+        self.played += 1;
+    }
+
+    pub fn end_state(&self) -> EndState {
+        EndState::from_points(&Points {
+            player_1: self.player_1,
+            player_2: self.player_2,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MatchRules, RoundOutcome, Rounds, RULE_PRESETS};
+    use crate::{game_state::EndState, goals::PlayerType, points::Points};
+
+    fn points(player_1: u32, player_2: u32) -> Points {
+        Points { player_1, player_2 }
+    }
+
+    #[test]
+    fn judge_round_when_time_limit_only_then_decided_when_time_is_up() {
+        // given
+        let rules = MatchRules::default();
+
+        // when
+        let running = rules.judge_round(&points(3, 0), false, false);
+        let won = rules.judge_round(&points(1, 2), true, false);
+        let tied = rules.judge_round(&points(1, 1), true, false);
+
+        // then
+        assert_eq!(running, RoundOutcome::Continue);
+        assert_eq!(won, RoundOutcome::Won(PlayerType::Second));
+        assert_eq!(tied, RoundOutcome::Draw);
+    }
+
+    #[test]
+    fn judge_round_when_score_limit_reached_then_won_before_time_is_up() {
+        // given
+        let rules = MatchRules {
+            time_limit: None,
+            score_limit: Some(5),
+            ..Default::default()
+        };
+
+        // when
+        let close = rules.judge_round(&points(4, 4), false, false);
+        let reached = rules.judge_round(&points(5, 4), false, false);
+
+        // then
+        assert_eq!(close, RoundOutcome::Continue);
+        assert_eq!(reached, RoundOutcome::Won(PlayerType::First));
+    }
+
+    #[test]
+    fn judge_round_when_score_limit_not_reached_in_time_then_leader_wins() {
+        // given
+        let rules = MatchRules {
+            score_limit: Some(5),
+            ..Default::default()
+        };
+
+        // when
+        let outcome = rules.judge_round(&points(2, 1), true, false);
+
+        // then
+        assert_eq!(outcome, RoundOutcome::Won(PlayerType::First));
+    }
+
+    #[test]
+    fn judge_round_when_golden_goal_and_tied_in_time_then_overtime_until_next_goal() {
+        // given
+        let rules = MatchRules {
+            golden_goal: true,
+            ..Default::default()
+        };
+
+        // when
+        let tied = rules.judge_round(&points(2, 2), true, false);
+        let still_tied = rules.judge_round(&points(2, 2), false, true);
+        let golden_goal = rules.judge_round(&points(2, 3), false, true);
+
+        // then
+        assert_eq!(tied, RoundOutcome::Overtime);
+        assert_eq!(still_tied, RoundOutcome::Continue);
+        assert_eq!(golden_goal, RoundOutcome::Won(PlayerType::Second));
+    }
+
+    #[test]
+    fn judge_round_when_golden_goal_and_leader_in_time_then_no_overtime() {
+        // given
+        let rules = MatchRules {
+            golden_goal: true,
+            ..Default::default()
+        };
+
+        // when
+        let outcome = rules.judge_round(&points(1, 0), true, false);
+
+        // then
+        assert_eq!(outcome, RoundOutcome::Won(PlayerType::First));
+    }
+
+    #[test]
+    fn judge_round_when_mercy_lead_reached_then_won_early() {
+        // given
+        let rules = MatchRules {
+            mercy_lead: Some(3),
+            ..Default::default()
+        };
+
+        // when
+        let behind = rules.judge_round(&points(1, 3), false, false);
+        let mercy = rules.judge_round(&points(0, 3), false, false);
+
+        // then
+        assert_eq!(behind, RoundOutcome::Continue);
+        assert_eq!(mercy, RoundOutcome::Won(PlayerType::Second));
+    }
+
+    #[test]
+    fn judge_round_when_mercy_and_score_limit_then_whichever_comes_first() {
+        // given
+        let rules = MatchRules {
+            time_limit: None,
+            score_limit: Some(10),
+            mercy_lead: Some(4),
+            ..Default::default()
+        };
+
+        // when
+        let mercy = rules.judge_round(&points(4, 0), false, false);
+        let limit = rules.judge_round(&points(10, 8), false, false);
+        let running = rules.judge_round(&points(7, 4), false, false);
+
+        // then
+        assert_eq!(mercy, RoundOutcome::Won(PlayerType::First));
+        assert_eq!(limit, RoundOutcome::Won(PlayerType::First));
+        assert_eq!(running, RoundOutcome::Continue);
+    }
+
+    #[test]
+    fn match_result_when_single_round_then_decided_by_it() {
+        // given
+        let rules = MatchRules::default();
+        let mut won = Rounds::default();
+        let mut drawn = Rounds::default();
+
+        // when
+        won.record(RoundOutcome::Won(PlayerType::Second));
+        drawn.record(RoundOutcome::Draw);
+
+        // then
+        assert_eq!(rules.match_result(&won), Some(EndState::Player2Won));
+        assert_eq!(rules.match_result(&drawn), Some(EndState::Draw));
+        assert_eq!(rules.match_result(&Rounds::default()), None);
+    }
+
+    #[test]
+    fn match_result_when_best_of_three_then_two_rounds_win() {
+        // given
+        let rules = MatchRules {
+            best_of: 3,
+            ..Default::default()
+        };
+        let mut rounds = Rounds::default();
+
+        // when
+        rounds.record(RoundOutcome::Won(PlayerType::First));
+        let after_one = rules.match_result(&rounds);
+        rounds.record(RoundOutcome::Won(PlayerType::Second));
+        let after_two = rules.match_result(&rounds);
+        rounds.record(RoundOutcome::Won(PlayerType::First));
+
+        // then
+        assert_eq!(after_one, None);
+        assert_eq!(after_two, None);
+        assert_eq!(rules.match_result(&rounds), Some(EndState::Player1Won));
+    }
+
+    #[test]
+    fn match_result_when_best_of_three_with_draws_then_ends_after_three_rounds() {
+        // given
+        let rules = MatchRules {
+            best_of: 3,
+            ..Default::default()
+        };
+        let mut rounds = Rounds::default();
+
+        // when
+        rounds.record(RoundOutcome::Draw);
+        rounds.record(RoundOutcome::Won(PlayerType::Second));
+        let after_two = rules.match_result(&rounds);
+        rounds.record(RoundOutcome::Draw);
+
+        // then
+        assert_eq!(after_two, None);
+        assert_eq!(rules.match_result(&rounds), Some(EndState::Player2Won));
+    }
+
+    #[test]
+    fn record_when_round_goes_on_then_nothing_counted() {
+        // given
+        let mut rounds = Rounds::default();
+
+        // when
+        rounds.record(RoundOutcome::Continue);
+        rounds.record(RoundOutcome::Overtime);
+
+        // then
+        assert_eq!(rounds, Rounds::default());
+    }
+
+    #[test]
+    fn rule_presets_when_listed_then_first_is_default_and_all_can_end() {
+        assert_eq!(RULE_PRESETS[0].1, MatchRules::default());
+        for (name, rules) in RULE_PRESETS {
+            let can_end = rules.time_limit.is_some() || rules.score_limit.is_some();
+            assert!(can_end && rules.best_of % 2 == 1, "{name}");
+        }
+    }
+}
@@ -0,0 +1,315 @@
+use crate::ai::Difficulty;
+use crate::character_menu::CharacterMenuPlugin;
+use crate::colors::{GREY, WHITE};
+use crate::controls::{
+    any_gamepad_just_pressed, joining_devices, InputDevice, PlayerDevices, GAMEPAD_BACK,
+    GAMEPAD_CHANGE, GAMEPAD_START,
+};
+use crate::controls_menu::ControlsMenuPlugin;
+use crate::game_state::MatchState;
+use crate::input_map::InputMap;
+use crate::touch::screen_half;
+use bevy::prelude::{
+    BackgroundColor, Button, Display, Interaction, Node, SubStates, Text, TextColor, TextFont,
+};
+use bevy::{
+    app::{Plugin, Update},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        hierarchy::ChildSpawnerCommands,
+        query::{Changed, QueryFilter, With},
+        resource::Resource,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    input::{gamepad::Gamepad, keyboard::KeyCode, touch::Touches, ButtonInput},
+    state::{
+        app::AppExtStates,
+        condition::in_state,
+        state::{NextState, OnEnter, StateSet, States},
+        state_scoped::StateScoped,
+    },
+    ui::{AlignItems, FlexDirection, JustifyContent, PositionType, UiRect, Val},
+    window::{PrimaryWindow, Window},
+};
+
+/// Which part of the main menu is shown
+#[derive(SubStates, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[source(MatchState = MatchState::MainMenu)]
+pub(crate) enum MenuScreen {
+    #[default]
+    Title,
+    /// Each player presses a button on the device they want to play with
+    Join,
+    /// Rebind the keys and gamepad buttons of both players
+    Controls,
+    /// Both players pick a character before kickoff
+    Characters,
+}
+
+#[derive(Component)]
+struct StartMatchButton;
+
+#[derive(Component)]
+struct ControlsButton;
+
+#[derive(Component)]
+struct VsComputerButton;
+
+#[derive(Component)]
+struct DifficultyButton;
+
+/// Set when player 2 is played by the computer, remembers the difficulty for the next match
+#[derive(Resource, Default)]
+struct VsComputer(Option<Difficulty>);
+
+/// Goes on to the character selection once both players joined
+#[derive(Component)]
+struct PlayButton;
+
+/// Shows which device a player joined with
+#[derive(Component)]
+struct JoinSlotText(usize);
+
+pub(crate) fn spawn_screen<S: States>(
+    commands: &mut Commands,
+    state: S,
+    children: impl FnOnce(&mut ChildSpawnerCommands),
+) {
+    commands
+        .spawn((
+            Node {
+                display: Display::Flex,
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(16.0),
+                ..Default::default()
+            },
+            StateScoped(state),
+        ))
+        .with_children(children);
+}
+
+pub(crate) fn text(value: &str, font_size: f32) -> (Text, TextFont, TextColor) {
+    (
+        Text(value.into()),
+        TextFont {
+            font_size,
+            ..Default::default()
+        },
+        TextColor(WHITE),
+    )
+}
+
+pub(crate) fn button(parent: &mut ChildSpawnerCommands, marker: impl Component, label: &str) {
+    parent
+        .spawn((
+            marker,
+            Button,
+            Node {
+                padding: UiRect::axes(Val::Px(24.0), Val::Px(8.0)),
+                ..Default::default()
+            },
+            BackgroundColor(GREY),
+        ))
+        .with_children(|button| {
+            button.spawn(text(label, 32.0));
+        });
+}
+
+fn setup_main_menu(mut commands: Commands) {
+    spawn_screen(&mut commands, MenuScreen::Title, |parent| {
+        parent.spawn(text("Slatchhammer", 64.0));
+        button(parent, StartMatchButton, "Start Match");
+        button(parent, VsComputerButton, "Play vs Computer");
+        button(parent, ControlsButton, "Controls");
+        parent.spawn(text(
+            "Press Enter or Start to start, C to play against the computer",
+            20.0,
+        ));
+    });
+}
+
+fn setup_join_screen(
+    mut commands: Commands,
+    vs_computer: Res<VsComputer>,
+    mut devices: ResMut<PlayerDevices>,
+) {
+    *devices = PlayerDevices::none();
+    devices.0[1] = vs_computer.0.map(InputDevice::Computer);
+    spawn_screen(&mut commands, MenuScreen::Join, |parent| {
+        parent.spawn(text("Press a button to join", 48.0));
+        parent.spawn((JoinSlotText(0), text("", 32.0)));
+        parent.spawn((JoinSlotText(1), text("", 32.0)));
+        if vs_computer.0.is_some() {
+            button(parent, DifficultyButton, "Change difficulty (Tab)");
+        }
+        button(parent, PlayButton, "Play");
+        parent.spawn(text(
+            "WASD, arrow keys, Start on a gamepad or a tap on your half of the screen to join, \
+            Enter or Start to begin, Esc or Select to go back",
+            20.0,
+        ));
+    });
+}
+
+fn setup_pause_menu(mut commands: Commands) {
+    spawn_screen(&mut commands, MatchState::Paused, |parent| {
+        parent.spawn(text("Paused", 64.0));
+        parent.spawn(text("Esc or Start to resume, Q to quit to the menu", 20.0));
+    });
+}
+
+#[allow(clippy::type_complexity)]
+fn pressed<F: QueryFilter>(query: &Query<&Interaction, F>) -> bool {
+    query.iter().any(|i| *i == Interaction::Pressed)
+}
+
+#[allow(clippy::type_complexity)]
+fn handle_title_screen(
+    input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    q_start: Query<&Interaction, (Changed<Interaction>, With<StartMatchButton>)>,
+    q_vs_computer: Query<&Interaction, (Changed<Interaction>, With<VsComputerButton>)>,
+    q_controls: Query<&Interaction, (Changed<Interaction>, With<ControlsButton>)>,
+    mut vs_computer: ResMut<VsComputer>,
+    mut next_screen: ResMut<NextState<MenuScreen>>,
+) {
+    if pressed(&q_start)
+        || input.just_pressed(KeyCode::Enter)
+        || any_gamepad_just_pressed(&gamepads, GAMEPAD_START)
+    {
+        vs_computer.0 = None;
+        next_screen.set(MenuScreen::Join);
+    } else if pressed(&q_vs_computer) || input.just_pressed(KeyCode::KeyC) {
+        vs_computer.0 = Some(vs_computer.0.unwrap_or_default());
+        next_screen.set(MenuScreen::Join);
+    } else if pressed(&q_controls) {
+        next_screen.set(MenuScreen::Controls);
+    }
+}
+
+fn change_difficulty(
+    input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    q_button: Query<&Interaction, (Changed<Interaction>, With<DifficultyButton>)>,
+    mut vs_computer: ResMut<VsComputer>,
+    mut devices: ResMut<PlayerDevices>,
+) {
+    let change = pressed(&q_button)
+        || input.just_pressed(KeyCode::Tab)
+        || any_gamepad_just_pressed(&gamepads, GAMEPAD_CHANGE);
+    if let (true, Some(difficulty)) = (change, vs_computer.0) {
+        vs_computer.0 = Some(difficulty.next());
+        devices.0[1] = vs_computer.0.map(InputDevice::Computer);
+    }
+}
+
+/// Keyboards and gamepads take the first free player, a tap joins the player of its screen half
+fn join_players(
+    input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<(Entity, &Gamepad)>,
+    touches: Res<Touches>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    input_map: Res<InputMap>,
+    mut devices: ResMut<PlayerDevices>,
+    mut q_slots: Query<(&JoinSlotText, &mut Text)>,
+) {
+    for device in joining_devices(&input_map, &input, &gamepads) {
+        devices.join(device);
+    }
+    if let Ok(window) = windows.single() {
+        for touch in touches.iter_just_pressed() {
+            devices.join_as(
+                screen_half(touch.position(), window.size()),
+                InputDevice::Touch,
+            );
+        }
+    }
+    for (slot, mut slot_text) in q_slots.iter_mut() {
+        let device = devices.0[slot.0].map_or("press a button to join".into(), |d| d.name());
+        slot_text.0 = format!("Player {}: {}", slot.0 + 1, device);
+    }
+}
+
+fn choose_characters(
+    input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    q_button: Query<&Interaction, (Changed<Interaction>, With<PlayButton>)>,
+    devices: Res<PlayerDevices>,
+    mut next_screen: ResMut<NextState<MenuScreen>>,
+) {
+    let start = pressed(&q_button)
+        || input.just_pressed(KeyCode::Enter)
+        || any_gamepad_just_pressed(&gamepads, GAMEPAD_START);
+    if start && devices.all_joined() {
+        next_screen.set(MenuScreen::Characters);
+    } else if input.just_pressed(KeyCode::Escape)
+        || any_gamepad_just_pressed(&gamepads, GAMEPAD_BACK)
+    {
+        next_screen.set(MenuScreen::Title);
+    }
+}
+
+fn pause_match(
+    input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut next_state: ResMut<NextState<MatchState>>,
+) {
+    if input.just_pressed(KeyCode::Escape) || any_gamepad_just_pressed(&gamepads, GAMEPAD_START) {
+        next_state.set(MatchState::Paused);
+    }
+}
+
+fn handle_pause_menu(
+    input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut next_state: ResMut<NextState<MatchState>>,
+) {
+    if input.just_pressed(KeyCode::Escape) || any_gamepad_just_pressed(&gamepads, GAMEPAD_START) {
+        next_state.set(MatchState::Playing);
+    } else if input.just_pressed(KeyCode::KeyQ) {
+        next_state.set(MatchState::MainMenu);
+    }
+}
+
+fn skip_match_over(
+    input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut next_state: ResMut<NextState<MatchState>>,
+) {
+    if input.just_pressed(KeyCode::Enter) || any_gamepad_just_pressed(&gamepads, GAMEPAD_START) {
+        next_state.set(MatchState::MainMenu);
+    }
+}
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_sub_state::<MenuScreen>()
+            .init_resource::<VsComputer>()
+            .add_plugins((ControlsMenuPlugin, CharacterMenuPlugin))
+            .add_systems(OnEnter(MenuScreen::Title), setup_main_menu)
+            .add_systems(OnEnter(MenuScreen::Join), setup_join_screen)
+            .add_systems(OnEnter(MatchState::Paused), setup_pause_menu)
+            .add_systems(
+                Update,
+                (
+                    handle_title_screen.run_if(in_state(MenuScreen::Title)),
+                    // Start also joins, the press that joins the last player must not begin
+                    (choose_characters, join_players, change_difficulty)
+                        .chain()
+                        .run_if(in_state(MenuScreen::Join)),
+                    pause_match.run_if(in_state(MatchState::Playing)),
+                    handle_pause_menu.run_if(in_state(MatchState::Paused)),
+                    skip_match_over.run_if(in_state(MatchState::MatchOver)),
+                ),
+            );
+    }
+}
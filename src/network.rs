@@ -0,0 +1,147 @@
+use std::time::Duration;
+
+use bevy::{
+    math::{Quat, Vec3},
+    transform::components::Transform,
+};
+use bevy_rapier3d::dynamics::Velocity;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{game_state::MatchState, goals::PlayerType, player::PlayerInput, points::Points};
+
+pub const DEFAULT_SERVER_ADDRESS: &str = "127.0.0.1:7878";
+
+pub const LOBBY_CODE_LENGTH: usize = 4;
+
+/// Sent by a client to the server
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ClientMsg {
+    Input {
+        tick: u32,
+        input: PlayerInput,
+    },
+    /// Joins the first lobby with a free slot, or opens a new one
+    QuickJoin,
+    CreateLobby,
+    JoinLobby {
+        code: String,
+    },
+    Ready,
+    LeaveLobby,
+}
+
+/// Sent by the server to its clients
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ServerMsg {
+    /// Sent whenever someone joins the lobby of the client or gets ready
+    LobbyInfo(LobbyInfo),
+    /// The lobby of the client is gone, e.g. because the opponent left
+    LobbyClosed,
+    JoinFailed {
+        reason: String,
+    },
+    Snapshot(Box<Snapshot>),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LobbyInfo {
+    pub code: String,
+    /// The player the receiving client controls
+    pub you: PlayerType,
+    pub joined: [bool; 2],
+    pub ready: [bool; 2],
+}
+
+/// The authoritative state of the server after one tick
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub tick: u32,
+    /// Tick of the last input the server applied for each player
+    pub input_ticks: [Option<u32>; 2],
+    pub state: MatchState,
+    pub phase_time_left: Duration,
+    /// Only present while a match is running
+    pub world: Option<WorldSnapshot>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WorldSnapshot {
+    pub player_1: BodySnapshot,
+    pub player_2: BodySnapshot,
+    pub ball: BodySnapshot,
+    pub points: Points,
+    pub game_time_left: Duration,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct BodySnapshot {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub linvel: Vec3,
+}
+
+impl BodySnapshot {
+    pub fn new(transform: &Transform, velocity: &Velocity) -> Self {
+        BodySnapshot {
+            translation: transform.translation,
+            rotation: transform.rotation,
+            linvel: velocity.linvel,
+        }
+    }
+
+    pub fn apply(&self, transform: &mut Transform, velocity: &mut Velocity) {
+        transform.translation = self.translation;
+        transform.rotation = self.rotation;
+        velocity.linvel = self.linvel;
+    }
+
+    /// The body `t` of the way from `self` to `other`
+    pub fn lerp(&self, other: &BodySnapshot, t: f32) -> Self {
+        BodySnapshot {
+            translation: self.translation.lerp(other.translation, t),
+            rotation: self.rotation.slerp(other.rotation, t),
+            linvel: self.linvel.lerp(other.linvel, t),
+        }
+    }
+}
+
+pub fn encode<T: Serialize>(msg: &T) -> Vec<u8> {
+    bincode::serialize(msg).expect("network messages are always serializable")
+}
+
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Option<T> {
+    bincode::deserialize(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::Vec3;
+
+    use super::{decode, encode, ClientMsg};
+    use crate::player::PlayerInput;
+
+    #[test]
+    fn decode_when_encoded_then_same_message() {
+        // given
+        let msg = ClientMsg::Input {
+            tick: 7,
+            input: PlayerInput {
+                movement: Vec3::X,
+                sprint: true,
+                kick: false,
+                tackle: false,
+            },
+        };
+
+        // when
+        let decoded = decode::<ClientMsg>(&encode(&msg));
+
+        // then
+        assert_eq!(decoded, Some(msg));
+    }
+
+    #[test]
+    fn decode_when_garbage_then_none() {
+        assert_eq!(decode::<ClientMsg>(&[255, 255, 255, 255, 1]), None);
+    }
+}
@@ -0,0 +1,429 @@
+use bevy::{
+    app::{App, Plugin, PostUpdate, Startup},
+    ecs::{
+        change_detection::DetectChanges,
+        event::{Event, EventReader, EventWriter},
+        query::Added,
+        resource::Resource,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, NonSendMut, Query, Res, ResMut},
+        world::World,
+    },
+    log::{error, info},
+    state::state::{NextState, State},
+};
+use bevy_rapier3d::plugin::{PhysicsSet, RapierConfiguration};
+
+use crate::{
+    game_state::{EndState, GameEndEvent, GameTime, MatchState, PhaseTimer, RemoteMatchFlow},
+    lobby_menu::LobbyMenuPlugin,
+    network::{decode, encode, ClientMsg, LobbyInfo, ServerMsg, Snapshot, DEFAULT_SERVER_ADDRESS},
+    points::Points,
+    powerup::PowerUpRules,
+    prediction::PredictionPlugin,
+};
+
+use socket::ClientSocket;
+
+#[derive(Resource)]
+struct ServerUrl(String);
+
+/// The connection to the server, only exists until it is closed
+pub(crate) struct ServerConnection {
+    socket: ClientSocket,
+    /// The socket was open once, closing it now means the connection was lost
+    opened: bool,
+}
+
+/// How far the socket to the server got, the same on every platform
+enum SocketState {
+    Connecting,
+    Open,
+    Closed,
+}
+
+/// The lobby the client is in, as last told by the server
+#[derive(Resource, Default)]
+pub(crate) struct CurrentLobby(pub Option<LobbyInfo>);
+
+/// Why the last attempt to get into a lobby failed, or why the lobby is gone
+#[derive(Resource, Default)]
+pub(crate) struct LobbyNotice(pub Option<String>);
+
+#[derive(Event)]
+pub(crate) struct SendToServer(pub ClientMsg);
+
+/// Newest snapshot received from the server
+#[derive(Resource, Default)]
+pub(crate) struct LatestSnapshot(pub Option<Snapshot>);
+
+/// The server URL if the game was started with `--connect [url]`, or `?connect=url` on the web
+pub fn requested_server_url() -> Option<String> {
+    let default_url = || format!("ws://{DEFAULT_SERVER_ADDRESS}");
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let mut args = std::env::args().skip_while(|arg| arg != "--connect");
+        args.next()?;
+        Some(args.next().unwrap_or_else(default_url))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        let search = web_sys::window()?.location().search().ok()?;
+        let params = web_sys::UrlSearchParams::new_with_str(&search).ok()?;
+        let url = params.get("connect")?;
+        Some(if url.is_empty() { default_url() } else { url })
+    }
+}
+
+fn connect_to_server(world: &mut World) {
+    let url = world.resource::<ServerUrl>().0.clone();
+    match ClientSocket::connect(&url) {
+        Ok(socket) => {
+            info!("Connecting to {url}");
+            world.insert_non_send_resource(ServerConnection {
+                socket,
+                opened: false,
+            });
+        }
+        Err(e) => {
+            error!("Could not connect to {url}: {e}");
+            world.resource_mut::<LobbyNotice>().0 = Some(format!("Could not connect to {url}"));
+        }
+    }
+}
+
+pub(crate) fn receive_server_messages(
+    connection: Option<NonSendMut<ServerConnection>>,
+    mut latest: ResMut<LatestSnapshot>,
+    mut lobby: ResMut<CurrentLobby>,
+    mut notice: ResMut<LobbyNotice>,
+    state: Res<State<MatchState>>,
+    mut next_state: ResMut<NextState<MatchState>>,
+) {
+    let Some(mut connection) = connection else {
+        return;
+    };
+    for bytes in connection.socket.receive() {
+        match decode::<ServerMsg>(&bytes) {
+            Some(ServerMsg::LobbyInfo(info)) => {
+                lobby.0 = Some(info);
+                notice.0 = None;
+            }
+            Some(ServerMsg::LobbyClosed) => {
+                lobby.0 = None;
+                latest.0 = None;
+                notice.0 = Some("The lobby was closed".into());
+                if *state.get() != MatchState::MainMenu {
+                    next_state.set(MatchState::MainMenu);
+                }
+            }
+            Some(ServerMsg::JoinFailed { reason }) => notice.0 = Some(reason),
+            Some(ServerMsg::Snapshot(snapshot)) => latest.0 = Some(*snapshot),
+            None => {}
+        }
+    }
+}
+
+/// Without the server there is no match to play, the menu shows what happened
+fn watch_connection(
+    mut commands: Commands,
+    url: Res<ServerUrl>,
+    connection: Option<NonSendMut<ServerConnection>>,
+    mut lobby: ResMut<CurrentLobby>,
+    mut notice: ResMut<LobbyNotice>,
+    state: Res<State<MatchState>>,
+    mut next_state: ResMut<NextState<MatchState>>,
+) {
+    let Some(mut connection) = connection else {
+        return;
+    };
+    match connection.socket.state() {
+        SocketState::Connecting => {}
+        SocketState::Open if !connection.opened => {
+            info!("Connected to {}", url.0);
+            connection.opened = true;
+        }
+        SocketState::Open => {}
+        SocketState::Closed => {
+            commands.queue(|world: &mut World| {
+                world.remove_non_send_resource::<ServerConnection>();
+                world.resource_mut::<LatestSnapshot>().0 = None;
+            });
+            lobby.0 = None;
+            notice.0 = Some(if connection.opened {
+                "Lost the connection to the server".into()
+            } else {
+                format!("Could not connect to {}", url.0)
+            });
+            if *state.get() != MatchState::MainMenu {
+                next_state.set(MatchState::MainMenu);
+            }
+        }
+    }
+}
+
+fn send_messages(
+    connection: Option<NonSendMut<ServerConnection>>,
+    mut msgs: EventReader<SendToServer>,
+) {
+    let Some(mut connection) = connection else {
+        msgs.clear();
+        return;
+    };
+    for SendToServer(msg) in msgs.read() {
+        connection.socket.send(encode(msg));
+    }
+}
+
+/// Mirrors the match phase of the server, the client never changes it on its own
+pub(crate) fn follow_server_state(
+    latest: Res<LatestSnapshot>,
+    state: Res<State<MatchState>>,
+    mut next_state: ResMut<NextState<MatchState>>,
+    phase_timer: Option<ResMut<PhaseTimer>>,
+    q_points: Query<&Points>,
+    mut game_end: EventWriter<GameEndEvent>,
+) {
+    if !latest.is_changed() {
+        return;
+    }
+    let Some(snapshot) = &latest.0 else {
+        return;
+    };
+    if let Some(mut timer) = phase_timer {
+        let elapsed = timer.duration().saturating_sub(snapshot.phase_time_left);
+        timer.set_elapsed(elapsed);
+    }
+    if *state.get() == snapshot.state {
+        return;
+    }
+    if snapshot.state == MatchState::MatchOver {
+        if let Ok(points) = q_points.single() {
+            let end_state = EndState::from_points(points);
+            game_end.write(GameEndEvent { end_state });
+        }
+    }
+    next_state.set(snapshot.state);
+}
+
+/// The bodies are left to the prediction, only the score and time are taken over as they are
+pub(crate) fn apply_scoreboard(
+    latest: Res<LatestSnapshot>,
+    mut q_points: Query<&mut Points>,
+    mut q_time: Query<&mut GameTime>,
+) {
+    let Some(world) = latest.0.as_ref().and_then(|s| s.world.as_ref()) else {
+        return;
+    };
+    for mut points in q_points.iter_mut() {
+        *points = world.points;
+    }
+    for mut time in q_time.iter_mut() {
+        time.set_current_time(world.game_time_left);
+    }
+}
+
+/// The server simulates, locally the bodies only show where the snapshots put them
+pub(crate) fn disable_local_physics(
+    mut q_config: Query<&mut RapierConfiguration, Added<RapierConfiguration>>,
+) {
+    for mut config in q_config.iter_mut() {
+        config.physics_pipeline_active = false;
+    }
+}
+
+/// Plays against another client through an authoritative server instead of on one keyboard.
+/// Replaces the `MenuPlugin` with the lobby menu, the server starts the match once both
+/// players in the lobby are ready.
+pub struct OnlinePlugin {
+    pub server_url: String,
+}
+
+impl Plugin for OnlinePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ServerUrl(self.server_url.clone()))
+            .insert_resource(RemoteMatchFlow)
+            .insert_resource(PowerUpRules::disabled())
+            .init_resource::<LatestSnapshot>()
+            .init_resource::<CurrentLobby>()
+            .init_resource::<LobbyNotice>()
+            .add_event::<SendToServer>()
+            .add_plugins((LobbyMenuPlugin, PredictionPlugin))
+            .add_systems(Startup, connect_to_server)
+            .add_systems(
+                PostUpdate,
+                (
+                    disable_local_physics,
+                    send_messages,
+                    receive_server_messages,
+                    watch_connection,
+                    follow_server_state,
+                    apply_scoreboard,
+                )
+                    .chain()
+                    .before(PhysicsSet::SyncBackend),
+            );
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod socket {
+    use std::{
+        io::ErrorKind,
+        net::TcpStream,
+        sync::mpsc::{self, Receiver, TryRecvError},
+        thread,
+    };
+
+    use bevy::log::warn;
+    use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+
+    use super::SocketState;
+
+    type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+    pub struct ClientSocket {
+        /// Hands over the socket once the connecting thread is done
+        connecting: Option<Receiver<Result<Socket, String>>>,
+        socket: Option<Socket>,
+    }
+
+    impl ClientSocket {
+        /// Connects on its own thread, so an unreachable server never freezes the window
+        pub fn connect(url: &str) -> Result<Self, String> {
+            let url = url.to_owned();
+            let (sender, receiver) = mpsc::channel();
+            thread::Builder::new()
+                .name("connect".into())
+                .spawn(move || {
+                    let _ = sender.send(open(&url));
+                })
+                .map_err(|e| e.to_string())?;
+            Ok(ClientSocket {
+                connecting: Some(receiver),
+                socket: None,
+            })
+        }
+
+        pub fn send(&mut self, bytes: Vec<u8>) {
+            // Inputs from before the connection opened are simply dropped,
+            // on `WouldBlock` the message stays queued and is flushed with the next send
+            if let Some(socket) = &mut self.socket {
+                let _ = socket.send(Message::binary(bytes));
+            }
+        }
+
+        pub fn receive(&mut self) -> Vec<Vec<u8>> {
+            self.finish_connecting();
+            let mut received = Vec::new();
+            let Some(socket) = &mut self.socket else {
+                return received;
+            };
+            loop {
+                match socket.read() {
+                    Ok(Message::Binary(bytes)) => received.push(bytes.to_vec()),
+                    Ok(_) => {}
+                    Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(e) => {
+                        warn!("Lost the connection to the server: {e}");
+                        self.socket = None;
+                        break;
+                    }
+                }
+            }
+            received
+        }
+
+        pub fn state(&self) -> SocketState {
+            match (&self.connecting, &self.socket) {
+                (Some(_), _) => SocketState::Connecting,
+                (None, Some(_)) => SocketState::Open,
+                (None, None) => SocketState::Closed,
+            }
+        }
+
+        fn finish_connecting(&mut self) {
+            let Some(connecting) = &self.connecting else {
+                return;
+            };
+            match connecting.try_recv() {
+                Ok(Ok(socket)) => self.socket = Some(socket),
+                Ok(Err(e)) => warn!("Could not connect to the server: {e}"),
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => {}
+            }
+            self.connecting = None;
+        }
+    }
+
+    fn open(url: &str) -> Result<Socket, String> {
+        let (mut socket, _) = tungstenite::connect(url).map_err(|e| e.to_string())?;
+        if let MaybeTlsStream::Plain(stream) = socket.get_mut() {
+            stream.set_nonblocking(true).map_err(|e| e.to_string())?;
+        }
+        Ok(socket)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod socket {
+    use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+    use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+    use web_sys::{BinaryType, MessageEvent, WebSocket};
+
+    use super::SocketState;
+
+    pub struct ClientSocket {
+        socket: WebSocket,
+        received: Rc<RefCell<VecDeque<Vec<u8>>>>,
+        _on_message: Closure<dyn FnMut(MessageEvent)>,
+    }
+
+    impl ClientSocket {
+        pub fn connect(url: &str) -> Result<Self, String> {
+            let socket = WebSocket::new(url).map_err(describe)?;
+            socket.set_binary_type(BinaryType::Arraybuffer);
+            let received = Rc::new(RefCell::new(VecDeque::new()));
+            let queue = received.clone();
+            let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |e: MessageEvent| {
+                if let Ok(buffer) = e.data().dyn_into::<js_sys::ArrayBuffer>() {
+                    queue
+                        .borrow_mut()
+                        .push_back(js_sys::Uint8Array::new(&buffer).to_vec());
+                }
+            });
+            socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+            Ok(ClientSocket {
+                socket,
+                received,
+                _on_message: on_message,
+            })
+        }
+
+        pub fn send(&mut self, bytes: Vec<u8>) {
+            // Inputs from before the connection opened are simply dropped
+            if self.socket.ready_state() == WebSocket::OPEN {
+                let _ = self.socket.send_with_u8_array(&bytes);
+            }
+        }
+
+        pub fn receive(&mut self) -> Vec<Vec<u8>> {
+            self.received.borrow_mut().drain(..).collect()
+        }
+
+        pub fn state(&self) -> SocketState {
+            match self.socket.ready_state() {
+                WebSocket::CONNECTING => SocketState::Connecting,
+                WebSocket::OPEN => SocketState::Open,
+                _ => SocketState::Closed,
+            }
+        }
+    }
+
+    fn describe(e: JsValue) -> String {
+        e.as_string().unwrap_or_else(|| format!("{e:?}"))
+    }
+}
@@ -1,32 +1,37 @@
-use bevy::input::ButtonInput;
 use bevy::{
-    app::{Plugin, Startup, Update},
+    app::{Plugin, Update},
     asset::Assets,
     ecs::{
         component::Component,
         entity::Entity,
         event::EventWriter,
-        query::{With, Without},
+        query::{Added, Has},
+        schedule::IntoScheduleConfigs,
         system::{Commands, Query, Res, ResMut},
     },
-    input::keyboard::KeyCode,
-    math::{vec3, Quat, Vec3},
+    math::{Quat, Vec3},
     pbr::StandardMaterial,
     prelude::*,
     render::mesh::Mesh,
+    state::{condition::in_state, state::OnEnter, state_scoped::StateScoped},
     time::Time,
     transform::components::Transform,
 };
 use bevy_rapier3d::{
     dynamics::{ExternalForce, GravityScale, LockedAxes, RigidBody, Velocity},
-    geometry::Collider,
+    geometry::{ActiveEvents, Collider, ColliderMassProperties, Restitution},
 };
+use serde::{Deserialize, Serialize};
 
 use crate::colors::{GREEN, ORANGE};
 use crate::{
-    camera::MainCamera,
-    constants::{PLAYER1_STARTING_POINT, PLAYER2_STARTING_POINT, PLAYER_MOVEMENT_SPEED},
+    arena::CurrentArena,
+    character::{Character, SelectedCharacters},
+    game_state::{GameplaySet, InMatch, MatchState},
+    handicap::InvertedControls,
+    kick::KickState,
     sprint::{ApplySprintEvent, ShowBars, SprintState},
+    tackle::TackleState,
 };
 
 #[derive(Component)]
@@ -35,145 +40,92 @@ pub struct Player1;
 #[derive(Component)]
 pub struct Player2;
 
-#[derive(Component, Default)]
-struct PlayerInput {
-    movement: Vec3,
-    current_velocity: f32,
+/// What a player wants to do, written by the keyboard, a gamepad or any other controller
+#[derive(Component, Default, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerInput {
+    pub movement: Vec3,
+    pub sprint: bool,
+    /// Held to charge a kick, which is released when this turns false
+    pub kick: bool,
+    /// Pressing it dashes forward to tackle the opponent
+    pub tackle: bool,
 }
 
-fn movement_input(
-    input: Res<ButtonInput<KeyCode>>,
-    mut query_p1: Query<
-        (Entity, &mut PlayerInput, &SprintState),
-        (With<Player1>, Without<Player2>),
-    >,
-    mut query_p2: Query<
-        (Entity, &mut PlayerInput, &SprintState),
-        (With<Player2>, Without<Player1>),
-    >,
+/// The velocity a player of `character` moves with after one step of `dt` seconds with `input`.
+/// Shared by the simulation and the prediction of online clients, so both move alike.
+pub fn steer(
+    velocity: Vec3,
+    input: &PlayerInput,
+    can_sprint: bool,
+    character: &Character,
+    dt: f32,
+) -> Vec3 {
+    let mut speed = character.speed;
+    if input.sprint && can_sprint {
+        speed *= 2.0;
+    }
+    // Analog sticks move slower when only tilted a bit
+    let desired_velocity = input.movement.clamp_length_max(1.0) * speed;
+    Vec3::lerp(velocity, desired_velocity, dt * character.acceleration)
+}
+
+#[allow(clippy::type_complexity)]
+pub(crate) fn apply_movement(
+    mut query: Query<(
+        Entity,
+        &PlayerInput,
+        &SprintState,
+        &TackleState,
+        &Character,
+        Has<InvertedControls>,
+        &mut Transform,
+        &mut Velocity,
+    )>,
     time: Res<Time>,
-    camera: Query<&Transform, With<MainCamera>>,
     mut event_writer: EventWriter<ApplySprintEvent>,
 ) {
-    let camera_transform = camera.single().unwrap();
-    let forward = camera_transform.right();
-    let rotation = Quat::from_axis_angle(Vec3::Y, forward.y);
     let frame_time = time.delta_secs();
-
-    for (entity, mut player_input, stamina) in query_p1.iter_mut() {
-        let (x, z) = wanted_player_direction(
-            &input,
-            KeyCode::KeyA,
-            KeyCode::KeyD,
-            KeyCode::KeyW,
-            KeyCode::KeyS,
-        );
-        let mut velocity = PLAYER_MOVEMENT_SPEED;
-        if player_wants_to_sprint(&input, KeyCode::ShiftLeft) {
-            velocity = change_velocity(stamina, frame_time, entity, &mut event_writer);
+    for (entity, input, stamina, tackle, character, inverted, mut transform, mut velocity) in
+        query.iter_mut()
+    {
+        if tackle.overrides_input() {
+            continue;
         }
-        let dir = vec3(x, 0.0, z).normalize_or_zero();
-        let dir = rotation * dir;
-        player_input.movement = dir;
-        player_input.current_velocity = velocity;
-    }
-    for (entity, mut player_input, stamina) in query_p2.iter_mut() {
-        let (x, z) = wanted_player_direction(
-            &input,
-            KeyCode::ArrowLeft,
-            KeyCode::ArrowRight,
-            KeyCode::ArrowUp,
-            KeyCode::ArrowDown,
-        );
-        let mut velocity = PLAYER_MOVEMENT_SPEED;
-        if player_wants_to_sprint(&input, KeyCode::ShiftRight) {
-            velocity = change_velocity(stamina, frame_time, entity, &mut event_writer);
-        }
-        let dir = vec3(x, 0.0, z).normalize_or_zero();
-        let dir = rotation * dir;
-        player_input.movement = dir;
-        player_input.current_velocity = velocity;
-    }
-}
-
-fn player_wants_to_sprint(input: &ButtonInput<KeyCode>, space: KeyCode) -> bool {
-    input.pressed(space)
-}
-
-fn change_velocity(
-    stamina: &SprintState,
-    stamina_change: f32,
-    target_entity: Entity,
-    event_writer: &mut EventWriter<ApplySprintEvent>,
-) -> f32 {
-    let mut vel = PLAYER_MOVEMENT_SPEED;
-    if stamina.is_available() {
-        vel *= 2.0;
-        let ev = ApplySprintEvent {
-            amount: -stamina_change,
-            target: target_entity,
+        // Inverted controls steer the opposite way
+        let input = &PlayerInput {
+            movement: if inverted {
+                -input.movement
+            } else {
+                input.movement
+            },
+            ..*input
         };
-        event_writer.write(ev);
-    }
-    vel
-}
-
-fn apply_movement(
-    mut query: Query<(&PlayerInput, &mut Transform, &mut Velocity)>,
-    time: Res<Time>,
-) {
-    for (input, mut transform, mut velocity) in query.iter_mut() {
-        let norm_input = input.movement.normalize_or_zero();
-
-        let desired_velocity = norm_input * input.current_velocity;
-        velocity.linvel = Vec3::lerp(velocity.linvel, desired_velocity, time.delta_secs() * 10.0);
+        let can_sprint = stamina.is_available();
+        if input.sprint && can_sprint {
+            event_writer.write(ApplySprintEvent {
+                amount: -frame_time,
+                target: entity,
+            });
+        }
+        velocity.linvel = steer(velocity.linvel, input, can_sprint, character, frame_time);
 
-        transform.rotation = Quat::from_rotation_y(f32::atan2(norm_input.x, norm_input.z));
+        // Standing players keep facing where they last moved, so they can still aim a kick
+        if input.movement != Vec3::ZERO {
+            let norm_input = input.movement.normalize();
+            transform.rotation = Quat::from_rotation_y(f32::atan2(norm_input.x, norm_input.z));
+        }
     }
 }
 
-fn wanted_player_direction(
-    input: &ButtonInput<KeyCode>,
-    left: KeyCode,
-    right: KeyCode,
-    up: KeyCode,
-    down: KeyCode,
-) -> (f32, f32) {
+fn player_body(character: Character, translation: Vec3) -> impl Bundle {
     (
-        movement_axis(input, left, right),
-        movement_axis(input, up, down),
-    )
-}
-
-fn movement_axis(input: &ButtonInput<KeyCode>, left: KeyCode, right: KeyCode) -> f32 {
-    match (input.pressed(left), input.pressed(right)) {
-        (true, false) => -1.0,
-        (false, true) => 1.0,
-        _ => 0f32,
-    }
-}
-
-fn spawn_player(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-) {
-    let mesh = meshes.add(Cylinder::default());
-    let material_green = materials.add(StandardMaterial {
-        base_color: GREEN,
-        ..Default::default()
-    });
-    let material_orange = materials.add(StandardMaterial {
-        base_color: ORANGE,
-        ..Default::default()
-    });
-
-    commands
-        .spawn((
-            Player1,
+        (
             PlayerInput::default(),
             RigidBody::Dynamic,
-            Collider::capsule(Vec3::ZERO, Vec3::Y, 0.5),
+            Collider::capsule(Vec3::ZERO, Vec3::Y, character.radius),
+            ColliderMassProperties::Mass(character.mass),
+            Restitution::coefficient(character.restitution),
+            ActiveEvents::COLLISION_EVENTS,
             Velocity::default(),
             ExternalForce {
                 force: Vec3::ZERO,
@@ -183,44 +135,71 @@ fn spawn_player(
             LockedAxes::ROTATION_LOCKED_X
                 | LockedAxes::ROTATION_LOCKED_Z
                 | LockedAxes::ROTATION_LOCKED_Y,
-        ))
-        .insert((
-            Mesh3d(mesh.clone()),
-            MeshMaterial3d(material_green),
-            Transform::from_translation(PLAYER1_STARTING_POINT),
-        ))
-        .insert(SprintState::default())
-        .insert(ShowBars);
-    commands
-        .spawn((
-            Player2,
-            PlayerInput::default(),
-            RigidBody::Dynamic,
-            Collider::capsule(Vec3::ZERO, Vec3::Y, 0.5),
-            Velocity::default(),
-            ExternalForce {
-                force: Vec3::ZERO,
-                torque: Vec3::ZERO,
-            },
-            GravityScale(1.0),
-            LockedAxes::ROTATION_LOCKED_X
-                | LockedAxes::ROTATION_LOCKED_Z
-                | LockedAxes::ROTATION_LOCKED_Y,
-        ))
-        .insert((
-            Mesh3d(mesh.clone()),
-            MeshMaterial3d(material_orange),
-            Transform::from_translation(PLAYER2_STARTING_POINT),
-        ))
-        .insert(SprintState::default())
-        .insert(ShowBars);
+        ),
+        Transform::from_translation(translation),
+        (
+            SprintState::with_capacity(character.stamina),
+            KickState::default(),
+            TackleState::default(),
+            character,
+        ),
+        (ShowBars, StateScoped(InMatch)),
+    )
+}
+
+fn spawn_player(
+    mut commands: Commands,
+    selected: Res<SelectedCharacters>,
+    arena: Res<CurrentArena>,
+) {
+    commands.spawn((
+        Player1,
+        player_body(selected.character(0), arena.spawns.player1),
+    ));
+    commands.spawn((
+        Player2,
+        player_body(selected.character(1), arena.spawns.player2),
+    ));
 }
 
+fn add_player_meshes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    q_players: Query<(Entity, &Character, Has<Player1>), Added<PlayerInput>>,
+) {
+    for (entity, character, is_player1) in q_players.iter() {
+        let color = if is_player1 { GREEN } else { ORANGE };
+        commands.entity(entity).insert((
+            Mesh3d(meshes.add(Cylinder::new(character.radius, 1.0))),
+            MeshMaterial3d(materials.add(StandardMaterial {
+                base_color: color,
+                ..Default::default()
+            })),
+        ));
+    }
+}
+
+/// Spawns the players and moves them according to their `PlayerInput`
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_systems(Startup, spawn_player)
-            .add_systems(Update, (apply_movement, movement_input));
+        app.init_resource::<SelectedCharacters>()
+            .add_systems(OnEnter(InMatch), spawn_player)
+            .add_systems(
+                Update,
+                apply_movement
+                    .run_if(in_state(MatchState::Playing))
+                    .in_set(GameplaySet::Movement),
+            );
+    }
+}
+
+pub struct PlayerRenderPlugin;
+
+impl Plugin for PlayerRenderPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_systems(Update, add_player_meshes);
     }
 }
@@ -1,16 +1,24 @@
 use bevy::{
-    app::{Plugin, Startup, Update},
+    app::{Plugin, Update},
     ecs::{
         component::Component,
         event::EventReader,
+        schedule::IntoScheduleConfigs,
         system::{Commands, Query},
     },
+    state::{condition::in_state, state::OnEnter, state_scoped::StateScoped},
 };
+use serde::{Deserialize, Serialize};
 
-use crate::goals::{GoalEvent, PlayerType};
+use crate::{
+    game_state::{GameplaySet, InMatch},
+    goals::{GoalEvent, PlayerType},
+};
 
 fn update_player_points(mut q_points: Query<&mut Points>, mut goal_events: EventReader<GoalEvent>) {
-    let mut points = q_points.single_mut().unwrap();
+    let Ok(mut points) = q_points.single_mut() else {
+        return;
+    };
     for ev in goal_events.read() {
         match ev.player {
             PlayerType::First => points.player_1 += ev.amount,
@@ -19,20 +27,24 @@ fn update_player_points(mut q_points: Query<&mut Points>, mut goal_events: Event
     }
 }
 
-#[derive(Component, Default)]
+#[derive(Component, Default, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Points {
     pub player_1: u32,
     pub player_2: u32,
 }
 
 fn spawn_points(mut commands: Commands) {
-    commands.spawn(Points::default());
+    commands.spawn((Points::default(), StateScoped(InMatch)));
 }
 
 pub struct PointsPlugin;
 impl Plugin for PointsPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_systems(Startup, spawn_points)
-            .add_systems(Update, (update_player_points,));
+        app.add_systems(OnEnter(InMatch), spawn_points).add_systems(
+            Update,
+            update_player_points
+                .run_if(in_state(InMatch))
+                .in_set(GameplaySet::Score),
+        );
     }
 }
@@ -0,0 +1,646 @@
+use std::collections::HashSet;
+
+use bevy::prelude::{
+    Cuboid, Deref, DerefMut, Display, Mesh3d, MeshMaterial3d, Node, Sphere, Text, TextColor,
+    TextFont,
+};
+use bevy::{
+    app::{Plugin, Update},
+    asset::Assets,
+    color::Color,
+    ecs::{
+        component::{Component, Mutable},
+        entity::Entity,
+        event::{EventReader, EventWriter},
+        query::{Added, Has, Or, QueryData, With, Without},
+        resource::Resource,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, EntityCommands, Query, Res, ResMut},
+    },
+    math::{vec3, Vec2, Vec3},
+    pbr::StandardMaterial,
+    render::mesh::Mesh,
+    state::{condition::in_state, state::OnEnter, state_scoped::StateScoped},
+    time::Time,
+    transform::components::Transform,
+    ui::{PositionType, Val},
+};
+use bevy_rapier3d::{
+    dynamics::{RigidBody, Velocity},
+    geometry::{ActiveEvents, Collider, ColliderMassProperties, Sensor},
+    pipeline::CollisionEvent,
+};
+
+use crate::{
+    arena::CurrentArena,
+    ball::Ball,
+    character::{Character, SelectedCharacters},
+    colors::{GREEN, ORANGE, RED, WHITE},
+    constants::BALL_RADIUS,
+    game_state::{GameplaySet, InMatch, MatchState},
+    goals::{GoalType, PlayerType},
+    handicap::{
+        goal_lock_left, slowed, Frozen, Handicap, HandicapEvent, HandicapRules, InvertedControls,
+        LockedGoal, Slowed,
+    },
+    kick::KickState,
+    player::{apply_movement, Player1, Player2},
+    sprint::SprintState,
+};
+
+/// Share of the field pickups spawn in, so they stay away from the walls
+const SPAWN_AREA: f32 = 0.8;
+/// Pickups do not spawn closer than this to players, the ball or other pickups
+const MIN_SPAWN_DISTANCE: f32 = 4.0;
+const PICKUP_SIZE: f32 = 0.8;
+
+/// A timed effect a player gets from a pickup
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PowerUp {
+    SpeedBoost,
+    InfiniteSprint,
+    Giant,
+    StickyBall,
+    Magnet,
+}
+
+impl PowerUp {
+    pub const ALL: [PowerUp; 5] = [
+        PowerUp::SpeedBoost,
+        PowerUp::InfiniteSprint,
+        PowerUp::Giant,
+        PowerUp::StickyBall,
+        PowerUp::Magnet,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PowerUp::SpeedBoost => "Speed",
+            PowerUp::InfiniteSprint => "Endless sprint",
+            PowerUp::Giant => "Giant",
+            PowerUp::StickyBall => "Sticky ball",
+            PowerUp::Magnet => "Magnet",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            PowerUp::SpeedBoost => GREEN,
+            PowerUp::InfiniteSprint => WHITE,
+            PowerUp::Giant => RED,
+            PowerUp::StickyBall => ORANGE,
+            PowerUp::Magnet => Color::srgb(0.3, 0.5, 1.0),
+        }
+    }
+
+    /// Gives the effect to a player, picking up the same power-up again restarts its time
+    fn grant(&self, player: &mut EntityCommands, seconds: f32) {
+        match self {
+            PowerUp::SpeedBoost => player.insert(SpeedBoost(seconds)),
+            PowerUp::InfiniteSprint => player.insert(InfiniteSprint(seconds)),
+            PowerUp::Giant => player.insert(Giant(seconds)),
+            PowerUp::StickyBall => player.insert(StickyBall(seconds)),
+            PowerUp::Magnet => player.insert(Magnet(seconds)),
+        };
+    }
+}
+
+/// Runs faster, the seconds left
+#[derive(Component, Clone, Deref, DerefMut)]
+pub struct SpeedBoost(pub f32);
+
+/// Sprint never runs out, the seconds left
+#[derive(Component, Clone, Deref, DerefMut)]
+pub struct InfiniteSprint(pub f32);
+
+/// Bigger, heavier and kicks harder, the seconds left
+#[derive(Component, Clone, Deref, DerefMut)]
+pub struct Giant(pub f32);
+
+/// The ball sticks to the front of the player until it is kicked, the seconds left
+#[derive(Component, Clone, Deref, DerefMut)]
+pub struct StickyBall(pub f32);
+
+/// Pulls the ball towards the player, the seconds left
+#[derive(Component, Clone, Deref, DerefMut)]
+pub struct Magnet(pub f32);
+
+/// What a pickup gives: a power-up for the collector or a handicap for its opponent
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Item {
+    PowerUp(PowerUp),
+    Handicap(Handicap),
+}
+
+impl Item {
+    fn color(&self) -> Color {
+        match self {
+            Item::PowerUp(power_up) => power_up.color(),
+            Item::Handicap(handicap) => handicap.color(),
+        }
+    }
+}
+
+/// An item lying in the arena, waiting for a player to run over it
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Pickup(pub Item);
+
+/// How often pickups appear and how strong their effects are
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct PowerUpRules {
+    /// Pickups are not part of online snapshots and rollback yet, so those modes turn them off
+    pub enabled: bool,
+    /// Seconds between two pickups
+    pub spawn_interval: f32,
+    /// No more pickups spawn while this many lie in the arena
+    pub max_pickups: usize,
+    /// Share of the pickups that are handicaps instead of power-ups
+    pub handicap_chance: f32,
+    /// Seconds an effect lasts
+    pub duration: f32,
+    /// Multiplies speed and acceleration
+    pub speed_factor: f32,
+    /// Multiplies the size of a giant, mass and kick power grow with it
+    pub giant_scale: f32,
+    /// Distance up to which the magnet pulls the ball
+    pub magnet_range: f32,
+    /// Acceleration towards the player the magnet gives the ball
+    pub magnet_strength: f32,
+    /// Distance up to which the ball sticks to the player
+    pub sticky_range: f32,
+}
+
+impl Default for PowerUpRules {
+    fn default() -> Self {
+        PowerUpRules {
+            enabled: true,
+            spawn_interval: 8.0,
+            max_pickups: 2,
+            handicap_chance: 0.4,
+            duration: 8.0,
+            speed_factor: 1.5,
+            giant_scale: 1.6,
+            magnet_range: 8.0,
+            magnet_strength: 25.0,
+            sticky_range: 2.0,
+        }
+    }
+}
+
+impl PowerUpRules {
+    /// No pickups spawn, for modes that can't sync them
+    pub fn disabled() -> Self {
+        PowerUpRules {
+            enabled: false,
+            ..Default::default()
+        }
+    }
+}
+
+/// When and where the next pickup appears, deterministic so matches can be replayed
+#[derive(Resource, Debug)]
+pub struct PickupSpawner {
+    countdown: f32,
+    rng: u64,
+}
+
+impl Default for PickupSpawner {
+    fn default() -> Self {
+        PickupSpawner {
+            countdown: PowerUpRules::default().spawn_interval,
+            rng: 0x5eed,
+        }
+    }
+}
+
+impl PickupSpawner {
+    /// Uniform in 0..1
+    fn random(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform index below `len`
+    fn pick(&mut self, len: usize) -> usize {
+        ((self.random() * len as f32) as usize).min(len - 1)
+    }
+
+    /// A random spot within `half_size` of the center away from everything `occupied`,
+    /// `None` if none was found
+    pub fn free_spot(&mut self, half_size: Vec2, occupied: &[Vec3]) -> Option<Vec3> {
+        (0..16).find_map(|_| {
+            let spot = vec3(
+                (self.random() * 2.0 - 1.0) * half_size.x,
+                PICKUP_SIZE,
+                (self.random() * 2.0 - 1.0) * half_size.y,
+            );
+            let flat = |p: Vec3| vec3(p.x, 0.0, p.z);
+            occupied
+                .iter()
+                .all(|o| flat(*o).distance(flat(spot)) >= MIN_SPAWN_DISTANCE)
+                .then_some(spot)
+        })
+    }
+}
+
+/// A sensor the players collect by running into it
+pub fn pickup(item: Item, translation: Vec3) -> impl bevy::ecs::bundle::Bundle {
+    (
+        Pickup(item),
+        RigidBody::Fixed,
+        Collider::cuboid(PICKUP_SIZE / 2.0, PICKUP_SIZE / 2.0, PICKUP_SIZE / 2.0),
+        Sensor,
+        ActiveEvents::COLLISION_EVENTS,
+        Transform::from_translation(translation),
+        StateScoped(InMatch),
+    )
+}
+
+fn reset_spawner(rules: Res<PowerUpRules>, mut spawner: ResMut<PickupSpawner>) {
+    spawner.countdown = rules.spawn_interval;
+}
+
+#[allow(clippy::type_complexity)]
+fn spawn_pickups(
+    mut commands: Commands,
+    time: Res<Time>,
+    arena: Res<CurrentArena>,
+    rules: Res<PowerUpRules>,
+    mut spawner: ResMut<PickupSpawner>,
+    q_pickups: Query<&Transform, With<Pickup>>,
+    q_occupied: Query<&Transform, Or<(With<Player1>, With<Player2>, With<Ball>)>>,
+) {
+    if !rules.enabled {
+        return;
+    }
+    spawner.countdown -= time.delta_secs();
+    if spawner.countdown > 0.0 || q_pickups.iter().count() >= rules.max_pickups {
+        return;
+    }
+    spawner.countdown = rules.spawn_interval;
+    let occupied = q_pickups
+        .iter()
+        .chain(q_occupied.iter())
+        .map(|t| t.translation)
+        .collect::<Vec<_>>();
+    let item = if spawner.random() < rules.handicap_chance {
+        Item::Handicap(Handicap::ALL[spawner.pick(Handicap::ALL.len())])
+    } else {
+        Item::PowerUp(PowerUp::ALL[spawner.pick(PowerUp::ALL.len())])
+    };
+    if let Some(spot) = spawner.free_spot(arena.half_size() * SPAWN_AREA, &occupied) {
+        commands.spawn(pickup(item, spot));
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub(crate) fn collect_pickups(
+    mut commands: Commands,
+    rules: Res<PowerUpRules>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut handicap_events: EventWriter<HandicapEvent>,
+    q_pickups: Query<&Pickup>,
+    q_players: Query<(), Or<(With<Player1>, With<Player2>)>>,
+) {
+    let mut collected = HashSet::new();
+    for ev in collision_events.read() {
+        let CollisionEvent::Started(first, second, _) = ev else {
+            continue;
+        };
+        let (pickup, player) = if q_pickups.contains(*first) {
+            (*first, *second)
+        } else {
+            (*second, *first)
+        };
+        let (Ok(Pickup(item)), true) = (q_pickups.get(pickup), q_players.contains(player)) else {
+            continue;
+        };
+        // Both players may touch it in the same frame, the first one gets it
+        if !collected.insert(pickup) {
+            continue;
+        }
+        commands.entity(pickup).despawn();
+        match item {
+            Item::PowerUp(power_up) => power_up.grant(&mut commands.entity(player), rules.duration),
+            Item::Handicap(handicap) => {
+                handicap_events.write(HandicapEvent {
+                    handicap: *handicap,
+                    collector: player,
+                });
+            }
+        }
+    }
+}
+
+/// Counts down an effect and removes it once its time is up
+pub(crate) fn expire<T: Component<Mutability = Mutable> + std::ops::DerefMut<Target = f32>>(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut T)>,
+) {
+    for (entity, mut effect) in query.iter_mut() {
+        **effect -= time.delta_secs();
+        if **effect <= 0.0 {
+            commands.entity(entity).remove::<T>();
+        }
+    }
+}
+
+/// The character a player plays with while boosted
+pub fn boosted(base: Character, speed_boost: bool, giant: bool, rules: &PowerUpRules) -> Character {
+    let mut character = base;
+    if speed_boost {
+        character.speed *= rules.speed_factor;
+        character.acceleration *= rules.speed_factor;
+    }
+    if giant {
+        character.radius *= rules.giant_scale;
+        character.mass *= rules.giant_scale.powi(3);
+        character.kick_power *= rules.giant_scale;
+    }
+    character
+}
+
+#[derive(QueryData)]
+#[query_data(mutable)]
+struct BoostedPlayer {
+    character: &'static mut Character,
+    sprint: &'static mut SprintState,
+    transform: &'static mut Transform,
+    mass: &'static mut ColliderMassProperties,
+    is_player_1: Has<Player1>,
+    speed_boost: Has<SpeedBoost>,
+    giant: Has<Giant>,
+    infinite_sprint: Has<InfiniteSprint>,
+    slowed: Has<Slowed>,
+}
+
+/// Derives the character of each player from its selection and the effects on it
+fn apply_boosts(
+    rules: Res<PowerUpRules>,
+    handicap_rules: Res<HandicapRules>,
+    selected: Res<SelectedCharacters>,
+    mut query: Query<BoostedPlayer>,
+) {
+    for mut player in query.iter_mut() {
+        let base = selected.character(if player.is_player_1 { 0 } else { 1 });
+        let mut character = boosted(base, player.speed_boost, player.giant, &rules);
+        if player.slowed {
+            character = slowed(character, &handicap_rules);
+        }
+        if *player.character != character {
+            *player.character = character;
+            // The collider grows with the scale
+            player.transform.scale = Vec3::splat(character.radius / base.radius);
+            *player.mass = ColliderMassProperties::Mass(character.mass);
+        }
+        if player.infinite_sprint {
+            player.sprint.resupply(f32::MAX, 0.0);
+        }
+    }
+}
+
+/// Magnets pull the ball closer, sticky players keep it in front of them until they kick.
+/// A charging kick keeps holding the ball, it only comes loose once `kick_ball` kicks it.
+#[allow(clippy::type_complexity)]
+fn pull_ball(
+    time: Res<Time>,
+    rules: Res<PowerUpRules>,
+    q_players: Query<
+        (
+            &Transform,
+            &Velocity,
+            &Character,
+            &KickState,
+            Has<StickyBall>,
+            Has<Magnet>,
+        ),
+        (Or<(With<StickyBall>, With<Magnet>)>, Without<Ball>),
+    >,
+    mut q_ball: Query<(&Transform, &mut Velocity), With<Ball>>,
+) {
+    let Ok((ball, mut ball_velocity)) = q_ball.single_mut() else {
+        return;
+    };
+    for (transform, velocity, character, kick, sticky, magnet) in q_players.iter() {
+        let offset = vec3(
+            transform.translation.x - ball.translation.x,
+            0.0,
+            transform.translation.z - ball.translation.z,
+        );
+        // `apply_movement` turns the player's +z towards where it moves
+        let facing = transform.rotation * Vec3::Z;
+        let hold = transform.translation + facing * (character.radius + BALL_RADIUS + 0.2);
+        let to_hold = vec3(
+            hold.x - ball.translation.x,
+            0.0,
+            hold.z - ball.translation.z,
+        );
+        if sticky && !kick.is_cooling_down() && offset.length() <= rules.sticky_range {
+            let carried = vec3(velocity.linvel.x, 0.0, velocity.linvel.z) + to_hold * 10.0;
+            ball_velocity.linvel = vec3(carried.x, ball_velocity.linvel.y, carried.z);
+        } else if magnet && offset.length() <= rules.magnet_range {
+            ball_velocity.linvel +=
+                offset.normalize_or_zero() * rules.magnet_strength * time.delta_secs();
+        }
+    }
+}
+
+/// Spawns pickups on a timer and applies the effects of collected ones
+pub struct PowerUpPlugin;
+
+impl Plugin for PowerUpPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<PowerUpRules>()
+            .init_resource::<PickupSpawner>()
+            .add_systems(OnEnter(InMatch), reset_spawner)
+            .add_systems(
+                Update,
+                (
+                    (apply_boosts, pull_ball)
+                        .before(apply_movement)
+                        .in_set(GameplaySet::Movement),
+                    collect_pickups.in_set(GameplaySet::Resolve),
+                    (
+                        spawn_pickups,
+                        expire::<SpeedBoost>,
+                        expire::<InfiniteSprint>,
+                        expire::<Giant>,
+                        expire::<StickyBall>,
+                        expire::<Magnet>,
+                    )
+                        .in_set(GameplaySet::Flow),
+                )
+                    .run_if(in_state(MatchState::Playing)),
+            );
+    }
+}
+
+fn add_pickup_meshes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    q_pickups: Query<(Entity, &Pickup), Added<Pickup>>,
+) {
+    for (entity, Pickup(item)) in q_pickups.iter() {
+        // Handicaps are round, so they can be told apart from power-ups of a similar color
+        let mesh = match item {
+            Item::PowerUp(_) => meshes.add(Cuboid::from_length(PICKUP_SIZE)),
+            Item::Handicap(_) => meshes.add(Sphere::new(PICKUP_SIZE / 2.0)),
+        };
+        commands.entity(entity).insert((
+            Mesh3d(mesh),
+            MeshMaterial3d(materials.add(StandardMaterial {
+                base_color: item.color(),
+                ..Default::default()
+            })),
+        ));
+    }
+}
+
+#[derive(Component)]
+struct EffectsText(usize);
+
+fn spawn_effects_text(mut commands: Commands) {
+    for player in 0..2 {
+        let mut node = Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(16.0),
+            display: Display::Flex,
+            ..Default::default()
+        };
+        if player == 0 {
+            node.left = Val::Px(16.0);
+        } else {
+            node.right = Val::Px(16.0);
+        }
+        commands.spawn((
+            EffectsText(player),
+            node,
+            Text::default(),
+            TextFont {
+                font_size: 20.0,
+                ..Default::default()
+            },
+            TextColor(if player == 0 { GREEN } else { ORANGE }),
+            StateScoped(InMatch),
+        ));
+    }
+}
+
+#[derive(QueryData)]
+struct PlayerEffects {
+    is_player_1: Has<Player1>,
+    speed_boost: Option<&'static SpeedBoost>,
+    infinite_sprint: Option<&'static InfiniteSprint>,
+    giant: Option<&'static Giant>,
+    sticky_ball: Option<&'static StickyBall>,
+    magnet: Option<&'static Magnet>,
+    inverted_controls: Option<&'static InvertedControls>,
+    slowed: Option<&'static Slowed>,
+    frozen: Option<&'static Frozen>,
+}
+
+#[allow(clippy::type_complexity)]
+fn show_effects(
+    q_players: Query<PlayerEffects, Or<(With<Player1>, With<Player2>)>>,
+    q_locks: Query<(&LockedGoal, &GoalType)>,
+    mut q_texts: Query<(&EffectsText, &mut Text)>,
+) {
+    for effects in q_players.iter() {
+        let (player, player_type) = if effects.is_player_1 {
+            (0, PlayerType::First)
+        } else {
+            (1, PlayerType::Second)
+        };
+        let active = [
+            (PowerUp::SpeedBoost.name(), effects.speed_boost.map(|e| e.0)),
+            (
+                PowerUp::InfiniteSprint.name(),
+                effects.infinite_sprint.map(|e| e.0),
+            ),
+            (PowerUp::Giant.name(), effects.giant.map(|e| e.0)),
+            (PowerUp::StickyBall.name(), effects.sticky_ball.map(|e| e.0)),
+            (PowerUp::Magnet.name(), effects.magnet.map(|e| e.0)),
+            (
+                Handicap::InvertedControls.name(),
+                effects.inverted_controls.map(|e| e.0),
+            ),
+            (Handicap::Slowed.name(), effects.slowed.map(|e| e.0)),
+            (Handicap::Frozen.name(), effects.frozen.map(|e| e.0)),
+            (
+                Handicap::LockedGoal.name(),
+                goal_lock_left(&q_locks, player_type),
+            ),
+        ];
+        let lines = active
+            .iter()
+            .filter_map(|(name, left)| left.map(|left| format!("{name} {}s", left.ceil())))
+            .collect::<Vec<_>>()
+            .join("\n");
+        for (slot, mut text) in &mut q_texts {
+            if slot.0 == player {
+                text.0.clone_from(&lines);
+            }
+        }
+    }
+}
+
+/// Draws the pickups and lists the effects of each player in the top corners
+pub struct PowerUpRenderPlugin;
+
+impl Plugin for PowerUpRenderPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_systems(OnEnter(InMatch), spawn_effects_text)
+            .add_systems(Update, (add_pickup_meshes, show_effects));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{vec2, vec3, Vec3};
+
+    use super::{boosted, PickupSpawner, PowerUpRules, MIN_SPAWN_DISTANCE};
+    use crate::character::Character;
+
+    #[test]
+    fn free_spot_when_arena_has_room_then_away_from_everything_and_inside() {
+        // given
+        let mut spawner = PickupSpawner::default();
+        let area = vec2(24.0, 12.0);
+        let occupied = [Vec3::ZERO, vec3(10.0, 1.0, 5.0), vec3(-10.0, 1.0, -5.0)];
+
+        // when
+        let spots = (0..50)
+            .filter_map(|_| spawner.free_spot(area, &occupied))
+            .collect::<Vec<_>>();
+
+        // then
+        assert_eq!(spots.len(), 50);
+        for spot in spots {
+            assert!(spot.x.abs() <= area.x && spot.z.abs() <= area.y);
+            assert!(occupied
+                .iter()
+                .all(|o| vec3(o.x - spot.x, 0.0, o.z - spot.z).length() >= MIN_SPAWN_DISTANCE));
+        }
+    }
+
+    #[test]
+    fn boosted_when_giant_and_fast_then_bigger_heavier_and_faster() {
+        // given
+        let base = Character::default();
+        let rules = PowerUpRules::default();
+
+        // when
+        let character = boosted(base, true, true, &rules);
+
+        // then
+        assert!(character.speed > base.speed);
+        assert!(character.radius > base.radius);
+        assert!(character.mass > base.mass);
+        assert!(character.kick_power > base.kick_power);
+        assert_eq!(boosted(base, false, false, &rules), base);
+    }
+}
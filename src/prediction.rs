@@ -0,0 +1,563 @@
+use std::{collections::VecDeque, time::Duration};
+
+use bevy::prelude::{Display, Node, Text, TextColor, TextFont};
+use bevy::{
+    app::{FixedUpdate, Plugin, PostUpdate, Startup, Update},
+    ecs::{
+        change_detection::DetectChanges,
+        component::Component,
+        entity::Entity,
+        event::EventWriter,
+        query::{Added, Has, Or, With},
+        resource::Resource,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    input::{keyboard::KeyCode, ButtonInput},
+    math::Vec3,
+    state::{condition::in_state, state::OnEnter},
+    time::{Fixed, Real, Time},
+    transform::components::Transform,
+    ui::{PositionType, UiRect, Val},
+};
+use bevy_rapier3d::{dynamics::Velocity, plugin::PhysicsSet};
+
+use crate::{
+    ball::Ball,
+    character::Character,
+    colors::WHITE,
+    game_state::MatchState,
+    goals::PlayerType,
+    network::{BodySnapshot, ClientMsg, Snapshot, WorldSnapshot},
+    online::{receive_server_messages, CurrentLobby, LatestSnapshot, SendToServer},
+    player::{steer, Player1, Player2, PlayerInput},
+    simulation::TICK_RATE,
+    sprint::SprintState,
+};
+
+/// How far remote bodies are shown behind the newest snapshot, so there usually is a
+/// snapshot on both sides to interpolate between
+const INTERPOLATION_DELAY_TICKS: f32 = 6.0;
+const MAX_BUFFERED_SNAPSHOTS: usize = 32;
+/// Further off than this, the playback jumps instead of catching up smoothly
+const MAX_PLAYBACK_DRIFT_TICKS: f32 = 10.0;
+/// Inputs the server never acknowledges are forgotten after this many ticks
+const MAX_INPUT_HISTORY: usize = 120;
+/// Corrections larger than this are shown at once instead of being smoothed out
+const MAX_SMOOTHED_CORRECTION: f32 = 2.0;
+
+/// The player this client controls
+#[derive(Component)]
+struct LocalPlayer;
+
+#[derive(Component)]
+struct NetStatsText;
+
+struct SentInput {
+    tick: u32,
+    input: PlayerInput,
+    sent_at: Duration,
+}
+
+/// Inputs sent to the server that it did not acknowledge yet
+#[derive(Resource, Default)]
+struct InputHistory {
+    next_tick: u32,
+    inputs: VecDeque<SentInput>,
+}
+
+/// The local player as far as it got with all inputs sent so far
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PredictedBody {
+    pub translation: Vec3,
+    pub linvel: Vec3,
+}
+
+impl PredictedBody {
+    /// Moves the body like the server does in one tick with `input`
+    pub fn step(
+        self,
+        input: &PlayerInput,
+        can_sprint: bool,
+        character: &Character,
+        dt: f32,
+    ) -> Self {
+        let linvel = steer(self.linvel, input, can_sprint, character, dt);
+        PredictedBody {
+            translation: self.translation + linvel * dt,
+            linvel,
+        }
+    }
+}
+
+impl From<BodySnapshot> for PredictedBody {
+    fn from(body: BodySnapshot) -> Self {
+        PredictedBody {
+            translation: body.translation,
+            linvel: body.linvel,
+        }
+    }
+}
+
+/// Replays the inputs the server did not apply yet on top of its state of the player
+pub fn reconcile<'a>(
+    server: PredictedBody,
+    pending: impl IntoIterator<Item = &'a PlayerInput>,
+    can_sprint: bool,
+    character: &Character,
+    dt: f32,
+) -> PredictedBody {
+    pending.into_iter().fold(server, |body, input| {
+        body.step(input, can_sprint, character, dt)
+    })
+}
+
+#[derive(Resource, Default)]
+struct Prediction {
+    /// Only predicted while playing, otherwise the server moves the players on its own
+    body: Option<PredictedBody>,
+    /// Left over from the last correction, shrinks every frame so the player does not jump
+    smoothing: Vec3,
+}
+
+/// Snapshots of the server by tick, remote bodies are shown between two of them
+#[derive(Resource, Default)]
+struct SnapshotBuffer {
+    snapshots: VecDeque<(u32, WorldSnapshot)>,
+    playback_tick: f32,
+}
+
+impl SnapshotBuffer {
+    /// Advances the playback by `ticks`, drifting towards a fixed delay behind the newest snapshot
+    fn advance(&mut self, ticks: f32) {
+        let Some((newest, _)) = self.snapshots.back() else {
+            return;
+        };
+        let target = *newest as f32 - INTERPOLATION_DELAY_TICKS;
+        self.playback_tick += ticks;
+        let drift = target - self.playback_tick;
+        if drift.abs() > MAX_PLAYBACK_DRIFT_TICKS {
+            self.playback_tick = target;
+        } else {
+            self.playback_tick += drift * 0.1;
+        }
+    }
+}
+
+/// The world between the two buffered snapshots around `tick`, or the first or last one
+/// when `tick` is outside of the buffer
+pub fn interpolate(snapshots: &VecDeque<(u32, WorldSnapshot)>, tick: f32) -> Option<WorldSnapshot> {
+    let (from, to) = match snapshots.iter().position(|(t, _)| *t as f32 >= tick) {
+        None => return snapshots.back().map(|(_, world)| world.clone()),
+        Some(0) => return snapshots.front().map(|(_, world)| world.clone()),
+        Some(i) => (&snapshots[i - 1], &snapshots[i]),
+    };
+    let t = (tick - from.0 as f32) / (to.0 - from.0) as f32;
+    Some(WorldSnapshot {
+        player_1: from.1.player_1.lerp(&to.1.player_1, t),
+        player_2: from.1.player_2.lerp(&to.1.player_2, t),
+        ball: from.1.ball.lerp(&to.1.ball, t),
+        ..to.1.clone()
+    })
+}
+
+#[derive(Resource, Default)]
+struct NetStats {
+    /// From sending an input until a snapshot includes it, smoothed over many inputs
+    rtt: Option<Duration>,
+    /// How far the last snapshot moved the predicted player
+    correction: f32,
+}
+
+fn own_body(snapshot: &Snapshot, you: PlayerType) -> Option<(usize, BodySnapshot)> {
+    let world = snapshot.world.as_ref()?;
+    Some(match you {
+        PlayerType::First => (0, world.player_1),
+        PlayerType::Second => (1, world.player_2),
+    })
+}
+
+fn mark_local_player(
+    mut commands: Commands,
+    lobby: Res<CurrentLobby>,
+    q_players: Query<(Entity, Has<Player1>), Added<PlayerInput>>,
+) {
+    let Some(you) = lobby.0.as_ref().map(|l| l.you) else {
+        return;
+    };
+    for (entity, is_player_1) in q_players.iter() {
+        if is_player_1 == (you == PlayerType::First) {
+            commands.entity(entity).insert(LocalPlayer);
+        }
+    }
+}
+
+fn reset_prediction(mut prediction: ResMut<Prediction>, mut history: ResMut<InputHistory>) {
+    *prediction = Prediction::default();
+    history.inputs.clear();
+}
+
+/// Sends the input of this tick and applies it locally right away
+fn predict_local_player(
+    time: Res<Time>,
+    real_time: Res<Time<Real>>,
+    mut history: ResMut<InputHistory>,
+    mut prediction: ResMut<Prediction>,
+    q_local: Query<(&PlayerInput, &SprintState, &Character), With<LocalPlayer>>,
+    mut send: EventWriter<SendToServer>,
+) {
+    let Ok((input, sprint, character)) = q_local.single() else {
+        return;
+    };
+    // Starts with the first snapshot of the server while playing
+    let Some(body) = prediction.body else {
+        return;
+    };
+
+    let tick = history.next_tick;
+    history.next_tick = tick.wrapping_add(1);
+    history.inputs.push_back(SentInput {
+        tick,
+        input: *input,
+        sent_at: real_time.elapsed(),
+    });
+    if history.inputs.len() > MAX_INPUT_HISTORY {
+        history.inputs.pop_front();
+    }
+    send.write(SendToServer(ClientMsg::Input {
+        tick,
+        input: *input,
+    }));
+    prediction.body = Some(body.step(input, sprint.is_available(), character, time.delta_secs()));
+}
+
+fn buffer_snapshot(latest: Res<LatestSnapshot>, mut buffer: ResMut<SnapshotBuffer>) {
+    if !latest.is_changed() {
+        return;
+    }
+    let Some(snapshot) = &latest.0 else {
+        *buffer = SnapshotBuffer::default();
+        return;
+    };
+    let Some(world) = &snapshot.world else {
+        return;
+    };
+    if buffer
+        .snapshots
+        .back()
+        .is_some_and(|(tick, _)| *tick >= snapshot.tick)
+    {
+        // A new match counts its ticks from zero again
+        buffer.snapshots.clear();
+    }
+    buffer.snapshots.push_back((snapshot.tick, world.clone()));
+    if buffer.snapshots.len() > MAX_BUFFERED_SNAPSHOTS {
+        buffer.snapshots.pop_front();
+    }
+}
+
+/// Restarts the prediction from the state of the server whenever a snapshot arrives while playing
+fn reconcile_prediction(
+    real_time: Res<Time<Real>>,
+    latest: Res<LatestSnapshot>,
+    lobby: Res<CurrentLobby>,
+    mut history: ResMut<InputHistory>,
+    mut prediction: ResMut<Prediction>,
+    mut stats: ResMut<NetStats>,
+    q_local: Query<(&SprintState, &Character), With<LocalPlayer>>,
+) {
+    if !latest.is_changed() {
+        return;
+    }
+    let Some(you) = lobby.0.as_ref().map(|l| l.you) else {
+        return;
+    };
+    let Some(snapshot) = latest.0.as_ref().filter(|s| s.state == MatchState::Playing) else {
+        return;
+    };
+    let Some((slot, server_body)) = own_body(snapshot, you) else {
+        return;
+    };
+
+    if let Some(acked) = snapshot.input_ticks[slot] {
+        if let Some(sent) = history.inputs.iter().find(|i| i.tick == acked) {
+            let sample = real_time.elapsed().saturating_sub(sent.sent_at);
+            stats.rtt = Some(match stats.rtt {
+                Some(rtt) => rtt.mul_f32(0.9) + sample.mul_f32(0.1),
+                None => sample,
+            });
+        }
+        history.inputs.retain(|i| i.tick > acked);
+    }
+
+    let (can_sprint, character) = q_local
+        .single()
+        .map(|(sprint, character)| (sprint.is_available(), *character))
+        .unwrap_or_default();
+    let pending = history.inputs.iter().map(|i| &i.input);
+    let reconciled = reconcile(
+        server_body.into(),
+        pending,
+        can_sprint,
+        &character,
+        1.0 / TICK_RATE,
+    );
+    if let Some(predicted) = prediction.body {
+        let error = predicted.translation - reconciled.translation;
+        stats.correction = error.length();
+        prediction.smoothing = if error.length() > MAX_SMOOTHED_CORRECTION {
+            Vec3::ZERO
+        } else {
+            prediction.smoothing + error
+        };
+    }
+    prediction.body = Some(reconciled);
+}
+
+/// Puts the local player where it is predicted and everything else where the server
+/// had it a few ticks ago
+#[allow(clippy::type_complexity)]
+fn show_bodies(
+    time: Res<Time>,
+    mut buffer: ResMut<SnapshotBuffer>,
+    mut prediction: ResMut<Prediction>,
+    mut q_bodies: Query<
+        (
+            &mut Transform,
+            &mut Velocity,
+            Has<Player1>,
+            Has<Player2>,
+            Has<LocalPlayer>,
+        ),
+        Or<(With<Player1>, With<Player2>, With<Ball>)>,
+    >,
+) {
+    let dt = time.delta_secs();
+    buffer.advance(dt * TICK_RATE);
+    prediction.smoothing *= (-dt * 10.0).exp();
+    let world = interpolate(&buffer.snapshots, buffer.playback_tick);
+
+    for (mut transform, mut velocity, is_player_1, is_player_2, is_local) in q_bodies.iter_mut() {
+        if let (true, Some(body)) = (is_local, prediction.body) {
+            transform.translation = body.translation + prediction.smoothing;
+            velocity.linvel = body.linvel;
+            continue;
+        }
+        let Some(world) = &world else {
+            continue;
+        };
+        let body = match (is_player_1, is_player_2) {
+            (true, _) => world.player_1,
+            (_, true) => world.player_2,
+            _ => world.ball,
+        };
+        body.apply(&mut transform, &mut velocity);
+    }
+}
+
+fn setup_net_stats_text(mut commands: Commands) {
+    commands.spawn((
+        NetStatsText,
+        Node {
+            display: Display::None,
+            position_type: PositionType::Absolute,
+            left: Val::Px(8.0),
+            top: Val::Px(8.0),
+            padding: UiRect::all(Val::Px(4.0)),
+            ..Default::default()
+        },
+        Text::default(),
+        TextFont {
+            font_size: 16.0,
+            ..Default::default()
+        },
+        TextColor(WHITE),
+    ));
+}
+
+fn toggle_net_stats(
+    input: Res<ButtonInput<KeyCode>>,
+    mut query: Query<&mut Node, With<NetStatsText>>,
+) {
+    if !input.just_pressed(KeyCode::F3) {
+        return;
+    }
+    for mut node in &mut query {
+        node.display = match node.display {
+            Display::None => Display::Flex,
+            _ => Display::None,
+        };
+    }
+}
+
+fn describe_net_stats(stats: &NetStats, buffered: usize) -> String {
+    let rtt = stats
+        .rtt
+        .map(|rtt| format!("{} ms", rtt.as_millis()))
+        .unwrap_or_else(|| "-".into());
+    format!(
+        "RTT: {rtt}\nSnapshots buffered: {buffered}\nCorrection: {:.3}",
+        stats.correction
+    )
+}
+
+fn update_net_stats_text(
+    stats: Res<NetStats>,
+    buffer: Res<SnapshotBuffer>,
+    mut query: Query<&mut Text, With<NetStatsText>>,
+) {
+    for mut text in &mut query {
+        text.0 = describe_net_stats(&stats, buffer.snapshots.len());
+    }
+}
+
+/// Predicts the local player of an online client, interpolates everything else between
+/// snapshots of the server and shows network stats with F3
+pub struct PredictionPlugin;
+
+impl Plugin for PredictionPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.insert_resource(Time::<Fixed>::from_hz(TICK_RATE.into()))
+            .init_resource::<InputHistory>()
+            .init_resource::<Prediction>()
+            .init_resource::<SnapshotBuffer>()
+            .init_resource::<NetStats>()
+            .add_systems(Startup, setup_net_stats_text)
+            .add_systems(OnEnter(MatchState::Playing), reset_prediction)
+            .add_systems(
+                Update,
+                (mark_local_player, toggle_net_stats, update_net_stats_text),
+            )
+            .add_systems(
+                FixedUpdate,
+                predict_local_player.run_if(in_state(MatchState::Playing)),
+            )
+            .add_systems(
+                PostUpdate,
+                (buffer_snapshot, reconcile_prediction, show_bodies)
+                    .chain()
+                    .after(receive_server_messages)
+                    .before(PhysicsSet::SyncBackend),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, time::Duration};
+
+    use bevy::math::{Quat, Vec3};
+
+    use super::{interpolate, reconcile, PredictedBody, SnapshotBuffer};
+    use crate::{
+        character::Character,
+        network::{BodySnapshot, WorldSnapshot},
+        player::PlayerInput,
+        points::Points,
+    };
+
+    fn world_with_ball_at(x: f32) -> WorldSnapshot {
+        let body = |translation| BodySnapshot {
+            translation,
+            rotation: Quat::IDENTITY,
+            linvel: Vec3::ZERO,
+        };
+        WorldSnapshot {
+            player_1: body(Vec3::ZERO),
+            player_2: body(Vec3::ZERO),
+            ball: body(Vec3::new(x, 0.0, 0.0)),
+            points: Points::default(),
+            game_time_left: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn reconcile_when_server_agrees_then_same_as_prediction() {
+        // given
+        let start = PredictedBody {
+            translation: Vec3::ZERO,
+            linvel: Vec3::ZERO,
+        };
+        let input = PlayerInput {
+            movement: Vec3::X,
+            sprint: false,
+            kick: false,
+            tackle: false,
+        };
+        let character = Character::default();
+        let acked = start.step(&input, false, &character, 0.1);
+        let predicted = acked
+            .step(&input, false, &character, 0.1)
+            .step(&input, false, &character, 0.1);
+
+        // when
+        let reconciled = reconcile(acked, [&input, &input], false, &character, 0.1);
+
+        // then
+        assert_eq!(reconciled, predicted);
+    }
+
+    #[test]
+    fn reconcile_when_server_disagrees_then_replays_from_server() {
+        // given
+        let server = PredictedBody {
+            translation: Vec3::new(0.0, 0.0, 5.0),
+            linvel: Vec3::ZERO,
+        };
+        let input = PlayerInput {
+            movement: Vec3::X,
+            sprint: false,
+            kick: false,
+            tackle: false,
+        };
+        let character = Character::default();
+
+        // when
+        let reconciled = reconcile(server, [&input], false, &character, 0.1);
+
+        // then
+        assert_eq!(reconciled.translation.z, 5.0);
+        assert!(reconciled.translation.x > 0.0);
+    }
+
+    #[test]
+    fn interpolate_when_between_snapshots_then_lerps() {
+        // given
+        let snapshots =
+            VecDeque::from([(10, world_with_ball_at(0.0)), (14, world_with_ball_at(4.0))]);
+
+        // when
+        let world = interpolate(&snapshots, 11.0);
+
+        // then
+        assert_eq!(world.map(|w| w.ball.translation.x), Some(1.0));
+    }
+
+    #[test]
+    fn interpolate_when_past_newest_snapshot_then_holds_it() {
+        // given
+        let snapshots =
+            VecDeque::from([(10, world_with_ball_at(0.0)), (14, world_with_ball_at(4.0))]);
+
+        // when
+        let world = interpolate(&snapshots, 20.0);
+
+        // then
+        assert_eq!(world.map(|w| w.ball.translation.x), Some(4.0));
+    }
+
+    #[test]
+    fn advance_when_far_behind_then_jumps_to_delay() {
+        // given
+        let mut buffer = SnapshotBuffer {
+            snapshots: VecDeque::from([(100, world_with_ball_at(0.0))]),
+            playback_tick: 0.0,
+        };
+
+        // when
+        buffer.advance(1.0);
+
+        // then
+        assert_eq!(buffer.playback_tick, 94.0);
+    }
+}
@@ -0,0 +1,688 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    rc::Rc,
+};
+
+use bevy::{
+    app::App,
+    ecs::{
+        component::{Component, Mutable},
+        entity::Entity,
+        event::Events,
+        query::{QueryFilter, With},
+        world::World,
+    },
+    state::state::{NextState, State},
+    transform::components::{GlobalTransform, Transform},
+};
+use bevy_rapier3d::{
+    dynamics::{RigidBody, Velocity},
+    pipeline::CollisionEvent,
+    plugin::context::{
+        RapierContextColliders, RapierContextJoints, RapierContextSimulation, RapierQueryPipeline,
+        RapierRigidBodySet,
+    },
+    rapier::prelude::{CCDSolver, DefaultBroadPhase, IslandManager, NarrowPhase},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arena_features::MovingPlatform,
+    ball::StuckTime,
+    game_state::{GameTime, MatchState, PhaseTimer},
+    goals::{BallsInGoal, PlayerType},
+    kick::KickState,
+    lobby::{player_input, snapshot},
+    match_rules::Rounds,
+    network::{decode, encode, Snapshot},
+    player::{Player1, Player2, PlayerInput},
+    points::Points,
+    powerup::PowerUpRules,
+    sprint::SprintState,
+    tackle::TackleState,
+};
+
+/// How many frames a peer may run ahead of the last input it has from the other peer
+pub const MAX_PREDICTION_FRAMES: u32 = 8;
+
+/// Moves bytes between the two peers. Messages may be late, lost or reordered.
+pub trait Transport {
+    fn send(&mut self, bytes: Vec<u8>);
+    fn receive(&mut self) -> Vec<Vec<u8>>;
+}
+
+/// Sent by a peer every frame. Repeats all inputs the other peer did not confirm yet,
+/// so a lost message is made up for by the next one.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PeerInputs {
+    /// Frame of the first input
+    pub first_frame: u32,
+    pub inputs: Vec<PlayerInput>,
+    /// The sender has every input of the receiver before this frame
+    pub received_until: u32,
+}
+
+/// Everything a frame of the match can change, to simulate it again with other inputs
+struct SavedState {
+    islands: IslandManager,
+    broad_phase: DefaultBroadPhase,
+    narrow_phase: NarrowPhase,
+    ccd_solver: CCDSolver,
+    bodies: RapierRigidBodySet,
+    colliders: RapierContextColliders,
+    joints: RapierContextJoints,
+    query_pipeline: RapierQueryPipeline,
+    state: MatchState,
+    next_state: NextState<MatchState>,
+    phase_timer: Option<PhaseTimer>,
+    transforms: Vec<(Entity, Transform)>,
+    global_transforms: Vec<(Entity, GlobalTransform)>,
+    velocities: Vec<(Entity, Velocity)>,
+    inputs: Vec<(Entity, PlayerInput)>,
+    sprint: Vec<(Entity, SprintState)>,
+    kicks: Vec<(Entity, KickState)>,
+    tackles: Vec<(Entity, TackleState)>,
+    points: Vec<(Entity, Points)>,
+    game_time: Vec<(Entity, GameTime)>,
+    rounds: Vec<(Entity, Rounds)>,
+    balls_in_goal: Vec<(Entity, BallsInGoal)>,
+    stuck_time: Vec<(Entity, StuckTime)>,
+    platforms: Vec<(Entity, MovingPlatform)>,
+    /// Contacts found by the last physics step, they are only read in the next frame
+    collisions: Vec<CollisionEvent>,
+}
+
+fn save<C: Component + Clone, F: QueryFilter>(world: &mut World) -> Vec<(Entity, C)> {
+    world
+        .query_filtered::<(Entity, &C), F>()
+        .iter(world)
+        .map(|(entity, component)| (entity, component.clone()))
+        .collect()
+}
+
+fn load<C: Component<Mutability = Mutable> + Clone>(world: &mut World, saved: &[(Entity, C)]) {
+    for (entity, component) in saved {
+        if let Some(mut current) = world.get_mut::<C>(*entity) {
+            *current = component.clone();
+        }
+    }
+}
+
+impl SavedState {
+    fn save(world: &mut World) -> Self {
+        let (simulation, bodies, colliders, joints, query_pipeline) = world
+            .query::<(
+                &RapierContextSimulation,
+                &RapierRigidBodySet,
+                &RapierContextColliders,
+                &RapierContextJoints,
+                &RapierQueryPipeline,
+            )>()
+            .single(world)
+            .expect("a match has exactly one physics context");
+        let (islands, broad_phase, narrow_phase, ccd_solver) = (
+            simulation.islands.clone(),
+            simulation.broad_phase.clone(),
+            simulation.narrow_phase.clone(),
+            simulation.ccd_solver.clone(),
+        );
+        let (bodies, colliders, joints, query_pipeline) = (
+            bodies.clone(),
+            colliders.clone(),
+            joints.clone(),
+            query_pipeline.clone(),
+        );
+        SavedState {
+            islands,
+            broad_phase,
+            narrow_phase,
+            ccd_solver,
+            bodies,
+            colliders,
+            joints,
+            query_pipeline,
+            state: *world.resource::<State<MatchState>>().get(),
+            next_state: world.resource::<NextState<MatchState>>().clone(),
+            phase_timer: world.get_resource::<PhaseTimer>().cloned(),
+            transforms: save::<Transform, With<RigidBody>>(world),
+            global_transforms: save::<GlobalTransform, With<RigidBody>>(world),
+            velocities: save::<Velocity, ()>(world),
+            inputs: save::<PlayerInput, ()>(world),
+            sprint: save::<SprintState, ()>(world),
+            kicks: save::<KickState, ()>(world),
+            tackles: save::<TackleState, ()>(world),
+            points: save::<Points, ()>(world),
+            game_time: save::<GameTime, ()>(world),
+            rounds: save::<Rounds, ()>(world),
+            balls_in_goal: save::<BallsInGoal, ()>(world),
+            stuck_time: save::<StuckTime, ()>(world),
+            platforms: save::<MovingPlatform, ()>(world),
+            collisions: world
+                .resource::<Events<CollisionEvent>>()
+                .iter_current_update_events()
+                .copied()
+                .collect(),
+        }
+    }
+
+    fn load(&self, world: &mut World) {
+        let (mut simulation, mut bodies, mut colliders, mut joints, mut query_pipeline) = world
+            .query::<(
+                &mut RapierContextSimulation,
+                &mut RapierRigidBodySet,
+                &mut RapierContextColliders,
+                &mut RapierContextJoints,
+                &mut RapierQueryPipeline,
+            )>()
+            .single_mut(world)
+            .expect("a match has exactly one physics context");
+        simulation.islands = self.islands.clone();
+        simulation.broad_phase = self.broad_phase.clone();
+        simulation.narrow_phase = self.narrow_phase.clone();
+        simulation.ccd_solver = self.ccd_solver.clone();
+        *bodies = self.bodies.clone();
+        *colliders = self.colliders.clone();
+        *joints = self.joints.clone();
+        *query_pipeline = self.query_pipeline.clone();
+
+        world.insert_resource(State::new(self.state));
+        world.insert_resource(self.next_state.clone());
+        match &self.phase_timer {
+            Some(timer) => world.insert_resource(timer.clone()),
+            None => {
+                world.remove_resource::<PhaseTimer>();
+            }
+        }
+        load(world, &self.transforms);
+        load(world, &self.global_transforms);
+        load(world, &self.velocities);
+        load(world, &self.inputs);
+        load(world, &self.sprint);
+        load(world, &self.kicks);
+        load(world, &self.tackles);
+        load(world, &self.points);
+        load(world, &self.game_time);
+        load(world, &self.rounds);
+        load(world, &self.balls_in_goal);
+        load(world, &self.stuck_time);
+        load(world, &self.platforms);
+
+        // Readers already went past the saved contacts, sending them again makes them new
+        let mut collisions = world.resource_mut::<Events<CollisionEvent>>();
+        collisions.clear();
+        collisions.send_batch(self.collisions.iter().copied());
+    }
+}
+
+/// A simulated frame that may still have to be simulated again
+struct Frame {
+    number: u32,
+    /// The match right before the frame
+    saved: SavedState,
+    inputs: [PlayerInput; 2],
+    /// Inputs only move the players while playing, other frames never need to be corrected
+    playing: bool,
+}
+
+/// One peer of a rollback match. Both peers simulate the whole match and only exchange inputs.
+/// The input of the other peer is predicted until it arrives, if the prediction was wrong
+/// the match is rewound to that frame and simulated again.
+pub struct RollbackSession<T: Transport> {
+    /// Advances one frame per update, like `headless_app`
+    app: Box<App>,
+    transport: T,
+    local: PlayerType,
+    /// The next frame to simulate
+    frame: u32,
+    /// Simulated frames that use a predicted input of the other peer, oldest first
+    frames: VecDeque<Frame>,
+    local_inputs: VecDeque<PlayerInput>,
+    /// Frame of the first of `local_inputs`
+    local_inputs_from: u32,
+    remote_inputs: BTreeMap<u32, PlayerInput>,
+    /// Every input of the other peer before this frame is known
+    remote_received_until: u32,
+    /// The other peer has every local input before this frame
+    peer_received_until: u32,
+    rollbacks: u32,
+}
+
+impl<T: Transport> RollbackSession<T> {
+    /// Starts the match right away, the peers synchronize through their inputs
+    pub fn new(new_match: fn() -> App, local: PlayerType, transport: T) -> Self {
+        let mut app = Box::new(new_match());
+        // Spawned pickups could not be rolled back
+        app.insert_resource(PowerUpRules::disabled());
+        // Runs the startup systems, which create the physics context
+        app.update();
+        app.world_mut()
+            .resource_mut::<NextState<MatchState>>()
+            .set(MatchState::Kickoff);
+        RollbackSession {
+            app,
+            transport,
+            local,
+            frame: 0,
+            frames: VecDeque::new(),
+            local_inputs: VecDeque::new(),
+            local_inputs_from: 0,
+            remote_inputs: BTreeMap::new(),
+            remote_received_until: 0,
+            peer_received_until: 0,
+            rollbacks: 0,
+        }
+    }
+
+    pub fn local(&self) -> PlayerType {
+        self.local
+    }
+
+    /// The next frame to simulate
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// How often a wrong prediction had to be corrected
+    pub fn rollbacks(&self) -> u32 {
+        self.rollbacks
+    }
+
+    /// The match as of the last simulated frame
+    pub fn snapshot(&mut self) -> Snapshot {
+        Snapshot {
+            tick: self.frame,
+            ..snapshot(self.app.world_mut())
+        }
+    }
+
+    /// Simulates the next frame with the local `input`. Returns false without simulating when
+    /// the other peer is too far behind, the same input should be passed again next time.
+    pub fn advance(&mut self, input: PlayerInput) -> bool {
+        self.receive();
+        self.correct_predictions();
+        if self.frame >= self.remote_received_until + MAX_PREDICTION_FRAMES {
+            self.send_inputs();
+            return false;
+        }
+        self.local_inputs.push_back(input);
+        self.simulate();
+        self.send_inputs();
+        self.forget_confirmed();
+        true
+    }
+
+    fn receive(&mut self) {
+        for bytes in self.transport.receive() {
+            let Some(msg) = decode::<PeerInputs>(&bytes) else {
+                continue;
+            };
+            for (frame, input) in (msg.first_frame..).zip(msg.inputs) {
+                if frame >= self.remote_received_until {
+                    self.remote_inputs.insert(frame, input);
+                }
+            }
+            self.peer_received_until = self.peer_received_until.max(msg.received_until);
+        }
+        while self.remote_inputs.contains_key(&self.remote_received_until) {
+            self.remote_received_until += 1;
+        }
+    }
+
+    /// The inputs of both players in `frame`, the remote one is the latest known before it
+    fn inputs_for(&self, frame: u32) -> [PlayerInput; 2] {
+        let local = self.local_inputs[(frame - self.local_inputs_from) as usize];
+        let remote = self
+            .remote_inputs
+            .range(..=frame)
+            .next_back()
+            .map(|(_, input)| *input)
+            .unwrap_or_default();
+        match self.local {
+            PlayerType::First => [local, remote],
+            PlayerType::Second => [remote, local],
+        }
+    }
+
+    fn remote_slot(&self) -> usize {
+        match self.local {
+            PlayerType::First => 1,
+            PlayerType::Second => 0,
+        }
+    }
+
+    /// Rewinds to the first frame played with a wrong input of the other peer and simulates
+    /// everything after it again
+    fn correct_predictions(&mut self) {
+        let remote = self.remote_slot();
+        let wrong = self.frames.iter().position(|frame| {
+            frame.playing
+                && self
+                    .remote_inputs
+                    .get(&frame.number)
+                    .is_some_and(|input| *input != frame.inputs[remote])
+        });
+        let Some(wrong) = wrong else {
+            return;
+        };
+        let replay_until = self.frame;
+        let first = self
+            .frames
+            .drain(wrong..)
+            .next()
+            .expect("the frame was found");
+        first.saved.load(self.app.world_mut());
+        self.frame = first.number;
+        while self.frame < replay_until {
+            self.simulate();
+        }
+        self.rollbacks += 1;
+    }
+
+    fn simulate(&mut self) {
+        let inputs = self.inputs_for(self.frame);
+        let saved = SavedState::save(self.app.world_mut());
+        let world = self.app.world_mut();
+        if let Some(mut input) = player_input::<Player1>(world) {
+            *input = inputs[0];
+        }
+        if let Some(mut input) = player_input::<Player2>(world) {
+            *input = inputs[1];
+        }
+        self.app.update();
+
+        let state = *self.app.world().resource::<State<MatchState>>().get();
+        if state == MatchState::MainMenu {
+            // Both peers get here in the same frame and go straight into a rematch
+            self.app
+                .world_mut()
+                .resource_mut::<NextState<MatchState>>()
+                .set(MatchState::Kickoff);
+        }
+        self.frames.push_back(Frame {
+            number: self.frame,
+            saved,
+            inputs,
+            playing: state == MatchState::Playing,
+        });
+        self.frame += 1;
+    }
+
+    fn send_inputs(&mut self) {
+        let skip = (self.peer_received_until - self.local_inputs_from) as usize;
+        let msg = PeerInputs {
+            first_frame: self.peer_received_until,
+            inputs: self.local_inputs.iter().skip(skip).copied().collect(),
+            received_until: self.remote_received_until,
+        };
+        self.transport.send(encode(&msg));
+    }
+
+    /// Drops what is not needed anymore once the inputs of both peers are known
+    fn forget_confirmed(&mut self) {
+        while self
+            .frames
+            .front()
+            .is_some_and(|frame| frame.number < self.remote_received_until)
+        {
+            self.frames.pop_front();
+        }
+        let oldest_frame = self.frames.front().map_or(self.frame, |frame| frame.number);
+        let keep_from = oldest_frame.min(self.peer_received_until);
+        while self.local_inputs_from < keep_from {
+            self.local_inputs.pop_front();
+            self.local_inputs_from += 1;
+        }
+        // The newest confirmed input stays as the prediction for the frames after it
+        let prediction_from = self.remote_received_until.saturating_sub(1);
+        self.remote_inputs = self.remote_inputs.split_off(&prediction_from);
+    }
+}
+
+/// Messages on their way to one end of a `LoopbackTransport`, with the receives left until they arrive
+type Queue = Rc<RefCell<VecDeque<(u32, Vec<u8>)>>>;
+
+/// Connects two sessions in one process. Every message arrives `latency` receives after it was
+/// sent, which makes it possible to test rollback with latency but without a network.
+pub struct LoopbackTransport {
+    outgoing: Queue,
+    incoming: Queue,
+    latency: u32,
+}
+
+impl LoopbackTransport {
+    pub fn pair(latency: u32) -> (Self, Self) {
+        let a_to_b = Queue::default();
+        let b_to_a = Queue::default();
+        (
+            LoopbackTransport {
+                outgoing: a_to_b.clone(),
+                incoming: b_to_a.clone(),
+                latency,
+            },
+            LoopbackTransport {
+                outgoing: b_to_a,
+                incoming: a_to_b,
+                latency,
+            },
+        )
+    }
+}
+
+impl Transport for LoopbackTransport {
+    fn send(&mut self, bytes: Vec<u8>) {
+        self.outgoing.borrow_mut().push_back((self.latency, bytes));
+    }
+
+    fn receive(&mut self) -> Vec<Vec<u8>> {
+        let mut incoming = self.incoming.borrow_mut();
+        for (delay, _) in incoming.iter_mut() {
+            *delay = delay.saturating_sub(1);
+        }
+        let mut received = Vec::new();
+        while incoming.front().is_some_and(|(delay, _)| *delay == 0) {
+            received.extend(incoming.pop_front().map(|(_, bytes)| bytes));
+        }
+        received
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use peer::{requested_rollback_peers, RollbackPlugin, UdpTransport};
+
+/// Rollback between two windowed games over UDP
+#[cfg(not(target_arch = "wasm32"))]
+mod peer {
+    use std::{
+        io::{self, ErrorKind},
+        net::UdpSocket,
+    };
+
+    use bevy::{
+        app::{App, FixedUpdate, Plugin, PostUpdate, Startup},
+        ecs::{
+            change_detection::DetectChanges,
+            query::{With, Without},
+            resource::Resource,
+            schedule::IntoScheduleConfigs,
+            system::{NonSendMut, Query, Res, ResMut},
+            world::World,
+        },
+        log::{error, info},
+        time::{Fixed, Time},
+        transform::components::Transform,
+    };
+    use bevy_rapier3d::{dynamics::Velocity, plugin::PhysicsSet};
+
+    use super::{RollbackSession, Transport};
+    use crate::{
+        ball::Ball,
+        game_state::RemoteMatchFlow,
+        goals::PlayerType,
+        online::{apply_scoreboard, disable_local_physics, follow_server_state, LatestSnapshot},
+        player::{Player1, Player2, PlayerInput},
+        powerup::PowerUpRules,
+        simulation::{headless_app, TICK_RATE},
+    };
+
+    /// The addresses if the game was started with `--rollback <local address> <peer address>`
+    pub fn requested_rollback_peers() -> Option<(String, String)> {
+        let mut args = std::env::args().skip_while(|arg| arg != "--rollback");
+        args.next()?;
+        Some((args.next()?, args.next()?))
+    }
+
+    #[derive(Resource)]
+    struct RollbackPeers {
+        bind: String,
+        peer: String,
+    }
+
+    fn start_session(world: &mut World) {
+        let peers = world.resource::<RollbackPeers>();
+        let (bind, peer) = (peers.bind.clone(), peers.peer.clone());
+        // Both peers agree on who is who without talking first
+        let local = if bind < peer {
+            PlayerType::First
+        } else {
+            PlayerType::Second
+        };
+        match UdpTransport::bind(&bind, &peer) {
+            Ok(transport) => {
+                info!("Playing as {local:?} on {bind} against {peer}");
+                let session = RollbackSession::new(headless_app, local, transport);
+                world.insert_non_send_resource(session);
+            }
+            Err(e) => error!("Could not listen on {bind}: {e}"),
+        }
+    }
+
+    fn advance_session(
+        session: Option<NonSendMut<RollbackSession<UdpTransport>>>,
+        q_p1: Query<&PlayerInput, With<Player1>>,
+        q_p2: Query<&PlayerInput, With<Player2>>,
+        mut latest: ResMut<LatestSnapshot>,
+    ) {
+        let Some(mut session) = session else {
+            return;
+        };
+        let input = match session.local() {
+            PlayerType::First => q_p1.single(),
+            PlayerType::Second => q_p2.single(),
+        };
+        if session.advance(input.copied().unwrap_or_default()) {
+            latest.0 = Some(session.snapshot());
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn apply_bodies(
+        latest: Res<LatestSnapshot>,
+        mut q_p1: Query<(&mut Transform, &mut Velocity), (With<Player1>, Without<Player2>)>,
+        mut q_p2: Query<(&mut Transform, &mut Velocity), (With<Player2>, Without<Player1>)>,
+        mut q_ball: Query<
+            (&mut Transform, &mut Velocity),
+            (With<Ball>, Without<Player1>, Without<Player2>),
+        >,
+    ) {
+        if !latest.is_changed() {
+            return;
+        }
+        let Some(world) = latest.0.as_ref().and_then(|s| s.world.as_ref()) else {
+            return;
+        };
+        let bodies = q_p1
+            .iter_mut()
+            .map(|q| (q, world.player_1))
+            .chain(q_p2.iter_mut().map(|q| (q, world.player_2)))
+            .chain(q_ball.iter_mut().map(|q| (q, world.ball)));
+        for ((mut transform, mut velocity), body) in bodies {
+            body.apply(&mut transform, &mut velocity);
+        }
+    }
+
+    /// Plays against one other instance of the game without a server, over UDP.
+    /// The match runs in a `RollbackSession`, the window only shows it like an online match.
+    pub struct RollbackPlugin {
+        pub bind: String,
+        pub peer: String,
+    }
+
+    impl Plugin for RollbackPlugin {
+        fn build(&self, app: &mut App) {
+            app.insert_resource(RollbackPeers {
+                bind: self.bind.clone(),
+                peer: self.peer.clone(),
+            })
+            .insert_resource(RemoteMatchFlow)
+            .insert_resource(PowerUpRules::disabled())
+            .insert_resource(Time::<Fixed>::from_hz(TICK_RATE.into()))
+            .init_resource::<LatestSnapshot>()
+            .add_systems(Startup, start_session)
+            .add_systems(FixedUpdate, advance_session)
+            .add_systems(
+                PostUpdate,
+                (
+                    disable_local_physics,
+                    follow_server_state,
+                    apply_scoreboard,
+                    apply_bodies,
+                )
+                    .chain()
+                    .before(PhysicsSet::SyncBackend),
+            );
+        }
+    }
+
+    pub struct UdpTransport(UdpSocket);
+
+    impl UdpTransport {
+        pub fn bind(local: &str, peer: &str) -> io::Result<Self> {
+            let socket = UdpSocket::bind(local)?;
+            socket.connect(peer)?;
+            socket.set_nonblocking(true)?;
+            Ok(UdpTransport(socket))
+        }
+    }
+
+    impl Transport for UdpTransport {
+        fn send(&mut self, bytes: Vec<u8>) {
+            // The peer may not listen yet, the next message repeats everything anyway
+            let _ = self.0.send(&bytes);
+        }
+
+        fn receive(&mut self) -> Vec<Vec<u8>> {
+            let mut received = Vec::new();
+            let mut buffer = [0; 2048];
+            loop {
+                match self.0.recv(&mut buffer) {
+                    Ok(len) => received.push(buffer[..len].to_vec()),
+                    // Reported for an earlier message the peer was not listening for
+                    Err(e) if e.kind() == ErrorKind::ConnectionRefused => {}
+                    Err(_) => break,
+                }
+            }
+            received
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LoopbackTransport, Transport};
+
+    #[test]
+    fn receive_when_latency_passed_then_message_arrives() {
+        // given
+        let (mut a, mut b) = LoopbackTransport::pair(2);
+        a.send(vec![1, 2, 3]);
+
+        // when
+        let first = b.receive();
+        let second = b.receive();
+
+        // then
+        assert!(first.is_empty());
+        assert_eq!(second, vec![vec![1, 2, 3]]);
+    }
+}
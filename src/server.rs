@@ -0,0 +1,273 @@
+use std::{
+    collections::HashMap,
+    io::{self, ErrorKind},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    app::App,
+    log::{info, warn},
+};
+use tungstenite::{
+    handshake::{
+        server::{NoCallback, ServerHandshake},
+        MidHandshake,
+    },
+    HandshakeError, Message, WebSocket,
+};
+
+use crate::{
+    lobby::{lobby_code, ClientId, Lobby},
+    network::{decode, encode, ClientMsg, ServerMsg},
+    simulation::TICK_RATE,
+};
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+
+type Handshake = ServerHandshake<TcpStream, NoCallback>;
+
+/// Hosts any number of lobbies, each running its own match authoritatively:
+/// clients only send their `PlayerInput`, the server simulates and sends back a `Snapshot`
+/// after every tick
+pub struct Server {
+    listener: TcpListener,
+    /// Builds the app of a new match, expected to advance one tick per update like `headless_app`
+    new_match: fn() -> App,
+    clients: HashMap<ClientId, WebSocket<TcpStream>>,
+    /// Connections whose WebSocket handshake is not done yet, with the time it started.
+    /// They are retried every step, so a slow connection never holds up the running matches.
+    handshakes: Vec<(Instant, MidHandshake<Handshake>)>,
+    lobbies: Vec<Lobby>,
+    next_client_id: ClientId,
+    next_lobby_number: u32,
+    /// Messages queued during a step, sent at its end
+    outbox: Vec<(ClientId, ServerMsg)>,
+}
+
+impl Server {
+    pub fn bind(address: impl ToSocketAddrs, new_match: fn() -> App) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Server {
+            listener,
+            new_match,
+            clients: HashMap::new(),
+            handshakes: Vec::new(),
+            lobbies: Vec::new(),
+            next_client_id: 0,
+            next_lobby_number: 0,
+            outbox: Vec::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Steps the server at `TICK_RATE` forever
+    pub fn run(mut self) -> ! {
+        let tick = Duration::from_secs_f32(1.0 / TICK_RATE);
+        loop {
+            let start = Instant::now();
+            self.step();
+            thread::sleep(tick.saturating_sub(start.elapsed()));
+        }
+    }
+
+    /// Accepts new clients, handles their messages and advances every running match by one tick
+    pub fn step(&mut self) {
+        self.accept_clients();
+        self.receive_messages();
+        for lobby in self.lobbies.iter_mut() {
+            if let Some(snapshot) = lobby.step(self.new_match) {
+                for client in lobby.players.into_iter().flatten() {
+                    let msg = ServerMsg::Snapshot(Box::new(snapshot.clone()));
+                    self.outbox.push((client, msg));
+                }
+            }
+            if lobby.take_changed() {
+                for client in lobby.players.into_iter().flatten() {
+                    if let Some(info) = lobby.info_for(client) {
+                        self.outbox.push((client, ServerMsg::LobbyInfo(info)));
+                    }
+                }
+            }
+        }
+        self.flush_outbox();
+    }
+
+    fn accept_clients(&mut self) {
+        for (started, handshake) in std::mem::take(&mut self.handshakes) {
+            if started.elapsed() > HANDSHAKE_TIMEOUT {
+                warn!("WebSocket handshake timed out");
+                continue;
+            }
+            self.continue_handshake(started, handshake.handshake());
+        }
+        loop {
+            let stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    warn!("Failed to accept a connection: {e}");
+                    return;
+                }
+            };
+            if let Err(e) = stream.set_nonblocking(true) {
+                warn!("Failed to accept a connection: {e}");
+                continue;
+            }
+            self.continue_handshake(Instant::now(), tungstenite::accept(stream));
+        }
+    }
+
+    /// Adds the client once its handshake is done, or keeps it pending until more data arrives
+    fn continue_handshake(
+        &mut self,
+        started: Instant,
+        result: Result<WebSocket<TcpStream>, HandshakeError<Handshake>>,
+    ) {
+        match result {
+            Ok(socket) => {
+                let client = self.next_client_id;
+                self.next_client_id += 1;
+                info!("Client {client} connected");
+                self.clients.insert(client, socket);
+            }
+            Err(HandshakeError::Interrupted(handshake)) => {
+                self.handshakes.push((started, handshake));
+            }
+            Err(HandshakeError::Failure(e)) => warn!("WebSocket handshake failed: {e}"),
+        }
+    }
+
+    fn receive_messages(&mut self) {
+        let mut received = Vec::new();
+        let mut gone = Vec::new();
+        for (client, socket) in self.clients.iter_mut() {
+            let mut msgs = Vec::new();
+            if !receive(socket, &mut msgs) {
+                gone.push(*client);
+            }
+            received.extend(msgs.into_iter().map(|msg| (*client, msg)));
+        }
+        for (client, msg) in received {
+            self.handle(client, msg);
+        }
+        for client in gone {
+            self.disconnect(client);
+        }
+    }
+
+    fn handle(&mut self, client: ClientId, msg: ClientMsg) {
+        let lobby = self.lobby_of(client);
+        match (msg, lobby) {
+            (ClientMsg::Input { tick, input }, Some(lobby)) => {
+                self.lobbies[lobby].queue_input(client, tick, input)
+            }
+            (ClientMsg::Ready, Some(lobby)) => self.lobbies[lobby].set_ready(client),
+            (ClientMsg::LeaveLobby, Some(lobby)) => self.close_lobby(lobby, client),
+            (ClientMsg::QuickJoin, None) => {
+                match self.lobbies.iter_mut().find(|l| l.has_free_slot()) {
+                    Some(lobby) => {
+                        lobby.join(client);
+                    }
+                    None => self.create_lobby(client),
+                }
+            }
+            (ClientMsg::CreateLobby, None) => self.create_lobby(client),
+            (ClientMsg::JoinLobby { code }, None) => {
+                let code = code.to_uppercase();
+                let reason = match self.lobbies.iter_mut().find(|l| l.code == code) {
+                    Some(lobby) => match lobby.join(client) {
+                        Some(_) => return,
+                        None => format!("Lobby {code} is full"),
+                    },
+                    None => format!("There is no lobby {code}"),
+                };
+                self.outbox.push((client, ServerMsg::JoinFailed { reason }));
+            }
+            // Inputs without a lobby, or joining while already in one
+            _ => {}
+        }
+    }
+
+    fn lobby_of(&self, client: ClientId) -> Option<usize> {
+        self.lobbies
+            .iter()
+            .position(|l| l.slot_of(client).is_some())
+    }
+
+    fn create_lobby(&mut self, client: ClientId) {
+        let code = loop {
+            let code = lobby_code(self.next_lobby_number);
+            self.next_lobby_number = self.next_lobby_number.wrapping_add(1);
+            if self.lobbies.iter().all(|l| l.code != code) {
+                break code;
+            }
+        };
+        info!("Lobby {code} opened");
+        let mut lobby = Lobby::new(code);
+        lobby.join(client);
+        self.lobbies.push(lobby);
+    }
+
+    /// Ends the match of the lobby once `left` left it, the other player goes back to the
+    /// lobby selection
+    fn close_lobby(&mut self, index: usize, left: ClientId) {
+        let lobby = self.lobbies.swap_remove(index);
+        info!("Lobby {} closed", lobby.code);
+        for client in lobby.players.into_iter().flatten() {
+            if client != left {
+                self.outbox.push((client, ServerMsg::LobbyClosed));
+            }
+        }
+    }
+
+    fn disconnect(&mut self, client: ClientId) {
+        info!("Client {client} disconnected");
+        self.clients.remove(&client);
+        if let Some(lobby) = self.lobby_of(client) {
+            self.close_lobby(lobby, client);
+        }
+    }
+
+    fn flush_outbox(&mut self) {
+        let mut gone = Vec::new();
+        for (client, msg) in std::mem::take(&mut self.outbox) {
+            if let Some(socket) = self.clients.get_mut(&client) {
+                if !send(socket, &msg) {
+                    gone.push(client);
+                }
+            }
+        }
+        for client in gone {
+            self.disconnect(client);
+        }
+    }
+}
+
+/// Returns false once the client is gone
+fn send(socket: &mut WebSocket<TcpStream>, msg: &ServerMsg) -> bool {
+    match socket.send(Message::binary(encode(msg))) {
+        Ok(()) => true,
+        // The message is queued and flushed with the next send
+        Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => true,
+        Err(_) => false,
+    }
+}
+
+/// Reads everything the client sent so far, returns false once the client is gone
+fn receive(socket: &mut WebSocket<TcpStream>, msgs: &mut Vec<ClientMsg>) -> bool {
+    loop {
+        match socket.read() {
+            Ok(Message::Binary(bytes)) => msgs.extend(decode::<ClientMsg>(&bytes)),
+            Ok(Message::Close(_)) => return false,
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => return true,
+            Err(_) => return false,
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::time::Duration;
+
+use bevy::{
+    app::{App, PluginGroup, PluginGroupBuilder},
+    state::app::StatesPlugin,
+    time::TimeUpdateStrategy,
+    transform::TransformPlugin,
+    MinimalPlugins,
+};
+use bevy_rapier3d::plugin::{NoUserData, RapierPhysicsPlugin, TimestepMode};
+
+use crate::{
+    ai::AiPlugin, arena::ArenaPlugin, arena_features::ArenaFeaturesPlugin, ball::BallPlugin,
+    game_state::GameStatePlugin, goals::GoalPlugin, handicap::HandicapPlugin, kick::KickPlugin,
+    player::PlayerPlugin, points::PointsPlugin, powerup::PowerUpPlugin, sprint::StatePlugin,
+    tackle::TacklePlugin,
+};
+
+pub const TICK_RATE: f32 = 60.0;
+
+/// Everything needed to play a match, without rendering or local input. Computer players are
+/// part of it, so they also play in headless matches.
+pub struct SlatchhammerGamePlugin;
+
+impl PluginGroup for SlatchhammerGamePlugin {
+    fn build(self) -> PluginGroupBuilder {
+        PluginGroupBuilder::start::<Self>()
+            .add(GameStatePlugin)
+            .add(ArenaPlugin)
+            .add(ArenaFeaturesPlugin)
+            .add(GoalPlugin)
+            .add(BallPlugin)
+            .add(PlayerPlugin)
+            .add(PointsPlugin)
+            .add(StatePlugin)
+            .add(KickPlugin)
+            .add(TacklePlugin)
+            .add(PowerUpPlugin)
+            .add(HandicapPlugin)
+            .add(AiPlugin)
+    }
+}
+
+/// Builds the game without window, rendering or input. Every `update` advances the
+/// simulation by exactly one tick, so runs with the same inputs end up in the same state.
+pub fn headless_app() -> App {
+    let tick = Duration::from_secs_f32(1.0 / TICK_RATE);
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        TransformPlugin,
+        StatesPlugin,
+        RapierPhysicsPlugin::<NoUserData>::default(),
+        SlatchhammerGamePlugin,
+    ))
+    .insert_resource(TimeUpdateStrategy::ManualDuration(tick))
+    .insert_resource(TimestepMode::Fixed {
+        dt: tick.as_secs_f32(),
+        substeps: 1,
+    });
+    app.finish();
+    app.cleanup();
+    app
+}
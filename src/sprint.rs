@@ -3,22 +3,67 @@ use bevy::{
     ecs::{
         component::Component,
         entity::Entity,
-        event::{Event, EventReader},
-        system::Query,
+        event::{Event, EventReader, EventWriter},
+        resource::Resource,
+        schedule::IntoScheduleConfigs,
+        system::{Query, Res},
     },
+    state::{condition::in_state, state::OnEnter},
+    time::Time,
 };
 
-#[derive(Component)]
+use crate::{
+    game_state::{GameplaySet, MatchState},
+    player::PlayerInput,
+};
+
+#[derive(Component, Clone)]
 pub struct SprintState {
     duration: f32,
+    /// Sprint seconds of a full bar
+    capacity: f32,
+    /// Ran out of sprint and has to wait for `SprintRules::exhaustion_threshold`
+    exhausted: bool,
+    /// Seconds since the last sprint
+    idle: f32,
 }
 
-const SPRINT_DURATION: f32 = 10.0;
+pub const SPRINT_DURATION: f32 = 10.0;
 
 impl Default for SprintState {
     fn default() -> Self {
-        SprintState {
-            duration: SPRINT_DURATION,
+        SprintState::with_capacity(SPRINT_DURATION)
+    }
+}
+
+/// How much sprint comes back after a goal
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GoalRefill {
+    Keep,
+    Full,
+    /// Seconds of sprint added
+    Amount(f32),
+}
+
+/// How sprint regenerates and what happens when it runs out
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct SprintRules {
+    /// Seconds without sprinting before the bar starts to refill
+    pub regeneration_delay: f32,
+    /// Sprint seconds regained per second
+    pub regeneration_rate: f32,
+    /// An empty bar has to refill to this many seconds before sprinting works again
+    pub exhaustion_threshold: f32,
+    pub goal_refill: GoalRefill,
+}
+
+impl Default for SprintRules {
+    fn default() -> Self {
+        SprintRules {
+            regeneration_delay: 1.5,
+            regeneration_rate: 0.5,
+            exhaustion_threshold: 3.0,
+            goal_refill: GoalRefill::Full,
         }
     }
 }
@@ -27,27 +72,65 @@ impl Default for SprintState {
 pub struct ShowBars;
 
 impl SprintState {
+    /// A full bar of `capacity` sprint seconds
+    pub fn with_capacity(capacity: f32) -> Self {
+        SprintState {
+            duration: capacity,
+            capacity,
+            exhausted: false,
+            idle: 0.0,
+        }
+    }
+
     pub fn reset(&mut self) {
-        self.duration = SPRINT_DURATION;
+        self.duration = self.capacity;
+        self.exhausted = false;
     }
 
-    pub fn resupply(&mut self, value: f32) {
-        if self.duration + value > SPRINT_DURATION {
-            self.duration = SPRINT_DURATION;
+    /// Adds sprint up to the maximum, an exhausted player recovers once above `threshold`
+    pub fn resupply(&mut self, value: f32, threshold: f32) {
+        self.duration = (self.duration + value).min(self.capacity);
+        if self.exhausted && self.duration >= threshold {
+            self.exhausted = false;
         }
-        self.duration += value;
     }
 
     pub fn reduce(&mut self, value: f32) {
-        if value > self.duration {
+        self.idle = 0.0;
+        if value >= self.duration {
             self.duration = 0.0;
+            self.exhausted = true;
         } else {
             self.duration -= value;
         }
     }
 
+    /// Counts the time without sprinting and returns how much sprint regenerates in it
+    pub fn rest(&mut self, dt: f32, rules: &SprintRules) -> f32 {
+        self.idle += dt;
+        if self.idle >= rules.regeneration_delay && self.duration < self.capacity {
+            rules.regeneration_rate * dt
+        } else {
+            0.0
+        }
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    /// Part of the full bar that is left, from 0 to 1
+    pub fn fraction(&self) -> f32 {
+        self.duration / self.capacity
+    }
+
+    /// Seconds of sprint left
+    pub fn remaining(&self) -> f32 {
+        self.duration
+    }
+
     pub fn is_available(&self) -> bool {
-        self.duration > 0.0
+        self.duration > 0.0 && !self.exhausted
     }
 }
 
@@ -57,7 +140,29 @@ pub struct ApplySprintEvent {
     pub target: Entity,
 }
 
+/// Players who are not sprinting get sprint back after the regeneration delay
+fn regenerate_sprint(
+    time: Res<Time>,
+    rules: Res<SprintRules>,
+    mut query: Query<(Entity, &PlayerInput, &mut SprintState)>,
+    mut event_writer: EventWriter<ApplySprintEvent>,
+) {
+    for (entity, input, mut state) in query.iter_mut() {
+        if input.sprint && state.is_available() {
+            continue;
+        }
+        let amount = state.rest(time.delta_secs(), &rules);
+        if amount > 0.0 {
+            event_writer.write(ApplySprintEvent {
+                amount,
+                target: entity,
+            });
+        }
+    }
+}
+
 fn apply_sprint_events(
+    rules: Res<SprintRules>,
     mut event_reader: EventReader<ApplySprintEvent>,
     mut query: Query<&mut SprintState>,
 ) {
@@ -66,8 +171,18 @@ fn apply_sprint_events(
             continue;
         };
         match ev.amount > 0. {
-            true => state.resupply(ev.amount),
-            false => state.reduce(ev.amount * -1.0),
+            true => state.resupply(ev.amount, rules.exhaustion_threshold),
+            false => state.reduce(-ev.amount),
+        }
+    }
+}
+
+fn refill_after_goal(rules: Res<SprintRules>, mut query: Query<&mut SprintState>) {
+    for mut state in query.iter_mut() {
+        match rules.goal_refill {
+            GoalRefill::Keep => {}
+            GoalRefill::Full => state.reset(),
+            GoalRefill::Amount(amount) => state.resupply(amount, rules.exhaustion_threshold),
         }
     }
 }
@@ -77,13 +192,30 @@ pub struct StatePlugin;
 impl Plugin for StatePlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.add_event::<ApplySprintEvent>()
-            .add_systems(Update, apply_sprint_events);
+            .init_resource::<SprintRules>()
+            .add_systems(OnEnter(MatchState::GoalScored), refill_after_goal)
+            .add_systems(
+                Update,
+                (
+                    regenerate_sprint
+                        .run_if(in_state(MatchState::Playing))
+                        .in_set(GameplaySet::Movement),
+                    apply_sprint_events.in_set(GameplaySet::Resolve),
+                ),
+            );
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::sprint::SprintState;
+    use crate::sprint::{SprintRules, SprintState, SPRINT_DURATION};
+
+    fn sprint_with(duration: f32) -> SprintState {
+        SprintState {
+            duration,
+            ..Default::default()
+        }
+    }
 
     #[test]
     fn is_available_when_called_then_is_true() {
@@ -93,11 +225,10 @@ mod tests {
     #[test]
     fn resupply_when_called_adds_duration() {
         // given
-        let mut sprint = SprintState::default();
-        sprint.duration = 0.0;
+        let mut sprint = sprint_with(0.0);
 
         // when
-        sprint.resupply(1.0);
+        sprint.resupply(1.0, 3.0);
 
         // then
         assert_eq!(sprint.duration as i32, 1);
@@ -106,8 +237,7 @@ mod tests {
     #[test]
     fn is_avaiable_when_called_after_depletion_then_false() {
         // given
-        let mut sprint = SprintState::default();
-        sprint.duration = 0.0;
+        let sprint = sprint_with(0.0);
 
         // when // then
         assert!(!sprint.is_available());
@@ -115,9 +245,74 @@ mod tests {
 
     #[test]
     fn reduce_when_called_reduces_duration() {
-        let mut sprint = SprintState::default();
+        let mut sprint = sprint_with(3.0);
         sprint.reduce(2.0);
 
         assert_eq!(sprint.duration as i32, 1);
     }
+
+    #[test]
+    fn resupply_when_almost_full_then_clamps_to_maximum() {
+        // given
+        let mut sprint = sprint_with(SPRINT_DURATION - 1.0);
+
+        // when
+        sprint.resupply(5.0, 3.0);
+
+        // then
+        assert_eq!(sprint.duration, SPRINT_DURATION);
+    }
+
+    #[test]
+    fn reduce_when_emptied_then_exhausted_until_threshold() {
+        // given
+        let mut sprint = sprint_with(1.0);
+        sprint.reduce(2.0);
+
+        // when
+        sprint.resupply(2.0, 3.0);
+        let below_threshold = sprint.is_available();
+        sprint.resupply(1.0, 3.0);
+
+        // then
+        assert!(!below_threshold);
+        assert!(sprint.is_available());
+    }
+
+    #[test]
+    fn rest_when_within_delay_then_nothing_regenerates() {
+        // given
+        let rules = SprintRules::default();
+        let mut sprint = sprint_with(5.0);
+        sprint.reduce(1.0);
+
+        // when
+        let early = sprint.rest(rules.regeneration_delay * 0.5, &rules);
+        let late = sprint.rest(rules.regeneration_delay, &rules);
+
+        // then
+        assert_eq!(early, 0.0);
+        assert!(late > 0.0);
+    }
+
+    #[test]
+    fn rest_when_full_then_nothing_regenerates() {
+        let rules = SprintRules::default();
+        let mut sprint = SprintState::default();
+        assert_eq!(sprint.rest(10.0, &rules), 0.0);
+    }
+
+    #[test]
+    fn resupply_when_capacity_is_smaller_then_fills_only_that_bar() {
+        // given
+        let mut sprint = SprintState::with_capacity(4.0);
+        sprint.reduce(2.0);
+
+        // when
+        sprint.resupply(5.0, 0.0);
+
+        // then
+        assert_eq!(sprint.remaining(), 4.0);
+        assert_eq!(sprint.fraction(), 1.0);
+    }
 }
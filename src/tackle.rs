@@ -0,0 +1,254 @@
+use bevy::{
+    app::{Plugin, Update},
+    ecs::{
+        component::Component,
+        event::EventReader,
+        query::{Or, With},
+        resource::Resource,
+        schedule::IntoScheduleConfigs,
+        system::{Query, Res},
+    },
+    math::{vec3, Vec3},
+    state::condition::in_state,
+    time::Time,
+    transform::components::Transform,
+};
+use bevy_rapier3d::{dynamics::Velocity, pipeline::CollisionEvent};
+
+use crate::{
+    game_state::{GameplaySet, MatchState},
+    player::{apply_movement, Player1, Player2, PlayerInput},
+};
+
+/// How far, how hard and how often players tackle
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct TackleRules {
+    pub dash_speed: f32,
+    /// Seconds a dash lasts unless it hits the opponent earlier
+    pub dash_time: f32,
+    /// Speed the opponent is pushed away with
+    pub knockback: f32,
+    /// Seconds a tackled opponent can't move
+    pub stun_time: f32,
+    /// Seconds a player who dashed into nothing can't move
+    pub miss_stun_time: f32,
+    /// Seconds from the start of one dash to the next
+    pub cooldown: f32,
+}
+
+impl Default for TackleRules {
+    fn default() -> Self {
+        TackleRules {
+            dash_speed: 25.0,
+            dash_time: 0.2,
+            knockback: 15.0,
+            stun_time: 1.0,
+            miss_stun_time: 0.6,
+            cooldown: 2.0,
+        }
+    }
+}
+
+/// Dash, stun and cooldown of a player's tackle
+#[derive(Component, Clone, Default, Debug, PartialEq)]
+pub struct TackleState {
+    /// Flat direction of the running dash
+    direction: Vec3,
+    dash: f32,
+    stun: f32,
+    cooldown: f32,
+    /// The tackle was held last frame, a dash starts only when it is pressed again
+    held: bool,
+}
+
+impl TackleState {
+    /// Starts a dash towards `facing` when the tackle is pressed and the cooldown is over.
+    /// A dash running out without hitting anyone stuns the dashing player.
+    pub fn update(&mut self, tackle: bool, facing: Vec3, dt: f32, rules: &TackleRules) {
+        let pressed = tackle && !self.held;
+        self.held = tackle;
+        self.cooldown = (self.cooldown - dt).max(0.0);
+        self.stun = (self.stun - dt).max(0.0);
+        if self.dash > 0.0 {
+            self.dash = (self.dash - dt).max(0.0);
+            if self.dash == 0.0 {
+                self.stun = rules.miss_stun_time;
+            }
+        } else if pressed && self.cooldown == 0.0 && self.stun == 0.0 {
+            self.direction = vec3(facing.x, 0.0, facing.z).normalize_or_zero();
+            self.dash = rules.dash_time;
+            self.cooldown = rules.cooldown;
+        }
+    }
+
+    /// The dash reached the opponent, so it ends without stunning the dashing player
+    pub fn hit(&mut self) {
+        self.dash = 0.0;
+    }
+
+    pub fn stun(&mut self, rules: &TackleRules) {
+        self.stun_for(rules.stun_time);
+    }
+
+    /// Ends a running dash and keeps the player from moving for `seconds`
+    pub fn stun_for(&mut self, seconds: f32) {
+        self.dash = 0.0;
+        self.stun = self.stun.max(seconds);
+    }
+
+    pub fn is_dashing(&self) -> bool {
+        self.dash > 0.0
+    }
+
+    pub fn is_stunned(&self) -> bool {
+        self.stun > 0.0
+    }
+
+    /// Dashing and stunned players ignore their `PlayerInput`
+    pub fn overrides_input(&self) -> bool {
+        self.is_dashing() || self.is_stunned()
+    }
+}
+
+fn dash(
+    time: Res<Time>,
+    rules: Res<TackleRules>,
+    mut query: Query<(&PlayerInput, &mut TackleState, &Transform, &mut Velocity)>,
+) {
+    for (input, mut tackle, transform, mut velocity) in query.iter_mut() {
+        // `apply_movement` turns the player's +z towards where it moves
+        let facing = transform.rotation * Vec3::Z;
+        tackle.update(input.tackle, facing, time.delta_secs(), &rules);
+        if tackle.is_dashing() {
+            let dash = tackle.direction * rules.dash_speed;
+            velocity.linvel = vec3(dash.x, velocity.linvel.y, dash.z);
+        }
+    }
+}
+
+fn knock_down(
+    attacker: &mut TackleState,
+    victim: &mut TackleState,
+    victim_velocity: &mut Velocity,
+    rules: &TackleRules,
+) {
+    let knockback = attacker.direction * rules.knockback;
+    attacker.hit();
+    victim.stun(rules);
+    victim_velocity.linvel = vec3(knockback.x, victim_velocity.linvel.y, knockback.z);
+}
+
+/// Dashing players who run into the opponent knock it back and stun it
+#[allow(clippy::type_complexity)]
+fn resolve_tackles(
+    rules: Res<TackleRules>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut q_players: Query<(&mut TackleState, &mut Velocity), Or<(With<Player1>, With<Player2>)>>,
+) {
+    for ev in collision_events.read() {
+        let CollisionEvent::Started(first, second, _) = ev else {
+            continue;
+        };
+        let Ok([(mut a, mut a_velocity), (mut b, mut b_velocity)]) =
+            q_players.get_many_mut([*first, *second])
+        else {
+            continue;
+        };
+        // Both dashing into each other knocks both down
+        let (a_dashing, b_dashing) = (a.is_dashing(), b.is_dashing());
+        if a_dashing {
+            knock_down(&mut a, &mut b, &mut b_velocity, &rules);
+        }
+        if b_dashing {
+            knock_down(&mut b, &mut a, &mut a_velocity, &rules);
+        }
+    }
+}
+
+/// Lets players dash into each other, knocking back and stunning the opponent
+pub struct TacklePlugin;
+
+impl Plugin for TacklePlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<TackleRules>().add_systems(
+            Update,
+            (
+                dash.before(apply_movement).in_set(GameplaySet::Movement),
+                resolve_tackles.in_set(GameplaySet::Resolve),
+            )
+                .run_if(in_state(MatchState::Playing)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::Vec3;
+
+    use super::{TackleRules, TackleState};
+
+    #[test]
+    fn update_when_pressed_then_dashes_until_dash_time_and_stuns_on_miss() {
+        // given
+        let rules = TackleRules::default();
+        let mut tackle = TackleState::default();
+
+        // when
+        tackle.update(true, Vec3::X, 0.0, &rules);
+        let dashing = tackle.is_dashing();
+        tackle.update(true, Vec3::X, rules.dash_time, &rules);
+
+        // then
+        assert!(dashing);
+        assert!(!tackle.is_dashing());
+        assert!(tackle.is_stunned());
+    }
+
+    #[test]
+    fn update_when_dash_hit_then_not_stunned_and_cooling_down() {
+        // given
+        let rules = TackleRules::default();
+        let mut tackle = TackleState::default();
+        tackle.update(true, Vec3::X, 0.0, &rules);
+
+        // when
+        tackle.hit();
+        tackle.update(false, Vec3::X, 0.1, &rules);
+        tackle.update(true, Vec3::X, 0.1, &rules);
+
+        // then
+        assert!(!tackle.overrides_input());
+    }
+
+    #[test]
+    fn update_when_held_after_cooldown_then_no_second_dash() {
+        // given
+        let rules = TackleRules::default();
+        let mut tackle = TackleState::default();
+        tackle.update(true, Vec3::X, 0.0, &rules);
+        tackle.hit();
+
+        // when
+        tackle.update(true, Vec3::X, rules.cooldown, &rules);
+
+        // then
+        assert!(!tackle.is_dashing());
+    }
+
+    #[test]
+    fn stun_when_dashing_then_dash_ends_and_input_ignored() {
+        // given
+        let rules = TackleRules::default();
+        let mut tackle = TackleState::default();
+        tackle.update(true, Vec3::X, 0.0, &rules);
+
+        // when
+        tackle.stun(&rules);
+
+        // then
+        assert!(!tackle.is_dashing());
+        assert!(tackle.overrides_input());
+        tackle.update(false, Vec3::X, rules.stun_time, &rules);
+        assert!(!tackle.overrides_input());
+    }
+}
@@ -0,0 +1,372 @@
+use bevy::prelude::{BackgroundColor, BorderRadius, Display, Node};
+use bevy::{
+    app::{Plugin, Update},
+    ecs::{
+        component::Component,
+        query::{With, Without},
+        resource::Resource,
+        schedule::IntoScheduleConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    input::touch::Touches,
+    math::{vec2, Vec2},
+    state::{state::OnEnter, state_scoped::StateScoped},
+    ui::{PositionType, Val},
+    window::{PrimaryWindow, Window},
+};
+
+use crate::{
+    colors::GREY,
+    controls::stick_movement,
+    game_state::{GameplaySet, InMatch},
+    player::PlayerInput,
+};
+
+/// How far a finger has to move from where it went down for full speed
+const STICK_RADIUS: f32 = 64.0;
+const BUTTON_RADIUS: f32 = 48.0;
+
+/// A finger moving a player, anchored where it first touched the screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TouchStick {
+    touch: u64,
+    origin: Vec2,
+    position: Vec2,
+}
+
+/// The fingers on one half of the screen
+#[derive(Default, Debug)]
+struct TouchHalf {
+    stick: Option<TouchStick>,
+    sprint: Option<u64>,
+    kick: Option<u64>,
+    tackle: Option<u64>,
+}
+
+/// Virtual joysticks and sprint buttons, the left half of the screen controls player 1 and the
+/// right half player 2
+#[derive(Resource, Default, Debug)]
+pub struct TouchControls {
+    halves: [TouchHalf; 2],
+    /// The screen was touched at least once, so the controls are drawn
+    pub used: bool,
+}
+
+pub(crate) fn screen_half(position: Vec2, screen: Vec2) -> usize {
+    if position.x < screen.x / 2.0 {
+        0
+    } else {
+        1
+    }
+}
+
+/// Near the middle of the screen, under the inner thumb of each player
+pub fn sprint_button_center(half: usize, screen: Vec2) -> Vec2 {
+    let x = if half == 0 { 0.4 } else { 0.6 };
+    vec2(screen.x * x, screen.y * 0.85)
+}
+
+/// Above the sprint button
+pub fn kick_button_center(half: usize, screen: Vec2) -> Vec2 {
+    let x = if half == 0 { 0.4 } else { 0.6 };
+    vec2(screen.x * x, screen.y * 0.65)
+}
+
+/// Above the kick button
+pub fn tackle_button_center(half: usize, screen: Vec2) -> Vec2 {
+    let x = if half == 0 { 0.4 } else { 0.6 };
+    vec2(screen.x * x, screen.y * 0.45)
+}
+
+impl TouchControls {
+    /// A new finger presses the sprint, kick or tackle button it is on, or becomes the stick of its half
+    /// if that has none yet
+    pub fn press(&mut self, touch: u64, position: Vec2, screen: Vec2) {
+        self.used = true;
+        let index = screen_half(position, screen);
+        let half = &mut self.halves[index];
+        let on_button = |center: fn(usize, Vec2) -> Vec2| {
+            position.distance(center(index, screen)) <= BUTTON_RADIUS
+        };
+        if on_button(sprint_button_center) {
+            half.sprint = Some(touch);
+        } else if on_button(kick_button_center) {
+            half.kick = Some(touch);
+        } else if on_button(tackle_button_center) {
+            half.tackle = Some(touch);
+        } else if half.stick.is_none() {
+            half.stick = Some(TouchStick {
+                touch,
+                origin: position,
+                position,
+            });
+        }
+    }
+
+    /// Sticks follow their finger even when it crosses into the other half
+    pub fn drag(&mut self, touch: u64, position: Vec2) {
+        for half in &mut self.halves {
+            if let Some(stick) = half.stick.as_mut().filter(|s| s.touch == touch) {
+                stick.position = position;
+            }
+        }
+    }
+
+    pub fn release(&mut self, touch: u64) {
+        for half in &mut self.halves {
+            if half.stick.is_some_and(|s| s.touch == touch) {
+                half.stick = None;
+            }
+            if half.sprint == Some(touch) {
+                half.sprint = None;
+            }
+            if half.kick == Some(touch) {
+                half.kick = None;
+            }
+            if half.tackle == Some(touch) {
+                half.tackle = None;
+            }
+        }
+    }
+
+    /// The input of the half's player, `None` while no finger is on that half
+    pub fn input(&self, half: usize) -> Option<PlayerInput> {
+        let TouchHalf {
+            stick,
+            sprint,
+            kick,
+            tackle,
+        } = &self.halves[half];
+        if [sprint, kick, tackle].iter().all(|button| button.is_none()) && stick.is_none() {
+            return None;
+        }
+        let movement = stick
+            .map(|s| {
+                let offset = (s.position - s.origin) / STICK_RADIUS;
+                stick_movement(vec2(offset.x, -offset.y))
+            })
+            .unwrap_or_default();
+        Some(PlayerInput {
+            movement,
+            sprint: sprint.is_some(),
+            kick: kick.is_some(),
+            tackle: tackle.is_some(),
+        })
+    }
+}
+
+pub(crate) fn read_touches(
+    touches: Res<Touches>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut controls: ResMut<TouchControls>,
+) {
+    let Ok(window) = windows.single() else {
+        return;
+    };
+    for touch in touches.iter_just_pressed() {
+        controls.press(touch.id(), touch.position(), window.size());
+    }
+    for touch in touches.iter() {
+        controls.drag(touch.id(), touch.position());
+    }
+    for touch in touches
+        .iter_just_released()
+        .chain(touches.iter_just_canceled())
+    {
+        controls.release(touch.id());
+    }
+}
+
+#[derive(Component)]
+struct StickBase(usize);
+
+#[derive(Component)]
+struct StickKnob(usize);
+
+#[derive(Component)]
+struct TouchButton {
+    half: usize,
+    center: fn(usize, Vec2) -> Vec2,
+}
+
+fn circle(radius: f32) -> (Node, BackgroundColor, BorderRadius) {
+    (
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Px(radius * 2.0),
+            height: Val::Px(radius * 2.0),
+            display: Display::None,
+            ..Default::default()
+        },
+        BackgroundColor(GREY),
+        BorderRadius::MAX,
+    )
+}
+
+fn spawn_touch_controls(mut commands: Commands) {
+    for half in 0..2 {
+        commands.spawn((StickBase(half), circle(STICK_RADIUS), StateScoped(InMatch)));
+        commands.spawn((
+            StickKnob(half),
+            circle(STICK_RADIUS / 2.0),
+            StateScoped(InMatch),
+        ));
+        for center in [
+            sprint_button_center,
+            kick_button_center,
+            tackle_button_center,
+        ] {
+            commands.spawn((
+                TouchButton { half, center },
+                circle(BUTTON_RADIUS),
+                StateScoped(InMatch),
+            ));
+        }
+    }
+}
+
+fn place(node: &mut Node, center: Option<Vec2>, radius: f32) {
+    match center {
+        Some(center) => {
+            node.display = Display::Flex;
+            node.left = Val::Px(center.x - radius);
+            node.top = Val::Px(center.y - radius);
+        }
+        None => node.display = Display::None,
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn show_touch_controls(
+    controls: Res<TouchControls>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut q_bases: Query<(&StickBase, &mut Node)>,
+    mut q_knobs: Query<(&StickKnob, &mut Node), Without<StickBase>>,
+    mut q_buttons: Query<(&TouchButton, &mut Node), (Without<StickBase>, Without<StickKnob>)>,
+) {
+    let Ok(window) = windows.single() else {
+        return;
+    };
+    for (base, mut node) in &mut q_bases {
+        let origin = controls.halves[base.0].stick.map(|s| s.origin);
+        place(&mut node, origin, STICK_RADIUS);
+    }
+    for (knob, mut node) in &mut q_knobs {
+        let position = controls.halves[knob.0].stick.map(|s| {
+            let offset = (s.position - s.origin).clamp_length_max(STICK_RADIUS);
+            s.origin + offset
+        });
+        place(&mut node, position, STICK_RADIUS / 2.0);
+    }
+    for (button, mut node) in &mut q_buttons {
+        let center = controls
+            .used
+            .then(|| (button.center)(button.half, window.size()));
+        place(&mut node, center, BUTTON_RADIUS);
+    }
+}
+
+/// Reads fingers on the screen into `TouchControls` and draws the sticks and sprint buttons
+pub struct TouchControlsPlugin;
+
+impl Plugin for TouchControlsPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<TouchControls>()
+            .add_systems(OnEnter(InMatch), spawn_touch_controls)
+            .add_systems(
+                Update,
+                (read_touches.in_set(GameplaySet::Input), show_touch_controls),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{vec2, Vec2, Vec3};
+
+    use super::{kick_button_center, sprint_button_center, TouchControls, STICK_RADIUS};
+
+    const SCREEN: Vec2 = vec2(1000.0, 600.0);
+
+    #[test]
+    fn press_when_two_fingers_on_both_halves_then_each_player_gets_a_stick() {
+        // given
+        let mut controls = TouchControls::default();
+        controls.press(1, vec2(200.0, 300.0), SCREEN);
+        controls.press(2, vec2(800.0, 300.0), SCREEN);
+
+        // when
+        controls.drag(1, vec2(200.0, 300.0 - STICK_RADIUS));
+        controls.drag(2, vec2(800.0 + STICK_RADIUS / 2.0, 300.0));
+
+        // then
+        let input_1 = controls.input(0).unwrap();
+        let input_2 = controls.input(1).unwrap();
+        assert_eq!(input_1.movement, Vec3::NEG_Z);
+        assert_eq!(input_2.movement, Vec3::X * 0.5);
+        assert!(!input_1.sprint && !input_2.sprint);
+    }
+
+    #[test]
+    fn press_when_finger_on_sprint_button_then_sprints_without_moving_the_stick() {
+        // given
+        let mut controls = TouchControls::default();
+        controls.press(1, vec2(200.0, 300.0), SCREEN);
+        controls.drag(1, vec2(250.0, 300.0));
+
+        // when
+        controls.press(2, sprint_button_center(0, SCREEN), SCREEN);
+
+        // then
+        let input = controls.input(0).unwrap();
+        assert!(input.sprint);
+        assert!(input.movement.x > 0.0);
+    }
+
+    #[test]
+    fn release_when_stick_finger_lifts_then_other_fingers_stay() {
+        // given
+        let mut controls = TouchControls::default();
+        controls.press(1, vec2(200.0, 300.0), SCREEN);
+        controls.press(2, sprint_button_center(0, SCREEN), SCREEN);
+        controls.press(3, vec2(800.0, 300.0), SCREEN);
+
+        // when
+        controls.release(1);
+
+        // then
+        assert_eq!(controls.input(0).unwrap().movement, Vec3::ZERO);
+        assert!(controls.input(0).unwrap().sprint);
+        assert!(controls.input(1).is_some());
+    }
+
+    #[test]
+    fn press_when_half_already_has_a_stick_then_second_finger_ignored() {
+        // given
+        let mut controls = TouchControls::default();
+        controls.press(1, vec2(200.0, 300.0), SCREEN);
+
+        // when
+        controls.press(2, vec2(100.0, 100.0), SCREEN);
+        controls.drag(2, vec2(0.0, 100.0));
+        controls.release(2);
+
+        // then
+        assert_eq!(controls.input(0).unwrap().movement, Vec3::ZERO);
+    }
+
+    #[test]
+    fn press_when_finger_on_kick_button_then_kicks_until_released() {
+        // given
+        let mut controls = TouchControls::default();
+        controls.press(1, kick_button_center(1, SCREEN), SCREEN);
+
+        // when
+        let held = controls.input(1).unwrap();
+        controls.release(1);
+
+        // then
+        assert!(held.kick && !held.sprint);
+        assert_eq!(controls.input(1), None);
+    }
+}
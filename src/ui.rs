@@ -1,20 +1,23 @@
 use crate::colors::{GREEN, ORANGE, WHITE};
 use crate::{
     constants::DISPLAY_DESPAWN_TIME,
-    game_state::{EndState, GameEndEvent, GameTime},
+    game_state::{EndState, GameEndEvent, GameTime, InMatch, MatchState, PhaseTimer},
+    match_rules::{MatchRules, Rounds},
     points::Points,
 };
-use bevy::prelude::{Display, Node, Text, TextColor, TextFont, Without};
+use bevy::prelude::{Bundle, Display, Node, Text, TextColor, TextFont, Without};
 use bevy::{
-    app::{Plugin, Startup, Update},
+    app::{Plugin, Update},
     ecs::{
         component::Component,
         entity::Entity,
         event::EventReader,
         query::With,
+        schedule::IntoScheduleConfigs,
         system::{Commands, Query, Res},
     },
     prelude::{Deref, DerefMut},
+    state::{condition::in_state, state::OnEnter, state_scoped::StateScoped},
     time::{Time, Timer, TimerMode},
     ui::{AlignItems, JustifyContent, PositionType, UiRect, Val},
 };
@@ -35,6 +38,49 @@ struct TimeText;
 #[derive(Component)]
 struct TimeDisplayRoot;
 
+#[derive(Component)]
+struct CountdownText;
+
+fn centered_text(text: &str) -> impl Bundle {
+    (
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.),
+            height: Val::Percent(100.),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            ..Default::default()
+        },
+        Text(text.into()),
+        TextColor(WHITE),
+        TextFont {
+            font_size: 96.0,
+            ..Default::default()
+        },
+    )
+}
+
+fn setup_kickoff_countdown(mut commands: Commands) {
+    commands.spawn((
+        CountdownText,
+        centered_text(""),
+        StateScoped(MatchState::Kickoff),
+    ));
+}
+
+fn update_kickoff_countdown(
+    timer: Res<PhaseTimer>,
+    mut query: Query<&mut Text, With<CountdownText>>,
+) {
+    for mut text in &mut query {
+        text.0 = format!("{}", timer.seconds_left());
+    }
+}
+
+fn setup_goal_celebration(mut commands: Commands) {
+    commands.spawn((centered_text("GOAL!"), StateScoped(MatchState::GoalScored)));
+}
+
 fn setup_time_ui(mut commands: Commands) {
     commands.spawn((
         TimeDisplayRoot,
@@ -53,6 +99,7 @@ fn setup_time_ui(mut commands: Commands) {
         TextColor(GREEN),
         TimeText,
         Text("Time Left: N/A".into()),
+        StateScoped(InMatch),
     ));
 }
 
@@ -77,6 +124,7 @@ fn spawn_game_end_notification(
         commands.spawn((
             MainUi,
             DisplayTime(Timer::from_seconds(DISPLAY_DESPAWN_TIME, TimerMode::Once)),
+            StateScoped(MatchState::MatchOver),
             (Node {
                 position_type: PositionType::Absolute,
                 width: Val::Percent(100.),
@@ -132,6 +180,7 @@ fn setup_points_ui(mut commands: Commands) {
             ..Default::default()
         },
         TextColor(GREEN),
+        StateScoped(InMatch),
     ));
     commands.spawn((
         PointDisplayRoot,
@@ -149,6 +198,7 @@ fn setup_points_ui(mut commands: Commands) {
             ..Default::default()
         },
         TextColor(ORANGE),
+        StateScoped(InMatch),
     ));
 }
 
@@ -157,19 +207,47 @@ fn point_text_update_system(
     mut q_p1: Query<&mut Text, (With<PointsText1>, Without<PointsText2>)>,
     mut q_p2: Query<&mut Text, (With<PointsText2>, Without<PointsText1>)>,
 ) {
-    let points = q_points.single().unwrap();
-    if let Ok(mut text) =q_p1.single_mut() {
+    let Ok(points) = q_points.single() else {
+        return;
+    };
+    if let Ok(mut text) = q_p1.single_mut() {
         text.0 = format!("{}", points.player_1);
     }
-    if let Ok(mut text) =q_p2.single_mut() {
+    if let Ok(mut text) = q_p2.single_mut() {
         text.0 = format!("{}", points.player_2);
     }
 }
 
-fn display_game_time(q_timer: Query<&mut GameTime>, mut query: Query<&mut Text, With<TimeText>>) {
-    if let Ok(game_timer) = q_timer.single() {
+/// The clock counts down to the time limit, or up without one. Rounds and the score limit
+/// are only shown when the rules have them.
+fn match_clock(rules: &MatchRules, game_time: &GameTime, rounds: &Rounds) -> String {
+    let mut clock = match (game_time.overtime(), rules.time_limit) {
+        (Some(overtime), _) => format!("Golden goal +{}", overtime.as_secs()),
+        (None, Some(_)) => format!("{}", game_time.current_time().as_secs()),
+        (None, None) => format!("{}", game_time.elapsed().as_secs()),
+    };
+    if let Some(limit) = rules.score_limit {
+        clock = format!("{clock} | first to {limit}");
+    }
+    if rules.best_of > 1 {
+        clock = format!(
+            "Round {} ({}-{}) | {clock}",
+            rounds.played + 1,
+            rounds.player_1,
+            rounds.player_2
+        );
+    }
+    clock
+}
+
+fn display_game_time(
+    rules: Res<MatchRules>,
+    q_timer: Query<(&GameTime, &Rounds)>,
+    mut query: Query<&mut Text, With<TimeText>>,
+) {
+    if let Ok((game_timer, rounds)) = q_timer.single() {
         for mut text in &mut query {
-            text.0 = format!("{}", game_timer.current_time().as_secs());
+            text.0 = match_clock(&rules, game_timer, rounds);
         }
     }
 }
@@ -178,12 +256,14 @@ pub struct UiPlugin;
 
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_systems(Startup, (setup_points_ui, setup_time_ui))
+        app.add_systems(OnEnter(InMatch), (setup_points_ui, setup_time_ui))
+            .add_systems(OnEnter(MatchState::Kickoff), setup_kickoff_countdown)
+            .add_systems(OnEnter(MatchState::GoalScored), setup_goal_celebration)
             .add_systems(
                 Update,
                 (
-                    point_text_update_system,
-                    display_game_time,
+                    (point_text_update_system, display_game_time).run_if(in_state(InMatch)),
+                    update_kickoff_countdown.run_if(in_state(MatchState::Kickoff)),
                     spawn_game_end_notification,
                     update_display_timers,
                     despawn_entities_with_display_time,
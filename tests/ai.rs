@@ -0,0 +1,49 @@
+use bevy::{
+    app::App,
+    ecs::{entity::Entity, query::With},
+    state::state::NextState,
+};
+
+use slatchhammer::{
+    ai::{AiController, Difficulty},
+    headless_app, MatchState, Player1, Points, TICK_RATE,
+};
+
+fn start_match_with_ai_player1(difficulty: Difficulty) -> App {
+    let mut app = headless_app();
+    app.update();
+    app.world_mut()
+        .resource_mut::<NextState<MatchState>>()
+        .set(MatchState::Kickoff);
+    app.update();
+    let world = app.world_mut();
+    let player1 = world
+        .query_filtered::<Entity, With<Player1>>()
+        .single(world)
+        .unwrap();
+    world
+        .entity_mut(player1)
+        .insert(AiController::new(difficulty, 1));
+    app
+}
+
+fn points(app: &mut App) -> Points {
+    let world = app.world_mut();
+    *world.query::<&Points>().single(world).unwrap()
+}
+
+#[test]
+fn ai_when_opponent_stands_still_then_scores() {
+    // given
+    let mut app = start_match_with_ai_player1(Difficulty::Hard);
+
+    // when
+    for _ in 0..(TICK_RATE * 40.0) as usize {
+        app.update();
+    }
+
+    // then
+    let points = points(&mut app);
+    assert!(points.player_1 > 0);
+    assert_eq!(points.player_2, 0);
+}
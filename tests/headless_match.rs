@@ -0,0 +1,626 @@
+use bevy::{
+    app::App,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{Or, With},
+    },
+    math::{vec2, vec3, Vec3},
+    state::state::{NextState, State},
+    transform::components::Transform,
+};
+use bevy_rapier3d::{dynamics::Velocity, geometry::ColliderDisabled};
+
+use slatchhammer::{
+    arena::{ArenaDefinition, CurrentArena},
+    arena_features::{ArenaFeature, Boost, FeatureKind},
+    character::{Character, SelectedCharacters, ROSTER},
+    constants::{GAME_TIME, KICKOFF_COUNTDOWN_TIME},
+    goals::GoalType,
+    handicap::{Frozen, Handicap, HandicapEvent, HandicapRules, LockedGoal},
+    headless_app,
+    match_rules::{MatchRules, Rounds},
+    powerup::{pickup, Item, Pickup, PowerUp, SpeedBoost, StickyBall},
+    tackle::TackleState,
+    Ball, GameTime, MatchState, Player1, Player2, PlayerInput, PlayerType, Points, SprintState,
+    TICK_RATE,
+};
+
+fn start_match(app: &mut App) {
+    app.update();
+    app.world_mut()
+        .resource_mut::<NextState<MatchState>>()
+        .set(MatchState::Kickoff);
+    app.update();
+}
+
+fn run_seconds(app: &mut App, seconds: f32) {
+    for _ in 0..(seconds * TICK_RATE) as usize {
+        app.update();
+    }
+}
+
+fn match_state(app: &App) -> MatchState {
+    *app.world().resource::<State<MatchState>>().get()
+}
+
+/// Changes the input of the player marked with `T`
+fn set_input<T: Component>(app: &mut App, change: impl Fn(&mut PlayerInput)) {
+    let mut query = app
+        .world_mut()
+        .query_filtered::<&mut PlayerInput, With<T>>();
+    for mut input in query.iter_mut(app.world_mut()) {
+        change(&mut input);
+    }
+}
+
+/// A copy of the `C` of the only entity marked with `T`
+fn component<C: Component + Clone, T: Component>(app: &mut App) -> C {
+    let mut query = app.world_mut().query_filtered::<&C, With<T>>();
+    query.single(app.world()).unwrap().clone()
+}
+
+/// Player 1 collected `handicap`, which hits player 2
+fn hinder_player2(app: &mut App, handicap: Handicap) {
+    let world = app.world_mut();
+    let mut query = world.query_filtered::<Entity, With<Player1>>();
+    let collector = query.single(world).unwrap();
+    world.send_event(HandicapEvent {
+        handicap,
+        collector,
+    });
+}
+
+/// Rolls the ball fast into the goal `scorer` scores in
+fn shoot_at_goal(app: &mut App, scorer: PlayerType) {
+    let goal = {
+        let world = app.world_mut();
+        let mut query = world.query::<(&Transform, &GoalType)>();
+        query
+            .iter(world)
+            .find(|(_, goal)| match goal {
+                GoalType::First => scorer == PlayerType::First,
+                GoalType::Second => scorer == PlayerType::Second,
+            })
+            .unwrap()
+            .0
+            .translation
+    };
+    let direction = vec3(goal.x.signum(), 0.0, 0.0);
+    place::<Ball>(app, vec3(goal.x, 0.5, goal.z) - direction * 3.0);
+    let mut query = app
+        .world_mut()
+        .query_filtered::<&mut Velocity, With<Ball>>();
+    query.single_mut(app.world_mut()).unwrap().linvel = direction * 15.0;
+}
+
+fn points(app: &mut App) -> Points {
+    let world = app.world_mut();
+    *world.query::<&Points>().single(world).unwrap()
+}
+
+fn place<T: Component>(app: &mut App, translation: Vec3) {
+    let mut query = app
+        .world_mut()
+        .query_filtered::<(&mut Transform, &mut Velocity), With<T>>();
+    for (mut transform, mut velocity) in query.iter_mut(app.world_mut()) {
+        transform.translation = translation;
+        *velocity = Velocity::zero();
+    }
+}
+
+#[test]
+fn headless_app_when_kickoff_is_over_then_playing() {
+    // given
+    let mut app = headless_app();
+    start_match(&mut app);
+
+    // when
+    run_seconds(&mut app, KICKOFF_COUNTDOWN_TIME + 0.1);
+
+    // then
+    assert_eq!(match_state(&app), MatchState::Playing);
+}
+
+#[test]
+fn headless_app_when_game_time_runs_out_then_match_over() {
+    // given
+    let mut app = headless_app();
+    start_match(&mut app);
+    set_input::<Player1>(&mut app, |input| {
+        input.movement = vec3(1.0, 0.0, 0.0);
+        input.sprint = true;
+    });
+
+    // when
+    run_seconds(&mut app, KICKOFF_COUNTDOWN_TIME + GAME_TIME as f32 + 0.1);
+
+    // then
+    assert_eq!(match_state(&app), MatchState::MatchOver);
+}
+
+#[test]
+fn headless_app_when_run_twice_with_same_input_then_same_result() {
+    // given
+    let mut first = headless_app();
+    let mut second = headless_app();
+
+    // when
+    for app in [&mut first, &mut second] {
+        start_match(app);
+        set_input::<Player1>(app, |input| {
+            input.movement = vec3(1.0, 0.0, 0.3);
+            input.sprint = true;
+        });
+        run_seconds(app, KICKOFF_COUNTDOWN_TIME + 5.0);
+    }
+
+    // then
+    assert_eq!(
+        component::<Transform, Ball>(&mut first).translation,
+        component::<Transform, Ball>(&mut second).translation
+    );
+}
+
+#[test]
+fn headless_app_when_player_stops_sprinting_then_sprint_regenerates() {
+    // given
+    let mut app = headless_app();
+    start_match(&mut app);
+    set_input::<Player1>(&mut app, |input| {
+        input.movement = vec3(0.0, 0.0, 1.0);
+        input.sprint = true;
+    });
+    run_seconds(&mut app, KICKOFF_COUNTDOWN_TIME + 4.0);
+    let after_sprint = component::<SprintState, Player1>(&mut app).remaining();
+
+    // when
+    set_input::<Player1>(&mut app, |input| {
+        input.movement = Vec3::ZERO;
+        input.sprint = false;
+    });
+    run_seconds(&mut app, 6.0);
+
+    // then
+    assert!(component::<SprintState, Player1>(&mut app).remaining() > after_sprint);
+}
+
+#[test]
+fn headless_app_when_charged_kick_released_next_to_ball_then_ball_flies_forward() {
+    // given
+    let mut app = headless_app();
+    start_match(&mut app);
+    run_seconds(&mut app, KICKOFF_COUNTDOWN_TIME + 0.1);
+    place::<Ball>(&mut app, vec3(0.0, 0.5, 0.0));
+    place::<Player1>(&mut app, vec3(-1.5, 1.0, 0.0));
+    // Face the ball
+    set_input::<Player1>(&mut app, |input| {
+        input.movement = vec3(0.01, 0.0, 0.0);
+        input.sprint = true;
+    });
+    app.update();
+    set_input::<Player1>(&mut app, |input| {
+        input.movement = Vec3::ZERO;
+        input.sprint = false;
+    });
+    set_input::<Player1>(&mut app, |input| input.kick = true);
+    run_seconds(&mut app, 1.0);
+
+    // when
+    set_input::<Player1>(&mut app, |input| input.kick = false);
+    run_seconds(&mut app, 0.1);
+
+    // then
+    assert!(component::<Velocity, Ball>(&mut app).linvel.x > 10.0);
+}
+
+#[test]
+fn headless_app_when_player_dashes_into_opponent_then_opponent_stunned() {
+    // given
+    let mut app = headless_app();
+    start_match(&mut app);
+    run_seconds(&mut app, KICKOFF_COUNTDOWN_TIME + 0.1);
+    place::<Player1>(&mut app, vec3(-2.5, 1.0, 0.0));
+    place::<Player2>(&mut app, vec3(0.0, 1.0, 0.0));
+    // Face the opponent
+    set_input::<Player1>(&mut app, |input| {
+        input.movement = vec3(0.01, 0.0, 0.0);
+        input.sprint = true;
+    });
+    app.update();
+    set_input::<Player1>(&mut app, |input| {
+        input.movement = Vec3::ZERO;
+        input.sprint = true;
+    });
+
+    // when
+    set_input::<Player1>(&mut app, |input| input.tackle = true);
+    run_seconds(&mut app, 0.2);
+
+    // then
+    assert!(component::<TackleState, Player2>(&mut app).is_stunned());
+}
+
+#[test]
+fn headless_app_when_characters_selected_then_players_spawn_with_them() {
+    // given
+    let mut app = headless_app();
+    app.insert_resource(SelectedCharacters([1, 2]));
+
+    // when
+    start_match(&mut app);
+
+    // then
+    let world = app.world_mut();
+    let mut query = world.query_filtered::<(&Character, &SprintState), With<Player2>>();
+    let (character, sprint) = query.single(world).unwrap();
+    assert_eq!(*character, ROSTER[2]);
+    assert_eq!(sprint.remaining(), ROSTER[2].stamina);
+}
+
+#[test]
+fn headless_app_when_player_runs_over_pickup_then_boosted_and_pickup_gone() {
+    // given
+    let mut app = headless_app();
+    start_match(&mut app);
+    run_seconds(&mut app, KICKOFF_COUNTDOWN_TIME + 0.1);
+    app.world_mut().spawn(pickup(
+        Item::PowerUp(PowerUp::SpeedBoost),
+        vec3(-8.0, 0.8, 0.0),
+    ));
+
+    // when
+    set_input::<Player1>(&mut app, |input| {
+        input.movement = Vec3::X;
+        input.sprint = true;
+    });
+    run_seconds(&mut app, 1.0);
+
+    // then
+    let world = app.world_mut();
+    let mut pickups = world.query_filtered::<(), With<Pickup>>();
+    assert_eq!(pickups.iter(world).count(), 0);
+    let mut query = world.query_filtered::<&Character, (With<Player1>, With<SpeedBoost>)>();
+    let character = query.single(world).unwrap();
+    assert!(character.speed > ROSTER[0].speed);
+}
+
+#[test]
+fn headless_app_when_both_players_touch_pickup_in_same_frame_then_only_one_gets_it() {
+    // given
+    let mut app = headless_app();
+    start_match(&mut app);
+    run_seconds(&mut app, KICKOFF_COUNTDOWN_TIME + 0.1);
+    place::<Ball>(&mut app, vec3(0.0, 0.5, 8.0));
+    place::<Player1>(&mut app, vec3(-0.5, 1.0, 0.0));
+    place::<Player2>(&mut app, vec3(0.5, 1.0, 0.0));
+
+    // when
+    app.world_mut().spawn(pickup(
+        Item::PowerUp(PowerUp::SpeedBoost),
+        vec3(0.0, 0.8, 0.0),
+    ));
+    run_seconds(&mut app, 0.1);
+
+    // then
+    let world = app.world_mut();
+    let mut pickups = world.query_filtered::<(), With<Pickup>>();
+    assert_eq!(pickups.iter(world).count(), 0);
+    let mut boosted = world.query_filtered::<(), With<SpeedBoost>>();
+    assert_eq!(boosted.iter(world).count(), 1);
+}
+
+#[test]
+fn headless_app_when_sticky_player_charges_kick_on_the_run_then_keeps_ball_until_release() {
+    // given
+    let mut app = headless_app();
+    start_match(&mut app);
+    run_seconds(&mut app, KICKOFF_COUNTDOWN_TIME + 0.1);
+    place::<Ball>(&mut app, vec3(0.0, 0.5, 0.0));
+    place::<Player1>(&mut app, vec3(-1.5, 1.0, 0.0));
+    let player = {
+        let world = app.world_mut();
+        let mut query = world.query_filtered::<Entity, With<Player1>>();
+        query.single(world).unwrap()
+    };
+    app.world_mut().entity_mut(player).insert(StickyBall(10.0));
+    // Face the ball
+    set_input::<Player1>(&mut app, |input| input.movement = vec3(0.01, 0.0, 0.0));
+    app.update();
+    set_input::<Player1>(&mut app, |input| input.movement = Vec3::ZERO);
+    run_seconds(&mut app, 0.5);
+
+    // when
+    set_input::<Player1>(&mut app, |input| {
+        input.movement = Vec3::Z;
+        input.kick = true;
+    });
+    run_seconds(&mut app, 1.0);
+    let held = component::<Transform, Ball>(&mut app).translation;
+    let holder = component::<Transform, Player1>(&mut app).translation;
+    set_input::<Player1>(&mut app, |input| input.kick = false);
+    run_seconds(&mut app, 0.1);
+
+    // then
+    assert!(held.distance(holder) < 2.0);
+    assert!(component::<Velocity, Ball>(&mut app).linvel.z > 10.0);
+}
+
+#[test]
+fn headless_app_when_player_runs_over_handicap_then_opponent_hindered() {
+    // given
+    let mut app = headless_app();
+    start_match(&mut app);
+    run_seconds(&mut app, KICKOFF_COUNTDOWN_TIME + 0.1);
+    app.world_mut().spawn(pickup(
+        Item::Handicap(Handicap::Frozen),
+        vec3(-8.0, 0.8, 0.0),
+    ));
+
+    // when
+    set_input::<Player1>(&mut app, |input| {
+        input.movement = Vec3::X;
+        input.sprint = true;
+    });
+    run_seconds(&mut app, 0.5);
+
+    // then
+    let world = app.world_mut();
+    let mut frozen = world.query_filtered::<(), (With<Player2>, With<Frozen>)>();
+    assert_eq!(frozen.iter(world).count(), 1);
+    let mut query = world.query_filtered::<(), (With<Player1>, With<Frozen>)>();
+    assert_eq!(query.iter(world).count(), 0);
+    assert!(component::<TackleState, Player2>(&mut app).is_stunned());
+}
+
+#[test]
+fn headless_app_when_controls_inverted_then_player_moves_the_other_way() {
+    // given
+    let mut app = headless_app();
+    start_match(&mut app);
+    run_seconds(&mut app, KICKOFF_COUNTDOWN_TIME + 0.1);
+    set_input::<Player2>(&mut app, |input| input.movement = Vec3::X);
+
+    // when
+    hinder_player2(&mut app, Handicap::InvertedControls);
+    run_seconds(&mut app, 0.5);
+
+    // then
+    assert!(component::<Velocity, Player2>(&mut app).linvel.x < -1.0);
+}
+
+#[test]
+fn headless_app_when_sprint_drained_then_bar_empty_and_exhausted() {
+    // given
+    let mut app = headless_app();
+    start_match(&mut app);
+    run_seconds(&mut app, KICKOFF_COUNTDOWN_TIME + 0.1);
+
+    // when
+    hinder_player2(&mut app, Handicap::DrainedSprint);
+    app.update();
+
+    // then
+    let sprint = component::<SprintState, Player2>(&mut app);
+    assert_eq!(sprint.remaining(), 0.0);
+    assert!(sprint.is_exhausted());
+    assert!(!sprint.is_available());
+}
+
+#[test]
+fn headless_app_when_goal_locked_then_ball_in_it_does_not_score() {
+    // given
+    let mut app = headless_app();
+    start_match(&mut app);
+    run_seconds(&mut app, KICKOFF_COUNTDOWN_TIME + 0.1);
+    hinder_player2(&mut app, Handicap::LockedGoal);
+    app.update();
+
+    // when
+    shoot_at_goal(&mut app, PlayerType::Second);
+    run_seconds(&mut app, 1.0);
+
+    // then
+    assert_eq!(points(&mut app).player_2, 0);
+    assert_eq!(match_state(&app), MatchState::Playing);
+}
+
+#[test]
+fn headless_app_when_goal_lock_runs_out_then_goal_scores_again() {
+    // given
+    let mut app = headless_app();
+    app.insert_resource(HandicapRules {
+        goal_lock_time: 1.0,
+        ..Default::default()
+    });
+    start_match(&mut app);
+    run_seconds(&mut app, KICKOFF_COUNTDOWN_TIME + 0.1);
+    hinder_player2(&mut app, Handicap::LockedGoal);
+    run_seconds(&mut app, 1.2);
+
+    // when
+    shoot_at_goal(&mut app, PlayerType::Second);
+    run_seconds(&mut app, 1.0);
+
+    // then
+    let world = app.world_mut();
+    let mut locked = world.query_filtered::<(), Or<(With<LockedGoal>, With<ColliderDisabled>)>>();
+    assert_eq!(locked.iter(world).count(), 0);
+    assert_eq!(points(&mut app).player_2, 1);
+}
+
+#[test]
+fn headless_app_when_arena_changed_then_field_rebuilt_and_players_start_on_its_spawns() {
+    // given
+    let pocket = std::fs::read_to_string("assets/arenas/pocket.arena.ron").unwrap();
+    let arena = ArenaDefinition::parse(pocket.as_bytes()).unwrap();
+    let mut app = headless_app();
+    app.update();
+    app.insert_resource(CurrentArena(arena.clone()));
+
+    // when
+    start_match(&mut app);
+
+    // then
+    let world = app.world_mut();
+    let mut query = world.query_filtered::<&Transform, With<Player1>>();
+    let start = query.single(world).unwrap().translation;
+    assert_eq!(
+        (start.x, start.z),
+        (arena.spawns.player1.x, arena.spawns.player1.z)
+    );
+    let mut goals = world.query::<&GoalType>();
+    assert_eq!(goals.iter(world).count(), 2);
+}
+
+#[test]
+fn headless_app_when_ball_rolls_into_bumper_then_bounced_back_harder() {
+    // given
+    let mut app = headless_app();
+    app.update();
+    let mut arena = ArenaDefinition::default();
+    arena.features.push(ArenaFeature {
+        center: vec3(0.0, 1.0, 6.0),
+        kind: FeatureKind::Bumper {
+            radius: 1.0,
+            strength: 20.0,
+        },
+    });
+    app.insert_resource(CurrentArena(arena));
+    start_match(&mut app);
+    run_seconds(&mut app, KICKOFF_COUNTDOWN_TIME + 0.1);
+    place::<Ball>(&mut app, vec3(0.0, 0.5, 2.0));
+
+    // when
+    let mut query = app
+        .world_mut()
+        .query_filtered::<&mut Velocity, With<Ball>>();
+    query.single_mut(app.world_mut()).unwrap().linvel = vec3(0.0, 0.0, 8.0);
+    run_seconds(&mut app, 0.6);
+
+    // then
+    assert!(component::<Velocity, Ball>(&mut app).linvel.z < -10.0);
+}
+
+#[test]
+fn headless_app_when_player_runs_over_refill_pad_then_sprint_full() {
+    // given
+    let mut app = headless_app();
+    start_match(&mut app);
+    run_seconds(&mut app, KICKOFF_COUNTDOWN_TIME + 0.1);
+    set_input::<Player1>(&mut app, |input| {
+        input.movement = Vec3::NEG_Z;
+        input.sprint = true;
+    });
+    run_seconds(&mut app, 1.0);
+    let drained = component::<SprintState, Player1>(&mut app).remaining();
+    let mut arena = ArenaDefinition::default();
+    arena.features.push(ArenaFeature {
+        center: vec3(-7.0, 0.5, 0.0),
+        kind: FeatureKind::BoostPad {
+            size: vec2(2.0, 30.0),
+            boost: Boost::Refill,
+        },
+    });
+    app.insert_resource(CurrentArena(arena));
+
+    // when
+    set_input::<Player1>(&mut app, |input| {
+        input.movement = Vec3::X;
+        input.sprint = false;
+    });
+    run_seconds(&mut app, 1.0);
+
+    // then
+    assert!(drained < ROSTER[0].stamina);
+    assert_eq!(
+        component::<SprintState, Player1>(&mut app).remaining(),
+        ROSTER[0].stamina
+    );
+}
+
+#[test]
+fn headless_app_when_ball_leaves_the_field_then_put_back_inside() {
+    // given
+    let mut app = headless_app();
+    start_match(&mut app);
+    run_seconds(&mut app, KICKOFF_COUNTDOWN_TIME + 0.1);
+    let arena = ArenaDefinition::default();
+    let half_size = arena.half_size();
+
+    // when
+    place::<Ball>(&mut app, vec3(half_size.x + 5.0, 1.0, 3.0));
+    app.update();
+
+    // then
+    let position = component::<Transform, Ball>(&mut app).translation;
+    assert!(position.x.abs() < half_size.x);
+    assert!((position.z - 3.0).abs() < 0.1);
+}
+
+#[test]
+fn headless_app_when_tied_at_time_limit_with_golden_goal_then_overtime() {
+    // given
+    let mut app = headless_app();
+    app.insert_resource(MatchRules {
+        time_limit: Some(1),
+        golden_goal: true,
+        ..Default::default()
+    });
+    start_match(&mut app);
+
+    // when
+    run_seconds(&mut app, KICKOFF_COUNTDOWN_TIME + 2.0);
+
+    // then
+    assert_eq!(match_state(&app), MatchState::Playing);
+    let world = app.world_mut();
+    let mut query = world.query::<&GameTime>();
+    assert!(query.single(world).unwrap().overtime().is_some());
+}
+
+#[test]
+fn headless_app_when_round_of_best_of_three_drawn_then_next_round_kicks_off() {
+    // given
+    let mut app = headless_app();
+    app.insert_resource(MatchRules {
+        time_limit: Some(1),
+        best_of: 3,
+        ..Default::default()
+    });
+    start_match(&mut app);
+
+    // when
+    run_seconds(&mut app, KICKOFF_COUNTDOWN_TIME + 1.1);
+
+    // then
+    assert_eq!(match_state(&app), MatchState::Kickoff);
+    let world = app.world_mut();
+    let mut query = world.query::<(&GameTime, &Rounds)>();
+    let (time, rounds) = query.single(world).unwrap();
+    assert_eq!(rounds.played, 1);
+    assert_eq!(time.current_time().as_secs(), 1);
+}
+
+#[test]
+fn headless_app_when_round_ends_then_goal_lock_is_lifted() {
+    // given
+    let mut app = headless_app();
+    app.insert_resource(MatchRules {
+        time_limit: Some(1),
+        best_of: 3,
+        ..Default::default()
+    });
+    start_match(&mut app);
+    run_seconds(&mut app, KICKOFF_COUNTDOWN_TIME + 0.1);
+    hinder_player2(&mut app, Handicap::LockedGoal);
+
+    // when
+    run_seconds(&mut app, 1.0);
+
+    // then
+    assert_eq!(match_state(&app), MatchState::Kickoff);
+    let world = app.world_mut();
+    let mut locked = world.query_filtered::<(), Or<(With<LockedGoal>, With<ColliderDisabled>)>>();
+    assert_eq!(locked.iter(world).count(), 0);
+}
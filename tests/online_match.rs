@@ -0,0 +1,276 @@
+use std::{
+    net::{SocketAddr, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::math::Vec3;
+use slatchhammer::{
+    arena::ArenaDefinition,
+    headless_app,
+    network::{decode, encode, ClientMsg, LobbyInfo, ServerMsg, Snapshot},
+    server::Server,
+    MatchState, PlayerInput, PlayerType,
+};
+use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+
+type Client = WebSocket<MaybeTlsStream<TcpStream>>;
+
+/// Servers play on the built-in arena
+fn player1_start_x() -> f32 {
+    ArenaDefinition::default().spawns.player1.x
+}
+
+/// Steps a server on a free localhost port as fast as possible until dropped
+struct TestServer {
+    address: SocketAddr,
+    stop: Arc<AtomicBool>,
+}
+
+impl TestServer {
+    fn start() -> Self {
+        let (tx, rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_server = stop.clone();
+        thread::spawn(move || {
+            let mut server = Server::bind("127.0.0.1:0", headless_app).unwrap();
+            tx.send(server.local_addr().unwrap()).unwrap();
+            while !stop_server.load(Ordering::Relaxed) {
+                server.step();
+                thread::sleep(Duration::from_micros(100));
+            }
+        });
+        TestServer {
+            address: rx.recv().unwrap(),
+            stop,
+        }
+    }
+
+    fn connect(&self) -> Client {
+        let (client, _) = tungstenite::connect(format!("ws://{}", self.address)).unwrap();
+        client
+    }
+
+    /// Connects a client that sends `msg` and returns once it is in a lobby
+    fn join(&self, msg: ClientMsg) -> (Client, LobbyInfo) {
+        let mut client = self.connect();
+        send(&mut client, msg);
+        let info = wait_for(&mut client, |msg| match msg {
+            ServerMsg::LobbyInfo(info) => Some(info),
+            _ => None,
+        });
+        (client, info)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn send(client: &mut Client, msg: ClientMsg) {
+    client.send(Message::binary(encode(&msg))).unwrap();
+}
+
+/// Reads messages until `f` picks one, panics if none does within a few thousand messages
+fn wait_for<T>(client: &mut Client, f: impl Fn(ServerMsg) -> Option<T>) -> T {
+    for _ in 0..5000 {
+        if let Message::Binary(bytes) = client.read().unwrap() {
+            if let Some(found) = f(decode(&bytes).unwrap()) {
+                return found;
+            }
+        }
+    }
+    panic!("the expected message never arrived");
+}
+
+fn wait_for_snapshot(client: &mut Client, f: impl Fn(&Snapshot) -> bool) -> Snapshot {
+    wait_for(client, |msg| match msg {
+        ServerMsg::Snapshot(snapshot) if f(&snapshot) => Some(*snapshot),
+        _ => None,
+    })
+}
+
+fn start_match(server: &TestServer) -> (Client, Client) {
+    let (mut client_1, info) = server.join(ClientMsg::CreateLobby);
+    let (mut client_2, _) = server.join(ClientMsg::JoinLobby { code: info.code });
+    send(&mut client_1, ClientMsg::Ready);
+    send(&mut client_2, ClientMsg::Ready);
+    wait_for_snapshot(&mut client_1, |s| s.state == MatchState::Kickoff);
+    (client_1, client_2)
+}
+
+fn player_1_x(snapshot: &Snapshot) -> Option<f32> {
+    snapshot.world.as_ref().map(|w| w.player_1.translation.x)
+}
+
+#[test]
+fn server_when_two_clients_quick_join_then_same_lobby() {
+    // given
+    let server = TestServer::start();
+
+    // when
+    let (_client_1, info_1) = server.join(ClientMsg::QuickJoin);
+    let (_client_2, info_2) = server.join(ClientMsg::QuickJoin);
+
+    // then
+    assert_eq!(info_1.code, info_2.code);
+    assert_eq!(info_1.you, PlayerType::First);
+    assert_eq!(info_2.you, PlayerType::Second);
+}
+
+#[test]
+fn server_when_only_one_player_is_ready_then_no_match() {
+    // given
+    let server = TestServer::start();
+    let (mut client_1, info) = server.join(ClientMsg::CreateLobby);
+    let (_client_2, _) = server.join(ClientMsg::JoinLobby { code: info.code });
+
+    // when
+    send(&mut client_1, ClientMsg::Ready);
+
+    // then
+    let info = wait_for(&mut client_1, |msg| match msg {
+        ServerMsg::LobbyInfo(info) if info.ready[0] => Some(info),
+        ServerMsg::Snapshot(_) => panic!("the match started without player 2"),
+        _ => None,
+    });
+    assert_eq!(info.ready, [true, false]);
+}
+
+#[test]
+fn server_when_both_players_are_ready_then_match_starts() {
+    // given
+    let server = TestServer::start();
+
+    // when
+    let (mut client_1, _client_2) = start_match(&server);
+
+    // then
+    let snapshot = wait_for_snapshot(&mut client_1, |_| true);
+    assert!(snapshot.world.is_some());
+}
+
+#[test]
+fn server_when_client_sends_input_then_its_player_moves() {
+    // given
+    let server = TestServer::start();
+    let (mut client_1, _client_2) = start_match(&server);
+
+    // when
+    let input = PlayerInput {
+        movement: Vec3::X,
+        sprint: false,
+        kick: false,
+        tackle: false,
+    };
+    send(&mut client_1, ClientMsg::Input { tick: 0, input });
+
+    // then
+    wait_for_snapshot(&mut client_1, |s| {
+        player_1_x(s).is_some_and(|x| x > player1_start_x() + 2.0)
+    });
+}
+
+#[test]
+fn server_when_client_sends_input_then_snapshot_acknowledges_its_tick() {
+    // given
+    let server = TestServer::start();
+    let (mut client_1, _client_2) = start_match(&server);
+
+    // when
+    send(
+        &mut client_1,
+        ClientMsg::Input {
+            tick: 42,
+            input: PlayerInput::default(),
+        },
+    );
+
+    // then
+    wait_for_snapshot(&mut client_1, |s| s.input_ticks[0] == Some(42));
+}
+
+#[test]
+fn server_when_two_lobbies_play_then_matches_are_isolated() {
+    // given
+    let server = TestServer::start();
+    let (mut client_a, _client_a2) = start_match(&server);
+    let (mut client_b, _client_b2) = start_match(&server);
+
+    // when
+    let input = PlayerInput {
+        movement: Vec3::X,
+        sprint: false,
+        kick: false,
+        tackle: false,
+    };
+    send(&mut client_a, ClientMsg::Input { tick: 0, input });
+    wait_for_snapshot(&mut client_a, |s| {
+        player_1_x(s).is_some_and(|x| x > player1_start_x() + 2.0)
+    });
+
+    // then
+    let snapshot = wait_for_snapshot(&mut client_b, |s| s.state == MatchState::Playing);
+    assert!(player_1_x(&snapshot).is_some_and(|x| x < player1_start_x() + 0.5));
+}
+
+#[test]
+fn server_when_joining_unknown_code_then_join_fails() {
+    // given
+    let server = TestServer::start();
+    let mut client = server.connect();
+
+    // when
+    send(
+        &mut client,
+        ClientMsg::JoinLobby {
+            code: "ZZZZ".into(),
+        },
+    );
+
+    // then
+    let reason = wait_for(&mut client, |msg| match msg {
+        ServerMsg::JoinFailed { reason } => Some(reason),
+        _ => None,
+    });
+    assert_eq!(reason, "There is no lobby ZZZZ");
+}
+
+#[test]
+fn server_when_a_connection_stalls_its_handshake_then_others_still_join() {
+    // given
+    let server = TestServer::start();
+    let _idle = TcpStream::connect(server.address).unwrap();
+    thread::sleep(Duration::from_millis(50));
+
+    // when
+    let start = Instant::now();
+    let (_client, info) = server.join(ClientMsg::QuickJoin);
+
+    // then
+    assert_eq!(info.you, PlayerType::First);
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn server_when_opponent_disconnects_then_lobby_is_closed() {
+    // given
+    let server = TestServer::start();
+    let (mut client_1, mut client_2) = start_match(&server);
+
+    // when
+    client_2.close(None).unwrap();
+    drop(client_2);
+
+    // then
+    wait_for(&mut client_1, |msg| match msg {
+        ServerMsg::LobbyClosed => Some(()),
+        _ => None,
+    });
+}
@@ -0,0 +1,176 @@
+use bevy::{
+    app::App,
+    ecs::{component::Component, query::With},
+    math::vec3,
+    state::state::NextState,
+    transform::components::Transform,
+};
+use bevy_rapier3d::dynamics::Velocity;
+use slatchhammer::{
+    arena::{ArenaDefinition, CurrentArena},
+    headless_app,
+    network::{BodySnapshot, WorldSnapshot},
+    rollback::{LoopbackTransport, RollbackSession, UdpTransport, MAX_PREDICTION_FRAMES},
+    Ball, GameTime, MatchState, Player1, Player2, PlayerInput, PlayerType, Points, TICK_RATE,
+};
+
+/// Long enough for the kickoff countdown and a few seconds of play
+const FRAMES: u32 = (TICK_RATE * 7.0) as u32;
+/// The players stand still at the end, so the last predictions are right
+const IDLE_FRAMES: u32 = 30;
+
+/// Changes direction and sprint often, so predictions of the other peer are often wrong
+fn scripted_input(player: PlayerType, frame: u32) -> PlayerInput {
+    if frame >= FRAMES - IDLE_FRAMES {
+        return PlayerInput::default();
+    }
+    let offset = match player {
+        PlayerType::First => 0.0,
+        PlayerType::Second => 2.0,
+    };
+    let turn = (frame / 13) as f32 + offset;
+    PlayerInput {
+        movement: vec3(turn.cos(), 0.0, turn.sin()),
+        sprint: (frame / 20).is_multiple_of(2),
+        kick: (frame / 30).is_multiple_of(3),
+        tackle: (frame / 45).is_multiple_of(4),
+    }
+}
+
+fn set_input<T: Component>(app: &mut App, input: PlayerInput) {
+    let world = app.world_mut();
+    let mut query = world.query_filtered::<&mut PlayerInput, With<T>>();
+    for mut current in query.iter_mut(world) {
+        *current = input;
+    }
+}
+
+fn body<T: Component>(app: &mut App) -> BodySnapshot {
+    let world = app.world_mut();
+    let mut query = world.query_filtered::<(&Transform, &Velocity), With<T>>();
+    let (transform, velocity) = query.single(world).unwrap();
+    BodySnapshot::new(transform, velocity)
+}
+
+/// A match on the Funhouse arena, whose moving platform crosses the kickoff spot
+fn funhouse_app() -> App {
+    let funhouse = std::fs::read_to_string("assets/arenas/funhouse.arena.ron").unwrap();
+    let mut app = headless_app();
+    app.insert_resource(CurrentArena(
+        ArenaDefinition::parse(funhouse.as_bytes()).unwrap(),
+    ));
+    app
+}
+
+/// The same match simulated in a single app that knows both inputs right away
+fn reference_world(new_match: fn() -> App) -> WorldSnapshot {
+    let mut app = new_match();
+    app.update();
+    app.world_mut()
+        .resource_mut::<NextState<MatchState>>()
+        .set(MatchState::Kickoff);
+    for frame in 0..FRAMES {
+        set_input::<Player1>(&mut app, scripted_input(PlayerType::First, frame));
+        set_input::<Player2>(&mut app, scripted_input(PlayerType::Second, frame));
+        app.update();
+    }
+    let world = app.world_mut();
+    let points = *world.query::<&Points>().single(world).unwrap();
+    let game_time_left = world
+        .query::<&GameTime>()
+        .single(world)
+        .unwrap()
+        .current_time();
+    WorldSnapshot {
+        player_1: body::<Player1>(&mut app),
+        player_2: body::<Player2>(&mut app),
+        ball: body::<Ball>(&mut app),
+        points,
+        game_time_left,
+    }
+}
+
+fn run_until(session: &mut RollbackSession<LoopbackTransport>, frame: u32) {
+    if session.frame() < frame {
+        let input = scripted_input(session.local(), session.frame());
+        session.advance(input);
+    }
+}
+
+#[test]
+fn rollback_when_inputs_arrive_late_then_both_peers_match_the_reference() {
+    // given
+    let (transport_1, transport_2) = LoopbackTransport::pair(4);
+    let mut peer_1 = RollbackSession::new(headless_app, PlayerType::First, transport_1);
+    let mut peer_2 = RollbackSession::new(headless_app, PlayerType::Second, transport_2);
+
+    // when
+    while peer_1.frame() < FRAMES || peer_2.frame() < FRAMES {
+        run_until(&mut peer_1, FRAMES);
+        run_until(&mut peer_2, FRAMES);
+    }
+
+    // then
+    assert!(peer_1.rollbacks() > 0);
+    assert!(peer_2.rollbacks() > 0);
+    let reference = reference_world(headless_app);
+    assert_eq!(peer_1.snapshot().world, Some(reference.clone()));
+    assert_eq!(peer_2.snapshot().world, Some(reference));
+}
+
+#[test]
+fn rollback_when_arena_has_moving_platforms_then_both_peers_match_the_reference() {
+    // given
+    let (transport_1, transport_2) = LoopbackTransport::pair(4);
+    let mut peer_1 = RollbackSession::new(funhouse_app, PlayerType::First, transport_1);
+    let mut peer_2 = RollbackSession::new(funhouse_app, PlayerType::Second, transport_2);
+
+    // when
+    while peer_1.frame() < FRAMES || peer_2.frame() < FRAMES {
+        run_until(&mut peer_1, FRAMES);
+        run_until(&mut peer_2, FRAMES);
+    }
+
+    // then
+    assert!(peer_1.rollbacks() > 0);
+    let reference = reference_world(funhouse_app);
+    assert_eq!(peer_1.snapshot().world, Some(reference.clone()));
+    assert_eq!(peer_2.snapshot().world, Some(reference));
+}
+
+#[test]
+fn rollback_when_peer_is_silent_then_stops_after_max_prediction() {
+    // given
+    let (transport, _silent_peer) = LoopbackTransport::pair(0);
+    let mut session = RollbackSession::new(headless_app, PlayerType::First, transport);
+
+    // when
+    let advanced = (0..20)
+        .filter(|_| session.advance(PlayerInput::default()))
+        .count();
+
+    // then
+    assert_eq!(advanced as u32, MAX_PREDICTION_FRAMES);
+    assert_eq!(session.frame(), MAX_PREDICTION_FRAMES);
+}
+
+#[test]
+fn rollback_when_peers_talk_over_udp_then_both_advance() {
+    // given
+    let address_1 = "127.0.0.1:47801";
+    let address_2 = "127.0.0.1:47802";
+    let transport_1 = UdpTransport::bind(address_1, address_2).unwrap();
+    let transport_2 = UdpTransport::bind(address_2, address_1).unwrap();
+    let mut peer_1 = RollbackSession::new(headless_app, PlayerType::First, transport_1);
+    let mut peer_2 = RollbackSession::new(headless_app, PlayerType::Second, transport_2);
+
+    // when
+    for _ in 0..(MAX_PREDICTION_FRAMES * 10) {
+        peer_1.advance(PlayerInput::default());
+        peer_2.advance(PlayerInput::default());
+    }
+
+    // then
+    assert!(peer_1.frame() > MAX_PREDICTION_FRAMES * 2);
+    assert!(peer_2.frame() > MAX_PREDICTION_FRAMES * 2);
+}